@@ -5,21 +5,59 @@
 //! pre-pulled (and in the future, pinned) before a new image root
 //! is considered ready.
 
+use std::future::Future;
+use std::num::NonZeroUsize;
+use std::time::Duration;
+
 use anyhow::{Context, Result};
-use camino::Utf8Path;
+use camino::{Utf8Path, Utf8PathBuf};
 use cap_std_ext::cap_std::fs::Dir;
 use cap_std_ext::dirext::CapStdExtDirExt;
 use fn_error_context::context;
+use futures_util::StreamExt;
 use ostree_ext::containers_image_proxy;
 use ostree_ext::ostree::Deployment;
+use serde::{Deserialize, Serialize};
 
 use crate::imgstorage::PullMode;
+use crate::progress_jsonl::{Event, ProgressWriter, SubTaskStep};
+use crate::spec::BoundImageStatus;
 use crate::store::Storage;
 
 /// The path in a root for bound images; this directory should only contain
-/// symbolic links to `.container` or `.image` files.
+/// symbolic links to `.container`, `.image`, `.pod` or `.kube` files.
 const BOUND_IMAGE_DIR: &str = "usr/lib/bootc/bound-images.d";
 
+/// The directory (in `/usr/lib`, `/usr/local/lib`, `/etc` or `/run`) with the
+/// configuration files for bound images.
+const CONFIG_DIR: &str = "bootc/bound-images.conf.d";
+/// The default number of bound images fetched in parallel.
+const DEFAULT_PULL_CONCURRENCY: usize = 4;
+/// The number of attempts made to fetch each bound image.
+const PULL_ATTEMPTS: u32 = 3;
+/// Substrings of (lowercased) fetch errors which are worth retrying, as the
+/// network or registry may be temporarily unavailable.
+const TRANSIENT_PULL_ERRORS: &[&str] = &[
+    "timeout",
+    "timed out",
+    "connection refused",
+    "connection reset",
+    "no route to host",
+    "network is unreachable",
+    "temporary failure in name resolution",
+    "unexpected eof",
+    "too many requests",
+    "500 internal server error",
+    "502 bad gateway",
+    "503 service unavailable",
+    "504 gateway timeout",
+];
+/// The delay before retrying a failed fetch; this doubles after each attempt.
+const PULL_RETRY_DELAY: Duration = Duration::from_secs(5);
+/// The directory (relative to the physical root) holding the digests of the bound
+/// images of each deployment, as resolved when it was staged.
+const RESOLVED_BOUND_IMAGES_DIR: &str = "ostree/bootc/bound-images";
+
 /// A subset of data parsed from a `.image` or `.container` file with
 /// the minimal information necessary to fetch the image.
 ///
@@ -31,16 +69,312 @@ pub(crate) struct BoundImage {
     pub(crate) auth_file: Option<String>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+/// The configuration for bound images, e.g. in
+/// `/etc/bootc/bound-images.conf.d/10-fetch.toml`; values in later files
+/// override earlier ones.
+#[derive(Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub(crate) struct Config {
+    /// The number of bound images fetched in parallel
+    concurrency: Option<NonZeroUsize>,
+    /// Whether a failure to resolve and record the digests of the bound images
+    /// of a deployment is fatal
+    require_digest: Option<bool>,
+    /// An OCI image layout directory from which digest-pinned images are copied
+    /// if present, instead of fetching them
+    oci_mirror: Option<Utf8PathBuf>,
+}
+
+impl Config {
+    /// Apply any values in other, overriding any existing values in `self`.
+    fn merge(&mut self, other: Self) {
+        if other.concurrency.is_some() {
+            self.concurrency = other.concurrency;
+        }
+        if other.require_digest.is_some() {
+            self.require_digest = other.require_digest;
+        }
+        if other.oci_mirror.is_some() {
+            self.oci_mirror = other.oci_mirror;
+        }
+    }
+
+    /// The number of bound images to fetch in parallel.
+    fn concurrency(&self) -> usize {
+        self.concurrency
+            .map_or(DEFAULT_PULL_CONCURRENCY, NonZeroUsize::get)
+    }
+}
+
+/// Load the bound images configuration, merging all found configuration files.
+#[context("Loading bound images configuration")]
+pub(crate) fn load_config() -> Result<Config> {
+    const SYSTEMD_CONVENTIONAL_BASES: &[&str] = &["/usr/lib", "/usr/local/lib", "/etc", "/run"];
+    let fragments = liboverdrop::scan(SYSTEMD_CONVENTIONAL_BASES, CONFIG_DIR, &["toml"], true);
+    let mut config = Config::default();
+    for (_name, path) in fragments {
+        let buf = std::fs::read_to_string(&path)?;
+        let c: Config = toml::from_str(&buf).with_context(|| format!("Parsing {path:?}"))?;
+        if let Some(mirror) = c.oci_mirror.as_ref() {
+            anyhow::ensure!(
+                mirror.is_absolute(),
+                "{path:?}: oci-mirror must be absolute: {mirror}"
+            );
+        }
+        config.merge(c);
+    }
+    Ok(config)
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub(crate) struct ResolvedBoundImage {
     pub(crate) image: String,
     pub(crate) digest: String,
 }
 
-/// Given a deployment, pull all container images it references.
-pub(crate) async fn pull_bound_images(sysroot: &Storage, deployment: &Deployment) -> Result<()> {
-    let bound_images = query_bound_images_for_deployment(sysroot, deployment)?;
-    pull_images(sysroot, bound_images).await
+/// Given a deployment, pull all container images it references. Authentication files
+/// in `/etc` are preferably taken from the merge deployment, as the `/etc` of a staged
+/// deployment does not have the local changes of the host until it is finalized.
+pub(crate) async fn pull_bound_images(
+    sysroot: &Storage,
+    deployment: &Deployment,
+    merge_deployment: Option<&Deployment>,
+    prog: ProgressWriter,
+) -> Result<()> {
+    let deployment_root = &crate::utils::deployment_fd(sysroot, deployment)?;
+    let merge_root = merge_deployment
+        .map(|d| crate::utils::deployment_fd(sysroot, d))
+        .transpose()?;
+    let bound_images = query_bound_images(deployment_root)?;
+    pull_images(
+        sysroot,
+        deployment_root,
+        merge_root.as_ref(),
+        bound_images,
+        prog,
+    )
+    .await?;
+    record_bound_images(sysroot, deployment)
+}
+
+/// The name of the file recording the resolved bound images of a deployment.
+fn resolved_record_name(deployment: &Deployment) -> String {
+    format!(
+        "{}-{}.{}.json",
+        deployment.osname(),
+        deployment.csum(),
+        deployment.deployserial()
+    )
+}
+
+/// Resolve the digests of the bound images of a deployment from the image storage, and
+/// record them so that they can be shown in the status and restored on rollback.
+/// Unless `require-digest` is configured, failures are only warnings.
+pub(crate) fn record_bound_images(sysroot: &Storage, deployment: &Deployment) -> Result<()> {
+    let require_digest = load_config()?.require_digest.unwrap_or_default();
+    match record_bound_images_impl(sysroot, deployment) {
+        Err(e) if !require_digest => {
+            tracing::warn!("Failed to record bound image digests: {e:#}");
+            Ok(())
+        }
+        r => r,
+    }
+}
+
+#[context("Recording bound images")]
+fn record_bound_images_impl(sysroot: &Storage, deployment: &Deployment) -> Result<()> {
+    let deployment_root = &crate::utils::deployment_fd(sysroot, deployment)?;
+    let bound_images = query_bound_images(deployment_root)?;
+    if bound_images.is_empty() {
+        return Ok(());
+    }
+    let imgstore = sysroot.get_ensure_imgstore()?;
+    let resolved = bound_images
+        .into_iter()
+        .map(|img| {
+            let digest = imgstore.image_digest(&img.image)?;
+            tracing::debug!("Resolved {}: {digest}", img.image);
+            Ok(ResolvedBoundImage {
+                image: img.image,
+                digest,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let sysroot_dir = &crate::utils::sysroot_dir(sysroot)?;
+    sysroot_dir.create_dir_all(RESOLVED_BOUND_IMAGES_DIR)?;
+    let dir = sysroot_dir.open_dir(RESOLVED_BOUND_IMAGES_DIR)?;
+    dir.atomic_replace_with(resolved_record_name(deployment), |w| {
+        serde_json::to_writer(w, &resolved)?;
+        anyhow::Ok(())
+    })?;
+    Ok(())
+}
+
+/// Return the bound images of a deployment as resolved when it was staged, if recorded.
+pub(crate) fn query_recorded_bound_images(
+    sysroot_dir: &Dir,
+    deployment: &Deployment,
+) -> Result<Option<Vec<ResolvedBoundImage>>> {
+    let path = Utf8Path::new(RESOLVED_BOUND_IMAGES_DIR).join(resolved_record_name(deployment));
+    let Some(f) = sysroot_dir.open_optional(&path)? else {
+        return Ok(None);
+    };
+    let r = serde_json::from_reader(std::io::BufReader::new(f))
+        .with_context(|| format!("Parsing {path}"))?;
+    Ok(Some(r))
+}
+
+/// Remove the recorded bound images of deployments which no longer exist.
+#[context("Pruning recorded bound images")]
+pub(crate) fn prune_recorded_bound_images(
+    sysroot: &Storage,
+    deployments: &[Deployment],
+) -> Result<()> {
+    let sysroot_dir = &crate::utils::sysroot_dir(sysroot)?;
+    let Some(dir) = sysroot_dir.open_dir_optional(RESOLVED_BOUND_IMAGES_DIR)? else {
+        return Ok(());
+    };
+    let live = deployments
+        .iter()
+        .map(resolved_record_name)
+        .collect::<std::collections::HashSet<_>>();
+    for entry in dir.entries()? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if name.to_str().is_some_and(|n| live.contains(n)) {
+            continue;
+        }
+        tracing::debug!("Removing recorded bound images: {name:?}");
+        dir.remove_file(&name)?;
+    }
+    Ok(())
+}
+
+/// Return the bound images of a deployment along with the digests recorded when the
+/// deployment was staged, if any. This only reads the recorded state, and does not
+/// access the image storage.
+pub(crate) fn bound_images_status(
+    sysroot: &Storage,
+    deployment: &Deployment,
+) -> Result<Vec<BoundImageStatus>> {
+    let deployment_root = &crate::utils::deployment_fd(sysroot, deployment)?;
+    let bound_images = match query_bound_images(deployment_root) {
+        Ok(v) => v,
+        Err(e) => {
+            tracing::warn!("Failed to query bound images: {e:#}");
+            return Ok(Vec::new());
+        }
+    };
+    if bound_images.is_empty() {
+        return Ok(Vec::new());
+    }
+    let sysroot_dir = &crate::utils::sysroot_dir(sysroot)?;
+    let recorded = query_recorded_bound_images(sysroot_dir, deployment)?.unwrap_or_default();
+    let r = bound_images
+        .into_iter()
+        .map(|img| {
+            let digest = recorded
+                .iter()
+                .find(|r| r.image == img.image)
+                .map(|r| r.digest.clone());
+            BoundImageStatus {
+                image: img.image,
+                digest,
+            }
+        })
+        .collect();
+    Ok(r)
+}
+
+/// Strip the tag (if any) from an image reference.
+fn image_repository(image: &str) -> &str {
+    // A `:` before the last `/` is part of the registry (port)
+    let name_start = image.rfind('/').map(|i| i + 1).unwrap_or_default();
+    match image[name_start..].rfind(':') {
+        Some(i) => &image[..name_start + i],
+        None => image,
+    }
+}
+
+/// The reference to the recorded digest of a bound image.
+fn pinned_reference(img: &ResolvedBoundImage) -> String {
+    format!("{}@{}", image_repository(&img.image), img.digest)
+}
+
+/// Return the bound images of a deployment whose tags no longer refer to the digests
+/// recorded when it was staged, and whose recorded images are still present; missing
+/// images are only a warning. This is used on rollback; see [`restore_bound_images`].
+#[context("Checking bound images")]
+pub(crate) async fn bound_images_to_restore(
+    sysroot: &Storage,
+    deployment: &Deployment,
+) -> Result<Vec<ResolvedBoundImage>> {
+    let sysroot_dir = &crate::utils::sysroot_dir(sysroot)?;
+    let Some(recorded) = query_recorded_bound_images(sysroot_dir, deployment)? else {
+        return Ok(Vec::new());
+    };
+    if recorded.is_empty() {
+        return Ok(Vec::new());
+    }
+    let imgstore = sysroot.get_ensure_imgstore()?;
+    let mut r = Vec::new();
+    for img in recorded.into_iter().filter(|img| !img.image.contains('@')) {
+        let current = imgstore.image_digest(&img.image).ok();
+        if current.as_deref() == Some(img.digest.as_str()) {
+            continue;
+        }
+        if !imgstore.exists(&pinned_reference(&img)).await? {
+            tracing::warn!(
+                "Bound image {} is no longer present with digest {}",
+                img.image,
+                img.digest
+            );
+            continue;
+        }
+        r.push(img);
+    }
+    Ok(r)
+}
+
+/// Move the tags of the bound images found by [`bound_images_to_restore`] back
+/// to their recorded digests.
+#[context("Restoring bound images")]
+pub(crate) async fn restore_bound_images(
+    sysroot: &Storage,
+    images: &[ResolvedBoundImage],
+) -> Result<()> {
+    if images.is_empty() {
+        return Ok(());
+    }
+    let imgstore = sysroot.get_ensure_imgstore()?;
+    for img in images {
+        println!("Restoring bound image {} to {}", img.image, img.digest);
+        imgstore.tag(&pinned_reference(img), &img.image).await?;
+    }
+    Ok(())
+}
+
+/// Return the references to the recorded digests of the bound images of the provided
+/// deployments, so that they are retained for [`restore_bound_images`] even once the
+/// tags have moved.
+#[context("Querying recorded bound images")]
+pub(crate) fn recorded_bound_image_references(
+    sysroot: &Storage,
+    deployments: &[Deployment],
+) -> Result<Vec<String>> {
+    let sysroot_dir = &crate::utils::sysroot_dir(sysroot)?;
+    let mut r = Vec::new();
+    for deployment in deployments {
+        let recorded = query_recorded_bound_images(sysroot_dir, deployment)?.unwrap_or_default();
+        // Images referenced by digest are retained by name
+        r.extend(
+            recorded
+                .iter()
+                .filter(|img| !img.image.contains('@'))
+                .map(pinned_reference),
+        );
+    }
+    Ok(r)
 }
 
 #[context("Querying bound images")]
@@ -54,6 +388,24 @@ pub(crate) fn query_bound_images_for_deployment(
 
 #[context("Querying bound images")]
 pub(crate) fn query_bound_images(root: &Dir) -> Result<Vec<BoundImage>> {
+    let mut r: Vec<BoundImage> = Vec::new();
+    for (_, images) in parse_bound_image_entries(root)? {
+        for image in images? {
+            // The same image may be referenced by multiple entries
+            if !r.iter().any(|i| i.image == image.image) {
+                r.push(image);
+            }
+        }
+    }
+    Ok(r)
+}
+
+/// Parse each entry in the bound images directory, returning the result for each
+/// individual entry alongside its filename so that errors can be reported separately.
+/// A single entry (e.g. a `.pod` or `.kube` file) may reference multiple images.
+pub(crate) fn parse_bound_image_entries(
+    root: &Dir,
+) -> Result<Vec<(String, Result<Vec<BoundImage>>)>> {
     let spec_dir = BOUND_IMAGE_DIR;
     let Some(bound_images_dir) = root.open_dir_optional(spec_dir)? else {
         tracing::debug!("Missing {spec_dir}");
@@ -78,28 +430,201 @@ pub(crate) fn query_bound_images(root: &Dir) -> Result<Vec<BoundImage>> {
             anyhow::bail!("Invalid non-UTF8 filename: {file_name:?} in {}", spec_dir);
         };
 
-        if !entry.file_type()?.is_symlink() {
-            anyhow::bail!("Not a symlink: {file_name}");
+        let r = parse_bound_image_entry(absroot, &entry, file_name);
+        bound_images.push((file_name.to_owned(), r));
+    }
+    // Ensure a stable ordering
+    bound_images.sort_by(|a, b| a.0.cmp(&b.0));
+
+    Ok(bound_images)
+}
+
+fn parse_bound_image_entry(
+    absroot: &Dir,
+    entry: &cap_std_ext::cap_std::fs::DirEntry,
+    file_name: &str,
+) -> Result<Vec<BoundImage>> {
+    if !entry.file_type()?.is_symlink() {
+        anyhow::bail!("Not a symlink: {file_name}");
+    }
+
+    //parse the file contents
+    let path = Utf8Path::new(BOUND_IMAGE_DIR).join(file_name);
+    let file_ini = read_quadlet(absroot, &path)?;
+    // Other files referenced by the quadlet are looked up relative to its
+    // real location, not the bound images directory.
+    let target = quadlet_target(absroot, &path)?;
+    let dir = target
+        .parent()
+        .filter(|p| !p.as_str().is_empty())
+        .unwrap_or(Utf8Path::new("."));
+    let file_extension = Utf8Path::new(file_name).extension();
+    let r = match file_extension {
+        Some("image") => parse_image_file(&file_ini).map(|i| vec![i]),
+        Some("container") => {
+            parse_container_quadlet(absroot, dir, &file_ini).map(|i| i.into_iter().collect())
         }
+        Some("pod") => {
+            // SAFETY: The target must have a filename, as we read it above
+            let pod_name = target.file_name().unwrap();
+            parse_pod_quadlet(absroot, dir, pod_name)
+        }
+        Some("kube") => parse_kube_quadlet(absroot, dir, &file_ini),
+        _ => anyhow::bail!("Invalid file extension: {file_name}"),
+    };
+    r.with_context(|| format!("Parsing {path}"))
+}
 
-        //parse the file contents
-        let path = Utf8Path::new(spec_dir).join(file_name);
-        let file_contents = absroot.read_to_string(&path)?;
+/// Read and parse a quadlet file.
+fn read_quadlet(absroot: &Dir, path: &Utf8Path) -> Result<tini::Ini> {
+    let file_contents = absroot
+        .read_to_string(path)
+        .with_context(|| format!("Reading {path}"))?;
+    tini::Ini::from_string(&file_contents).context("Parse to ini")
+}
 
-        let file_ini = tini::Ini::from_string(&file_contents).context("Parse to ini")?;
-        let file_extension = Utf8Path::new(file_name).extension();
-        let bound_image = match file_extension {
-            Some("image") => parse_image_file(&file_ini).with_context(|| format!("Parsing {path}")),
-            Some("container") => {
-                parse_container_file(&file_ini).with_context(|| format!("Parsing {path}"))
-            }
-            _ => anyhow::bail!("Invalid file extension: {file_name}"),
-        }?;
+/// Return the path (relative to the root) of the quadlet file that the
+/// provided symbolic link points to.
+fn quadlet_target(absroot: &Dir, link: &Utf8Path) -> Result<Utf8PathBuf> {
+    let target = absroot
+        .read_link_contents(link)
+        .with_context(|| format!("Reading {link}"))?;
+    let target = Utf8PathBuf::try_from(target)?;
+    let r = match target.strip_prefix("/") {
+        Ok(target) => target.to_owned(),
+        // SAFETY: The link is in the bound images directory, so it has a parent
+        Err(_) => link.parent().unwrap().join(target),
+    };
+    Ok(r)
+}
 
-        bound_images.push(bound_image);
+/// Resolve a file referenced by a quadlet in `dir`; relative paths are
+/// relative to that directory, and absolute paths to the root.
+fn resolve_quadlet_ref(dir: &Utf8Path, name: &str) -> Utf8PathBuf {
+    match name.strip_prefix('/') {
+        Some(name) => Utf8PathBuf::from(name),
+        None => dir.join(name),
     }
+}
 
-    Ok(bound_images)
+/// Parse a `.container` file, resolving references to `.image` files. Images
+/// referencing a `.build` file are built locally, and hence there is nothing to
+/// fetch; `None` is returned for these.
+fn parse_container_quadlet(
+    absroot: &Dir,
+    dir: &Utf8Path,
+    file_contents: &tini::Ini,
+) -> Result<Option<BoundImage>> {
+    let image: String = file_contents
+        .get("Container", "Image")
+        .ok_or_else(|| anyhow::anyhow!("Missing Image field"))?;
+    if image.ends_with(".build") {
+        tracing::debug!("Skipping locally built image: {image}");
+        return Ok(None);
+    }
+    if image.ends_with(".image") {
+        let path = resolve_quadlet_ref(dir, &image);
+        let image_file = read_quadlet(absroot, &path)?;
+        return parse_image_file(&image_file)
+            .with_context(|| format!("Parsing {path}"))
+            .map(Some);
+    }
+    parse_container_file(file_contents).map(Some)
+}
+
+/// Parse the images of all `.container` files in `dir` which are part of the
+/// pod `pod_name`.
+fn parse_pod_quadlet(absroot: &Dir, dir: &Utf8Path, pod_name: &str) -> Result<Vec<BoundImage>> {
+    let mut containers = Vec::new();
+    for entry in absroot
+        .read_dir(dir)
+        .with_context(|| format!("Reading {dir}"))?
+    {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(name) = name.to_str().filter(|n| n.ends_with(".container")) else {
+            continue;
+        };
+        containers.push(name.to_owned());
+    }
+    // Ensure a stable ordering
+    containers.sort();
+
+    let mut r = Vec::new();
+    let mut found = false;
+    for name in containers {
+        let path = dir.join(&name);
+        let file_contents = read_quadlet(absroot, &path)?;
+        let pod: Option<String> = file_contents.get("Container", "Pod");
+        if pod.as_deref() != Some(pod_name) {
+            continue;
+        }
+        found = true;
+        let image = parse_container_quadlet(absroot, dir, &file_contents)
+            .with_context(|| format!("Parsing {path}"))?;
+        r.extend(image);
+    }
+    if !found {
+        anyhow::bail!("No containers found for pod {pod_name}");
+    }
+    Ok(r)
+}
+
+/// Parse the images referenced by the Kubernetes YAML of a `.kube` file.
+fn parse_kube_quadlet(
+    absroot: &Dir,
+    dir: &Utf8Path,
+    file_contents: &tini::Ini,
+) -> Result<Vec<BoundImage>> {
+    let yaml: String = file_contents
+        .get("Kube", "Yaml")
+        .ok_or_else(|| anyhow::anyhow!("Missing Yaml field"))?;
+    let path = resolve_quadlet_ref(dir, &yaml);
+    let yaml = absroot
+        .read_to_string(&path)
+        .with_context(|| format!("Reading {path}"))?;
+    parse_kube_yaml(&yaml)
+        .with_context(|| format!("Parsing {path}"))?
+        .into_iter()
+        .map(|image| BoundImage::new(image, None))
+        .collect()
+}
+
+/// Gather the images of all containers and init containers in a (possibly
+/// multi-document) Kubernetes YAML file.
+fn parse_kube_yaml(contents: &str) -> Result<Vec<String>> {
+    fn gather(v: &serde_yaml::Value, images: &mut Vec<String>) {
+        match v {
+            serde_yaml::Value::Mapping(m) => {
+                for (k, v) in m {
+                    if matches!(k.as_str(), Some("containers" | "initContainers")) {
+                        let containers = v.as_sequence().map(|v| v.as_slice()).unwrap_or_default();
+                        for image in containers
+                            .iter()
+                            .filter_map(|c| c.get("image").and_then(|i| i.as_str()))
+                        {
+                            if !images.iter().any(|i| i == image) {
+                                images.push(image.to_owned());
+                            }
+                        }
+                    }
+                    gather(v, images);
+                }
+            }
+            serde_yaml::Value::Sequence(s) => s.iter().for_each(|v| gather(v, images)),
+            _ => {}
+        }
+    }
+
+    let mut images = Vec::new();
+    for doc in serde_yaml::Deserializer::from_str(contents) {
+        let v = serde_yaml::Value::deserialize(doc)?;
+        gather(&v, &mut images);
+    }
+    if images.is_empty() {
+        anyhow::bail!("No container images found");
+    }
+    Ok(images)
 }
 
 impl ResolvedBoundImage {
@@ -122,15 +647,9 @@ fn parse_image_file(file_contents: &tini::Ini) -> Result<BoundImage> {
         .get("Image", "Image")
         .ok_or_else(|| anyhow::anyhow!("Missing Image field"))?;
 
-    //TODO: auth_files have some semi-complicated edge cases that we need to handle,
-    //      so for now let's bail out if we see one since the existence of an authfile
-    //      will most likely result in a failure to pull the image
     let auth_file: Option<String> = file_contents.get("Image", "AuthFile");
-    if auth_file.is_some() {
-        anyhow::bail!("AuthFile is not supported by bound bootc images");
-    }
 
-    let bound_image = BoundImage::new(image.to_string(), None)?;
+    let bound_image = BoundImage::new(image.to_string(), auth_file)?;
     Ok(bound_image)
 }
 
@@ -138,45 +657,185 @@ fn parse_container_file(file_contents: &tini::Ini) -> Result<BoundImage> {
     let image: String = file_contents
         .get("Container", "Image")
         .ok_or_else(|| anyhow::anyhow!("Missing Image field"))?;
+    let auth_file: Option<String> = file_contents.get("Container", "AuthFile");
 
-    let bound_image = BoundImage::new(image.to_string(), None)?;
+    let bound_image = BoundImage::new(image.to_string(), auth_file)?;
     Ok(bound_image)
 }
 
 #[context("Pulling bound images")]
 pub(crate) async fn pull_images(
     sysroot: &Storage,
+    root: &Dir,
+    merge_root: Option<&Dir>,
     bound_images: Vec<crate::boundimage::BoundImage>,
+    prog: ProgressWriter,
 ) -> Result<()> {
     // Only do work like initializing the image storage if we have images to pull.
     if bound_images.is_empty() {
         return Ok(());
     }
     let imgstore = sysroot.get_ensure_imgstore()?;
-    pull_images_impl(imgstore, bound_images).await
+    pull_images_impl(imgstore, root, merge_root, bound_images, prog).await
+}
+
+/// Returns true if a failed fetch may succeed when retried; errors such as a missing
+/// image or failed authentication are not.
+fn is_transient_pull_error(e: &anyhow::Error) -> bool {
+    let msg = format!("{e:#}").to_ascii_lowercase();
+    TRANSIENT_PULL_ERRORS.iter().any(|p| msg.contains(p))
+}
+
+/// Invoke `f` up to `attempts` times until it succeeds or fails with an error for
+/// which `transient` returns false, waiting `delay` after the first failure and
+/// doubling it after each subsequent one.
+async fn retry_with_backoff<T, F, Fut>(
+    desc: &str,
+    attempts: u32,
+    mut delay: Duration,
+    transient: impl Fn(&anyhow::Error) -> bool,
+    mut f: F,
+) -> Result<T>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T>>,
+{
+    let mut attempt = 1;
+    loop {
+        match f().await {
+            Ok(v) => return Ok(v),
+            Err(e) if attempt < attempts && transient(&e) => {
+                tracing::warn!(
+                    "{desc} failed (attempt {attempt}/{attempts}), retrying in {delay:?}: {e:#}"
+                );
+                tokio::time::sleep(delay).await;
+                delay *= 2;
+                attempt += 1;
+            }
+            Err(e) => return Err(e),
+        }
+    }
 }
 
+/// Pull the bound images of the deployment root `root` into `imgstore`; see
+/// [`BoundImage::open_auth_file`] for `merge_root`.
 #[context("Pulling bound images")]
 pub(crate) async fn pull_images_impl(
     imgstore: &crate::imgstorage::Storage,
+    root: &Dir,
+    merge_root: Option<&Dir>,
     bound_images: Vec<crate::boundimage::BoundImage>,
+    prog: ProgressWriter,
 ) -> Result<()> {
     let n = bound_images.len();
     tracing::debug!("Pulling bound images: {n}");
-    // TODO: do this in parallel
+    let mut missing = Vec::new();
     for bound_image in bound_images {
         let image = &bound_image.image;
         if imgstore.exists(image).await? {
             tracing::debug!("Bound image already present: {image}");
             continue;
         }
-        let desc = format!("Fetching bound image: {image}");
-        crate::utils::async_task_with_spinner(&desc, async move {
-            imgstore
-                .pull(&bound_image.image, PullMode::IfNotExists)
-                .await
+        let auth_file = bound_image.open_auth_file(root, merge_root)?;
+        missing.push((bound_image, auth_file));
+    }
+    let cached = (n - missing.len()) as u64;
+    let config = load_config()?;
+    let concurrency = config.concurrency();
+    let oci_mirror = config.oci_mirror.as_deref();
+
+    let mut subtasks = missing
+        .iter()
+        .map(|(img, _)| SubTaskStep {
+            subtask: "bound_image".into(),
+            description: "Bound image".into(),
+            id: img.image.clone().into(),
+            completed: false,
         })
-        .await?;
+        .collect::<Vec<_>>();
+    let send_steps = |subtasks: Vec<SubTaskStep<'static>>| {
+        let prog = &prog;
+        async move {
+            let done = subtasks.iter().filter(|s| s.completed).count() as u64;
+            prog.send(Event::ProgressSteps {
+                task: "pulling_bound_images".into(),
+                description: "Pulling bound images".into(),
+                id: "bound_images".into(),
+                steps_cached: cached,
+                steps: cached + done,
+                steps_total: n as u64,
+                subtasks,
+            })
+            .await
+        }
+    };
+
+    let pull_one = |(bound_image, auth_file): (BoundImage, Option<std::fs::File>)| {
+        let prog = &prog;
+        async move {
+            let image = bound_image.image.as_str();
+            // The total size is not known until the image is pulled
+            let send_bytes = |bytes: u64, steps: u64| {
+                prog.send(Event::ProgressBytes {
+                    task: "pulling_bound_image".into(),
+                    description: format!("Pulling bound image: {image}").into(),
+                    id: image.to_owned().into(),
+                    bytes_cached: 0,
+                    bytes,
+                    bytes_total: bytes,
+                    steps_cached: 0,
+                    steps,
+                    steps_total: 1,
+                    subtasks: Vec::new(),
+                })
+            };
+            send_bytes(0, 0).await;
+            let desc = format!("Fetching bound image {image}");
+            let r = retry_with_backoff(
+                &desc,
+                PULL_ATTEMPTS,
+                PULL_RETRY_DELAY,
+                is_transient_pull_error,
+                || imgstore.pull(image, PullMode::IfNotExists, auth_file.as_ref(), oci_mirror),
+            )
+            .await;
+            if r.is_ok() {
+                let size = imgstore.image_size(image).await.unwrap_or_else(|e| {
+                    tracing::debug!("Failed to query size of {image}: {e:#}");
+                    0
+                });
+                send_bytes(size, 1).await;
+            }
+            (bound_image.image, r)
+        }
+    };
+
+    send_steps(subtasks.clone()).await;
+    let desc = format!("Fetching bound images: {}", missing.len());
+    let errors = crate::utils::async_task_with_spinner(&desc, async {
+        let mut errors = Vec::new();
+        let mut pulls = futures_util::stream::iter(missing.into_iter().map(pull_one))
+            .buffer_unordered(concurrency);
+        while let Some((image, r)) = pulls.next().await {
+            match r {
+                Ok(_) => {
+                    if let Some(s) = subtasks.iter_mut().find(|s| s.id == image) {
+                        s.completed = true;
+                    }
+                    send_steps(subtasks.clone()).await;
+                }
+                Err(e) => errors.push(format!("{image}: {e:#}")),
+            }
+        }
+        errors
+    })
+    .await;
+    if !errors.is_empty() {
+        anyhow::bail!(
+            "Failed to fetch {} bound images:\n{}",
+            errors.len(),
+            errors.join("\n")
+        );
     }
 
     println!("Bound images stored: {n}");
@@ -196,6 +855,49 @@ impl BoundImage {
 
         Ok(BoundImage { image, auth_file })
     }
+
+    /// Open the authentication file for this image (if any), resolving it
+    /// relative to the provided root. A file in `/etc` is preferably taken from
+    /// `merge_root`, the deployment whose `/etc` will be merged into `root`.
+    #[context("Opening AuthFile for {}", self.image)]
+    fn open_auth_file(
+        &self,
+        root: &Dir,
+        merge_root: Option<&Dir>,
+    ) -> Result<Option<std::fs::File>> {
+        let Some(auth_file) = self.auth_file.as_deref() else {
+            return Ok(None);
+        };
+        let path = auth_file.trim_start_matches('/');
+        if let Some(merge_root) = merge_root.filter(|_| Utf8Path::new(path).starts_with("etc")) {
+            let absroot = merge_root.open_dir_rooted_ext(".")?;
+            match absroot.open(path) {
+                Ok(f) => return Ok(Some(f.into_std())),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e).with_context(|| format!("Opening {auth_file}")),
+            }
+        }
+        // Use a view of the root with RESOLVE_IN_ROOT so that absolute paths and
+        // symlinks are resolved relative to it, and not the host.
+        let absroot = root.open_dir_rooted_ext(".")?;
+        let f = absroot
+            .open(path)
+            .with_context(|| format!("Opening {auth_file}"))?;
+        Ok(Some(f.into_std()))
+    }
+
+    /// Returns true if the image name includes a registry, and hence does not
+    /// depend on short-name resolution.
+    pub(crate) fn is_fully_qualified(&self) -> bool {
+        self.image
+            .split_once('/')
+            .is_some_and(|(domain, _)| domain.contains(['.', ':']) || domain == "localhost")
+    }
+
+    /// Returns true if the image is pinned by digest.
+    pub(crate) fn is_digest_pinned(&self) -> bool {
+        self.image.contains('@')
+    }
 }
 
 /// Given a string, parse it in a way similar to how systemd would do it.
@@ -291,6 +993,284 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_parse_quadlet_references() -> Result<()> {
+        const CONTAINER_IMAGE_DIR: &str = "usr/share/containers/systemd";
+        let td = &cap_std_ext::cap_tempfile::TempDir::new(cap_std::ambient_authority())?;
+        td.create_dir_all(BOUND_IMAGE_DIR)?;
+        td.create_dir_all(CONTAINER_IMAGE_DIR)?;
+        let link = |name: &str| {
+            td.symlink_contents(
+                format!("/{CONTAINER_IMAGE_DIR}/{name}"),
+                format!("{BOUND_IMAGE_DIR}/{name}"),
+            )
+        };
+
+        // A container referencing a .image file, and one built locally
+        td.write(
+            format!("{CONTAINER_IMAGE_DIR}/app.image"),
+            "[Image]\nImage=quay.io/example/app:latest\nAuthFile=/etc/app-auth.json\n",
+        )?;
+        td.write(
+            format!("{CONTAINER_IMAGE_DIR}/app.container"),
+            "[Container]\nImage=app.image\n",
+        )?;
+        td.write(
+            format!("{CONTAINER_IMAGE_DIR}/local.container"),
+            "[Container]\nImage=local.build\n",
+        )?;
+        link("app.container")?;
+        link("local.container")?;
+        let images = query_bound_images(td)?;
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].image, "quay.io/example/app:latest");
+        assert_eq!(images[0].auth_file.as_deref(), Some("/etc/app-auth.json"));
+
+        // A pod gathers the images of its containers
+        td.write(format!("{CONTAINER_IMAGE_DIR}/web.pod"), "[Pod]\n")?;
+        td.write(
+            format!("{CONTAINER_IMAGE_DIR}/web-server.container"),
+            "[Container]\nImage=quay.io/example/server:latest\nPod=web.pod\n",
+        )?;
+        td.write(
+            format!("{CONTAINER_IMAGE_DIR}/web-cache.container"),
+            "[Container]\nImage=quay.io/example/cache:latest\nPod=web.pod\n",
+        )?;
+        td.write(
+            format!("{CONTAINER_IMAGE_DIR}/web-app.container"),
+            "[Container]\nImage=app.image\nPod=web.pod\n",
+        )?;
+        link("web.pod")?;
+        let images = query_bound_images(td)?;
+        let images = images.iter().map(|i| i.image.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            images,
+            [
+                "quay.io/example/app:latest",
+                "quay.io/example/cache:latest",
+                "quay.io/example/server:latest"
+            ]
+        );
+
+        // A kube file gathers the images from its YAML
+        td.write(
+            format!("{CONTAINER_IMAGE_DIR}/stack.yaml"),
+            indoc::indoc! { r#"
+            apiVersion: v1
+            kind: Pod
+            spec:
+              initContainers:
+              - name: init
+                image: quay.io/example/init:latest
+              containers:
+              - name: db
+                image: quay.io/example/db:latest
+            ---
+            apiVersion: apps/v1
+            kind: Deployment
+            spec:
+              template:
+                spec:
+                  containers:
+                  - name: db
+                    image: quay.io/example/db:latest
+                  - name: api
+                    image: quay.io/example/api:latest
+            "# },
+        )?;
+        td.write(
+            format!("{CONTAINER_IMAGE_DIR}/stack.kube"),
+            "[Kube]\nYaml=stack.yaml\n",
+        )?;
+        link("stack.kube")?;
+        let entries = parse_bound_image_entries(td)?;
+        let (_, r) = entries.iter().find(|(n, _)| n == "stack.kube").unwrap();
+        let images = r.as_ref().unwrap();
+        let images = images.iter().map(|i| i.image.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            images,
+            [
+                "quay.io/example/init:latest",
+                "quay.io/example/db:latest",
+                "quay.io/example/api:latest"
+            ]
+        );
+        assert_eq!(query_bound_images(td)?.len(), 6);
+
+        // A pod without any containers is an error
+        td.write(format!("{CONTAINER_IMAGE_DIR}/empty.pod"), "[Pod]\n")?;
+        link("empty.pod")?;
+        assert!(query_bound_images(td).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_kube_yaml() -> Result<()> {
+        assert!(parse_kube_yaml("").is_err());
+        assert!(parse_kube_yaml("apiVersion: v1\nkind: ConfigMap\n").is_err());
+        let images = parse_kube_yaml(indoc::indoc! { r#"
+            apiVersion: v1
+            kind: Pod
+            spec:
+              containers:
+              - name: a
+                image: quay.io/example/a:latest
+              - name: b
+                image: quay.io/example/a:latest
+        "# })?;
+        assert_eq!(images, ["quay.io/example/a:latest"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_image_repository() {
+        let cases = [
+            ("quay.io/foo/bar:latest", "quay.io/foo/bar"),
+            ("quay.io/foo/bar", "quay.io/foo/bar"),
+            ("localhost:5000/foo:v1", "localhost:5000/foo"),
+            ("localhost:5000/foo", "localhost:5000/foo"),
+            ("foo:latest", "foo"),
+        ];
+        for (image, expected) in cases {
+            assert_eq!(image_repository(image), expected, "{image}");
+        }
+    }
+
+    #[test]
+    fn test_open_auth_file() -> Result<()> {
+        let td = &cap_std_ext::cap_tempfile::TempDir::new(cap_std::ambient_authority())?;
+        let img = BoundImage::new("quay.io/foo/foo:latest".into(), None)?;
+        assert!(img.open_auth_file(td, None)?.is_none());
+
+        let img = BoundImage::new(
+            "quay.io/foo/foo:latest".into(),
+            Some("/etc/containers/auth.json".into()),
+        )?;
+        assert!(img.open_auth_file(td, None).is_err());
+
+        // Absolute symlinks are resolved relative to the root
+        td.create_dir_all("etc/containers")?;
+        td.create_dir_all("usr/lib/container-auth")?;
+        td.write("usr/lib/container-auth/auth.json", "{}")?;
+        td.symlink_contents(
+            "/usr/lib/container-auth/auth.json",
+            "etc/containers/auth.json",
+        )?;
+        let mut f = img.open_auth_file(td, None)?.unwrap();
+        let mut buf = String::new();
+        std::io::Read::read_to_string(&mut f, &mut buf)?;
+        assert_eq!(buf, "{}");
+
+        // A credential which only exists in the /etc of the host
+        let host = &cap_std_ext::cap_tempfile::TempDir::new(cap_std::ambient_authority())?;
+        let img = BoundImage::new(
+            "quay.io/foo/foo:latest".into(),
+            Some("/etc/app-auth.json".into()),
+        )?;
+        assert!(img.open_auth_file(td, Some(host)).is_err());
+        host.create_dir("etc")?;
+        host.write("etc/app-auth.json", "host")?;
+        let mut f = img.open_auth_file(td, Some(host))?.unwrap();
+        let mut buf = String::new();
+        std::io::Read::read_to_string(&mut f, &mut buf)?;
+        assert_eq!(buf, "host");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retry_with_backoff() -> Result<()> {
+        let attempts = std::cell::Cell::new(0);
+        let f = || {
+            attempts.set(attempts.get() + 1);
+            let n = attempts.get();
+            async move {
+                if n < 3 {
+                    anyhow::bail!("failed {n}")
+                }
+                Ok(n)
+            }
+        };
+        let r = retry_with_backoff("test", 3, Duration::ZERO, |_| true, f).await?;
+        assert_eq!(r, 3);
+
+        attempts.set(0);
+        let e = retry_with_backoff("test", 2, Duration::ZERO, |_| true, f)
+            .await
+            .unwrap_err();
+        assert_eq!(e.to_string(), "failed 2");
+        assert_eq!(attempts.get(), 2);
+
+        // Errors which aren't transient fail immediately
+        attempts.set(0);
+        let e = retry_with_backoff("test", 3, Duration::ZERO, |_| false, f)
+            .await
+            .unwrap_err();
+        assert_eq!(e.to_string(), "failed 1");
+        assert_eq!(attempts.get(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_transient_pull_error() {
+        let err = |msg: &str| anyhow::anyhow!("{msg}").context("Failed to pull image");
+        for msg in [
+            "Error: initializing source docker://quay.io/foo/bar:latest: pinging container registry quay.io: Get \"https://quay.io/v2/\": dial tcp: lookup quay.io: Temporary failure in name resolution",
+            "Error: reading manifest latest in quay.io/foo/bar: received unexpected HTTP status: 503 Service Unavailable",
+            "Error: copying system image from manifest list: read tcp 10.0.0.2:4312->1.2.3.4:443: read: connection reset by peer",
+        ] {
+            assert!(is_transient_pull_error(&err(msg)), "{msg}");
+        }
+        for msg in [
+            "Error: initializing source docker://quay.io/foo/bar:latest: reading manifest latest in quay.io/foo/bar: manifest unknown",
+            "Error: initializing source docker://quay.io/foo/bar:latest: reading manifest latest in quay.io/foo/bar: unauthorized: access to the requested resource is not authorized",
+        ] {
+            assert!(!is_transient_pull_error(&err(msg)), "{msg}");
+        }
+    }
+
+    #[test]
+    fn test_config() -> Result<()> {
+        let mut config = Config::default();
+        assert_eq!(config.concurrency(), DEFAULT_PULL_CONCURRENCY);
+        config.merge(toml::from_str("concurrency = 2")?);
+        config.merge(toml::from_str("require-digest = true")?);
+        assert_eq!(config.concurrency(), 2);
+        assert_eq!(config.require_digest, Some(true));
+        config.merge(toml::from_str("oci-mirror = \"/var/mnt/mirror\"")?);
+        assert_eq!(
+            config.oci_mirror.as_deref(),
+            Some(Utf8Path::new("/var/mnt/mirror"))
+        );
+        assert_eq!(config.concurrency(), 2);
+        assert!(toml::from_str::<Config>("concurrency = 0").is_err());
+        assert!(toml::from_str::<Config>("unknown = 1").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bound_image_reference() -> Result<()> {
+        let cases = [
+            ("quay.io/foo/foo:latest", true, false),
+            ("localhost/foo", true, false),
+            ("registry:5000/foo", true, false),
+            ("foo/foo:latest", false, false),
+            ("foo", false, false),
+            (
+                "quay.io/foo/foo@sha256:6a5b2ba0d0bc1a9ecc2a9d1a2ecec4e63c23bb0b0ea53b1d8dde4e8ea8b3b5c1",
+                true,
+                true,
+            ),
+        ];
+        for (image, qualified, pinned) in cases {
+            let img = BoundImage::new(image.to_owned(), None)?;
+            assert_eq!(img.is_fully_qualified(), qualified, "{image}");
+            assert_eq!(img.is_digest_pinned(), pinned, "{image}");
+        }
+        Ok(())
+    }
+
     #[test]
     fn test_parse_spec_value() -> Result<()> {
         //should parse string with no % characters
@@ -358,14 +1338,18 @@ mod tests {
         assert_eq!(bound_image.image, "quay.io/foo/foo:latest");
         assert_eq!(bound_image.auth_file, None);
 
-        //should error when auth_file is present
+        //should return BoundImage with the auth_file when present
         let file_contents = tini::Ini::from_string(indoc::indoc! { "
             [Image]
             Image=quay.io/foo/foo:latest
             AuthFile=/etc/containers/auth.json
         " })
         .unwrap();
-        assert!(parse_image_file(&file_contents).is_err());
+        let bound_image = parse_image_file(&file_contents).unwrap();
+        assert_eq!(
+            bound_image.auth_file.as_deref(),
+            Some("/etc/containers/auth.json")
+        );
 
         //should return error when missing image field
         let file_contents = tini::Ini::from_string("[Image]\n").unwrap();
@@ -383,6 +1367,19 @@ mod tests {
         assert_eq!(bound_image.image, "quay.io/foo/foo:latest");
         assert_eq!(bound_image.auth_file, None);
 
+        //should return BoundImage with the auth_file when present
+        let file_contents = tini::Ini::from_string(indoc::indoc! { "
+            [Container]
+            Image=quay.io/foo/foo:latest
+            AuthFile=/etc/containers/auth.json
+        " })
+        .unwrap();
+        let bound_image = parse_container_file(&file_contents).unwrap();
+        assert_eq!(
+            bound_image.auth_file.as_deref(),
+            Some("/etc/containers/auth.json")
+        );
+
         //should return error when missing image field
         let file_contents = tini::Ini::from_string("[Container]\n").unwrap();
         assert!(parse_container_file(&file_contents).is_err());
@@ -24,7 +24,8 @@ use ostree_ext::ostree;
 use schemars::schema_for;
 use serde::{Deserialize, Serialize};
 
-use crate::deploy::RequiredHostSpec;
+use crate::deploy::{ImageState, RequiredHostSpec};
+use crate::history::{record_failures, Operation, Transition};
 use crate::lints;
 use crate::progress_jsonl::{ProgressWriter, RawProgressFd};
 use crate::spec::Host;
@@ -145,6 +146,54 @@ pub(crate) struct EditOpts {
     pub(crate) quiet: bool,
 }
 
+/// Perform a kernel argument operation
+#[derive(Debug, Parser, PartialEq, Eq)]
+pub(crate) struct KargsOpts {
+    /// Add a kernel argument, e.g. `console=ttyS0`
+    #[clap(long, value_name = "KARG")]
+    pub(crate) append: Vec<String>,
+
+    /// Remove a kernel argument. If no value is given (e.g. `console`), all
+    /// instances of the key are removed.
+    #[clap(long, value_name = "KARG")]
+    pub(crate) delete: Vec<String>,
+
+    /// Replace a kernel argument, in the form `KEY=VALUE` if there is a single
+    /// instance of `KEY`, or `KEY=OLD=NEW` to replace a specific value.
+    #[clap(long, value_name = "KARG")]
+    pub(crate) replace: Vec<String>,
+
+    /// Restart or reboot into the new deployment.
+    #[clap(long)]
+    pub(crate) apply: bool,
+
+    #[clap(flatten)]
+    pub(crate) progress: ProgressOptions,
+}
+
+/// Perform a factory reset
+#[derive(Debug, Parser, PartialEq, Eq)]
+pub(crate) struct FactoryResetOpts {
+    /// An additional path in `/etc` or `/var` to preserve, e.g. `/var/lib/containers`.
+    /// A trailing `*` in the file name matches all files with that prefix.
+    #[clap(long, value_name = "PATH")]
+    pub(crate) keep: Vec<Utf8PathBuf>,
+
+    /// Do not preserve the default set of paths which identify the host,
+    /// such as `/etc/machine-id` and the SSH host keys.
+    #[clap(long)]
+    pub(crate) no_default_keep: bool,
+
+    /// The name of the new stateroot, as for `bootc install --stateroot`. Defaults to
+    /// `reset-` followed by the current time.
+    #[clap(long)]
+    pub(crate) stateroot: Option<String>,
+
+    /// Restart or reboot into the new deployment.
+    #[clap(long)]
+    pub(crate) apply: bool,
+}
+
 #[derive(Debug, Clone, ValueEnum, PartialEq, Eq)]
 #[clap(rename_all = "lowercase")]
 pub(crate) enum OutputFormat {
@@ -169,16 +218,76 @@ pub(crate) struct StatusOpts {
     #[clap(long)]
     pub(crate) format: Option<OutputFormat>,
 
-    /// The desired format version. There is currently one supported
-    /// version, which is exposed as both `0` and `1`. Pass this
-    /// option to explicitly request it; it is possible that another future
-    /// version 2 or newer will be supported in the future.
+    /// The desired format version. Version `1` (also exposed as `0`) is
+    /// the stable default; version `2` adds more details about each boot
+    /// entry, such as the kernel version and the local changes of `/etc`.
+    /// Pass this option to explicitly request a version.
     #[clap(long)]
     pub(crate) format_version: Option<u32>,
 
     /// Only display status for the booted deployment.
     #[clap(long)]
     pub(crate) booted: bool,
+
+    /// Do not exit; output the status again each time it changes.
+    ///
+    /// With `--format=json`, each status is a single line (JSON Lines);
+    /// with `--format=yaml`, each status is a separate YAML document.
+    #[clap(long)]
+    pub(crate) watch: bool,
+
+    /// Output the history of changes to the host (upgrades, switches, rollbacks
+    /// and other edits) instead of the current status.
+    #[clap(long, conflicts_with = "watch")]
+    pub(crate) history: bool,
+
+    /// Include the changes a reboot into the staged deployment would apply:
+    /// container image layers, components (e.g. packages), kernel arguments,
+    /// and files in `/usr`. For JSON and YAML, this requires `--format-version=2`.
+    #[clap(long, conflicts_with = "history")]
+    pub(crate) diff: bool,
+}
+
+/// Subcommands which operate on the configuration in `/etc`.
+#[derive(Debug, clap::Subcommand, PartialEq, Eq)]
+pub(crate) enum ConfigOpts {
+    /// Show the local changes of `/etc` relative to the defaults from the image.
+    ///
+    /// The `/etc` of the booted deployment is compared against the defaults
+    /// shipped in the image, which are stored in `/usr/etc`. Added (`A`), removed (`D`)
+    /// and modified (`M`) files are listed; a file is modified if its content,
+    /// permissions or ownership differ. Added or removed directories are listed
+    /// without their contents.
+    Diff {
+        /// The output format.
+        #[clap(long)]
+        format: Option<OutputFormat>,
+
+        /// Also show the changes of the content of modified files, as unified diffs.
+        #[clap(long)]
+        content: bool,
+    },
+    /// Restore `/etc` to the defaults from the image, discarding local changes.
+    ///
+    /// A new deployment of the current image is queued for the next boot, in which the
+    /// provided paths are restored to the defaults shipped in the image (or removed, if
+    /// there is no default). With `--all`, all of `/etc` is reset, except for the paths
+    /// preserved by `bootc factory-reset` and the user and group databases. The running
+    /// system is not changed; any staged deployment is discarded. Until the host is
+    /// rebooted into the new deployment, staging other deployments is refused.
+    Reset {
+        /// Paths in `/etc` to reset, e.g. `/etc/ssh/sshd_config`; directories are
+        /// reset including their contents.
+        paths: Vec<Utf8PathBuf>,
+
+        /// Reset all of `/etc`; this is required if no paths are provided.
+        #[clap(long, conflicts_with = "paths")]
+        all: bool,
+
+        /// Restart or reboot into the new deployment.
+        #[clap(long)]
+        apply: bool,
+    },
 }
 
 #[derive(Debug, clap::Subcommand, PartialEq, Eq)]
@@ -253,6 +362,13 @@ pub(crate) enum ContainerOpts {
         #[clap(long, default_value = "/")]
         rootfs: Utf8PathBuf,
 
+        /// Operate on the provided container image (e.g. `oci-archive:foo.tar` or
+        /// `containers-storage:quay.io/example/os`) without running it. Each
+        /// finding is attributed to the layer which introduced it. The image is
+        /// unpacked in `$TMPDIR`, or `/var/tmp` if unset.
+        #[clap(long, conflicts_with = "rootfs")]
+        image: Option<String>,
+
         /// Make warnings fatal.
         #[clap(long)]
         fatal_warnings: bool,
@@ -377,6 +493,25 @@ pub(crate) enum ImageOpts {
         /// The image to pull
         image: String,
     },
+    /// Show details of a logically bound image: its digest and size in the bootc
+    /// storage, and the deployments which reference it.
+    Inspect {
+        /// The image name, as referenced by a bound image file
+        image: String,
+        #[clap(long = "format")]
+        #[arg(default_value_t)]
+        format: ImageListFormat,
+    },
+    /// Verify that the logically bound images of the staged, booted and rollback
+    /// deployments are present in the bootc storage, and match the digests
+    /// recorded when the deployments were staged.
+    Verify,
+    /// Remove images from the bootc storage which are not referenced by any deployment.
+    Prune {
+        /// Only print the images which would be removed.
+        #[clap(long)]
+        dry_run: bool,
+    },
     /// List fetched images stored in the bootc storage.
     ///
     /// Note that these are distinct from images stored via e.g. `podman`.
@@ -387,6 +522,7 @@ pub(crate) enum ImageOpts {
 #[derive(Debug, Clone, clap::ValueEnum, PartialEq, Eq)]
 pub(crate) enum SchemaType {
     Host,
+    HostV2,
     Progress,
 }
 
@@ -443,9 +579,25 @@ pub(crate) enum InternalsOpts {
         // The stateroot
         stateroot: String,
     },
+    /// Serve the local varlink API; normally socket activated via `bootc-varlink.socket`.
+    Varlink {
+        /// Listen on this socket path instead of using socket activation
+        #[clap(long)]
+        address: Option<Utf8PathBuf>,
+    },
     #[cfg(feature = "rhsm")]
     /// Publish subscription-manager facts to /etc/rhsm/facts/bootc.facts
     PublishRhsmFacts,
+    /// Write the facts about the host images to the files configured
+    /// in /usr/lib/bootc/facts.d
+    PublishFacts,
+    /// Output Prometheus metrics describing the host state
+    Metrics {
+        /// Atomically write the metrics to this file (e.g. for the node_exporter
+        /// textfile collector) instead of standard output
+        #[clap(long)]
+        textfile: Option<Utf8PathBuf>,
+    },
 }
 
 #[derive(Debug, clap::Subcommand, PartialEq, Eq)]
@@ -519,6 +671,16 @@ pub(crate) enum Opt {
     ///
     /// Only changes to the `spec` section are honored.
     Edit(EditOpts),
+    /// Display or change the kernel arguments.
+    ///
+    /// With no options, the kernel arguments of the deployment queued for the
+    /// next boot are printed.
+    ///
+    /// Otherwise, a new deployment of the current image with the changed kernel
+    /// arguments is staged. Kernel arguments changed this way are tracked separately
+    /// from those provided by the image in `/usr/lib/bootc/kargs.d`, and are
+    /// carried across upgrades; they are shown in the `spec.kargs` field of the host.
+    Kargs(KargsOpts),
     /// Display status
     ///
     /// If standard output is a terminal, this will output a description of the bootc system state.
@@ -535,6 +697,26 @@ pub(crate) enum Opt {
     ///
     /// Invoke e.g. `bootc status --json`, and check if `status.booted` is not `null`.
     Status(StatusOpts),
+    /// Operations on the configuration in `/etc`.
+    ///
+    /// On each upgrade, the changes made locally to `/etc` are merged with the defaults
+    /// from the new image.
+    #[clap(subcommand)]
+    Config(ConfigOpts),
+    /// Reset the host to the state of a fresh installation of the booted image.
+    ///
+    /// The booted image is deployed into a new stateroot, with the defaults of `/etc`
+    /// from the image and an empty `/var`. Only the paths which identify the host,
+    /// such as `/etc/machine-id`, the SSH host keys, the network configuration, the
+    /// mounted filesystems (`/etc/fstab` and `/etc/crypttab`) and the pull secrets
+    /// (`/etc/ostree/auth.json` and `/etc/containers/auth.json`), and those provided
+    /// via `--keep` are copied over. The kernel arguments are retained.
+    ///
+    /// The new deployment is queued for the next boot; until the host is rebooted into
+    /// it, staging other deployments is refused. The previous stateroot, including its
+    /// `/var`, is left on disk; it can be removed via `ostree admin undeploy` once the
+    /// reset is complete.
+    FactoryReset(FactoryResetOpts),
     /// Adds a transient writable overlayfs on `/usr` that will be discarded on reboot.
     ///
     /// ## Use cases
@@ -721,8 +903,13 @@ fn prepare_for_write() -> Result<()> {
 async fn upgrade(opts: UpgradeOpts) -> Result<()> {
     let sysroot = &get_storage().await?;
     let repo = &sysroot.repo();
-    let (booted_deployment, _deployments, host) =
-        crate::status::get_status_require_booted(sysroot)?;
+    let (booted_deployment, deployments, host) = crate::status::get_status_require_booted(sysroot)?;
+    let tx = Transition::begin(
+        Operation::Upgrade,
+        Some(&booted_deployment),
+        &deployments,
+        &host,
+    );
     let imgref = host.spec.image.as_ref();
     let prog: ProgressWriter = opts.progress.try_into()?;
 
@@ -781,8 +968,10 @@ async fn upgrade(opts: UpgradeOpts) -> Result<()> {
                 }
             }
         }
+        crate::metrics::record_fetch(sysroot, true, None);
     } else {
         let fetched = crate::deploy::pull(repo, imgref, None, opts.quiet, prog.clone()).await?;
+        crate::metrics::record_fetch(sysroot, true, Some(fetched.bytes_fetched));
         let staged_digest = staged_image.map(|s| s.digest().expect("valid digest in status"));
         let fetched_digest = &fetched.manifest_digest;
         tracing::debug!("staged: {staged_digest:?}");
@@ -806,6 +995,7 @@ async fn upgrade(opts: UpgradeOpts) -> Result<()> {
         } else {
             let osname = booted_deployment.osname();
             crate::deploy::stage(sysroot, &osname, &fetched, &spec, prog.clone()).await?;
+            tx.commit(sysroot);
             changed = true;
             if let Some(prev) = booted_image.as_ref() {
                 if let Some(fetched_manifest) = fetched.get_manifest(repo)? {
@@ -861,8 +1051,13 @@ async fn switch(opts: SwitchOpts) -> Result<()> {
 
     let sysroot = &get_storage().await?;
     let repo = &sysroot.repo();
-    let (booted_deployment, _deployments, host) =
-        crate::status::get_status_require_booted(sysroot)?;
+    let (booted_deployment, deployments, host) = crate::status::get_status_require_booted(sysroot)?;
+    let tx = Transition::begin(
+        Operation::Switch,
+        Some(&booted_deployment),
+        &deployments,
+        &host,
+    );
 
     let new_spec = {
         let mut new_spec = host.spec.clone();
@@ -877,6 +1072,7 @@ async fn switch(opts: SwitchOpts) -> Result<()> {
     let new_spec = RequiredHostSpec::from_spec(&new_spec)?;
 
     let fetched = crate::deploy::pull(repo, &target, None, opts.quiet, prog.clone()).await?;
+    crate::metrics::record_fetch(sysroot, false, Some(fetched.bytes_fetched));
 
     if !opts.retain {
         // By default, we prune the previous ostree ref so it will go away after later upgrades
@@ -891,6 +1087,7 @@ async fn switch(opts: SwitchOpts) -> Result<()> {
 
     let stateroot = booted_deployment.osname();
     crate::deploy::stage(sysroot, &stateroot, &fetched, &new_spec, prog.clone()).await?;
+    tx.commit(sysroot);
 
     sysroot.update_mtime()?;
 
@@ -914,8 +1111,7 @@ async fn edit(opts: EditOpts) -> Result<()> {
     let sysroot = &get_storage().await?;
     let repo = &sysroot.repo();
 
-    let (booted_deployment, _deployments, host) =
-        crate::status::get_status_require_booted(sysroot)?;
+    let (booted_deployment, deployments, host) = crate::status::get_status_require_booted(sysroot)?;
     let new_host: Host = if let Some(filename) = opts.filename {
         let mut r = std::io::BufReader::new(std::fs::File::open(filename)?);
         serde_yaml::from_reader(&mut r)?
@@ -933,24 +1129,180 @@ async fn edit(opts: EditOpts) -> Result<()> {
     }
     host.spec.verify_transition(&new_host.spec)?;
     let new_spec = RequiredHostSpec::from_spec(&new_host.spec)?;
+    let tx = Transition::begin(
+        Operation::Edit,
+        Some(&booted_deployment),
+        &deployments,
+        &host,
+    );
 
     let prog = ProgressWriter::default();
 
-    // We only support two state transitions right now; switching the image,
-    // or flipping the bootloader ordering.
+    // We only support a few state transitions right now; switching the image
+    // and/or changing the kernel arguments, or flipping the bootloader ordering.
     if host.spec.boot_order != new_host.spec.boot_order {
         return crate::deploy::rollback(sysroot).await;
     }
 
-    let fetched = crate::deploy::pull(repo, new_spec.image, None, opts.quiet, prog.clone()).await?;
+    // If only the kernel arguments changed, there's no need to pull.
+    let fetched = if host.spec.image == new_host.spec.image {
+        current_image_state(repo, &host)?
+    } else {
+        let fetched =
+            crate::deploy::pull(repo, new_spec.image, None, opts.quiet, prog.clone()).await?;
+        crate::metrics::record_fetch(sysroot, false, Some(fetched.bytes_fetched));
+        fetched
+    };
 
     // TODO gc old layers here
 
     let stateroot = booted_deployment.osname();
     crate::deploy::stage(sysroot, &stateroot, &fetched, &new_spec, prog.clone()).await?;
+    tx.commit(sysroot);
+
+    sysroot.update_mtime()?;
+
+    Ok(())
+}
+
+/// Find the image of the deployment queued for the next boot.
+fn current_image_state(repo: &ostree::Repo, host: &Host) -> Result<Box<ImageState>> {
+    let image = host
+        .status
+        .staged
+        .as_ref()
+        .or(host.status.booted.as_ref())
+        .map(|entry| entry.query_image(repo))
+        .transpose()?
+        .flatten()
+        .ok_or_else(|| anyhow::anyhow!("No container image found for the current deployment"))?;
+    Ok(Box::new((*image).into()))
+}
+
+/// Implementation of the `bootc kargs` CLI command.
+#[context("Changing kernel arguments")]
+async fn kargs(opts: KargsOpts) -> Result<()> {
+    let sysroot = &get_storage().await?;
+    let repo = &sysroot.repo();
+
+    let (booted_deployment, deployments, host) = crate::status::get_status_require_booted(sysroot)?;
+    let current = deployments.staged.as_ref().unwrap_or(&booted_deployment);
+    let current_kargs = current
+        .bootconfig()
+        .and_then(|bootconfig| bootconfig.get("options"))
+        .map(|options| crate::kernel::Cmdline::parse(&options))
+        .unwrap_or_default();
+
+    if opts.append.is_empty() && opts.delete.is_empty() && opts.replace.is_empty() {
+        println!("{current_kargs}");
+        return Ok(());
+    }
+    let mut current_kargs = current_kargs.into_args();
+
+    let new_spec = {
+        let mut new_spec = host.spec.clone();
+        crate::kargs::edit_local_kargs(
+            &mut current_kargs,
+            &mut new_spec.kargs,
+            &opts.append,
+            &opts.delete,
+            &opts.replace,
+        )?;
+        new_spec
+    };
+    if new_spec == host.spec {
+        println!("Kernel arguments are unchanged.");
+        return Ok(());
+    }
+    host.spec.verify_transition(&new_spec)?;
+    let new_spec = RequiredHostSpec::from_spec(&new_spec)?;
+    let prog: ProgressWriter = opts.progress.try_into()?;
+
+    let tx = Transition::begin(
+        Operation::Kargs,
+        Some(&booted_deployment),
+        &deployments,
+        &host,
+    );
+    let image = current_image_state(repo, &host)?;
+    let stateroot = booted_deployment.osname();
+    crate::deploy::stage(sysroot, &stateroot, &image, &new_spec, prog).await?;
+    tx.commit(sysroot);
+
+    sysroot.update_mtime()?;
+
+    if opts.apply {
+        crate::reboot::reboot()?;
+    }
+
+    Ok(())
+}
+
+/// Implementation of the `bootc config reset` CLI command; if `paths` is empty,
+/// all of `/etc` is reset.
+#[context("Resetting configuration")]
+async fn config_reset(paths: &[Utf8PathBuf], apply: bool) -> Result<()> {
+    let sysroot = &get_storage().await?;
+    let repo = &sysroot.repo();
+    let (booted_deployment, deployments, host) = crate::status::get_status_require_booted(sysroot)?;
+    let spec = RequiredHostSpec::from_spec(&host.spec)?;
+
+    let tx = Transition::begin(
+        Operation::ConfigReset,
+        Some(&booted_deployment),
+        &deployments,
+        &host,
+    );
+    let image = current_image_state(repo, &host)?;
+    let stateroot = booted_deployment.osname();
+    crate::deploy::reset_etc(sysroot, &stateroot, &image, &spec, paths).await?;
+    tx.commit(sysroot);
+
+    sysroot.update_mtime()?;
+
+    if apply {
+        crate::reboot::reboot()?;
+    }
+
+    Ok(())
+}
+
+/// Implementation of the `bootc factory-reset` CLI command.
+#[context("Performing factory reset")]
+async fn factory_reset(opts: FactoryResetOpts) -> Result<()> {
+    let sysroot = &get_storage().await?;
+    let (booted_deployment, deployments, host) = crate::status::get_status_require_booted(sysroot)?;
+    let stateroot = opts.stateroot.unwrap_or_else(|| {
+        chrono::Local::now()
+            .format("reset-%Y%m%d%H%M%S")
+            .to_string()
+    });
+    let mut keep = Vec::new();
+    if !opts.no_default_keep {
+        keep.extend(
+            crate::factory_reset::DEFAULT_KEEP
+                .iter()
+                .map(Utf8PathBuf::from),
+        );
+    }
+    keep.extend(opts.keep);
+
+    let tx = Transition::begin(
+        Operation::FactoryReset,
+        Some(&booted_deployment),
+        &deployments,
+        &host,
+    );
+    crate::factory_reset::factory_reset(sysroot, &booted_deployment, &host, &stateroot, &keep)
+        .await?;
+    tx.commit(sysroot);
 
     sysroot.update_mtime()?;
 
+    if opts.apply {
+        crate::reboot::reboot()?;
+    }
+
     Ok(())
 }
 
@@ -1047,14 +1399,17 @@ impl Opt {
 async fn run_from_opt(opt: Opt) -> Result<()> {
     let root = &Dir::open_ambient_dir("/", cap_std::ambient_authority())?;
     match opt {
-        Opt::Upgrade(opts) => upgrade(opts).await,
-        Opt::Switch(opts) => switch(opts).await,
-        Opt::Rollback(opts) => rollback(opts).await,
-        Opt::Edit(opts) => edit(opts).await,
+        Opt::Upgrade(opts) if opts.check => upgrade(opts).await,
+        Opt::Upgrade(opts) => record_failures(Operation::Upgrade, upgrade(opts)).await,
+        Opt::Switch(opts) => record_failures(Operation::Switch, switch(opts)).await,
+        Opt::Rollback(opts) => record_failures(Operation::Rollback, rollback(opts)).await,
+        Opt::Edit(opts) => record_failures(Operation::Edit, edit(opts)).await,
+        Opt::Kargs(opts) => record_failures(Operation::Kargs, kargs(opts)).await,
         Opt::UsrOverlay => usroverlay().await,
         Opt::Container(opts) => match opts {
             ContainerOpts::Lint {
                 rootfs,
+                image,
                 fatal_warnings,
                 list,
                 skip,
@@ -1067,6 +1422,10 @@ async fn run_from_opt(opt: Opt) -> Result<()> {
                 } else {
                     lints::WarningDisposition::AllowWarnings
                 };
+                let skip = skip.iter().map(|s| s.as_str());
+                if let Some(image) = image {
+                    return lints::lint_image(&image, warnings, skip, std::io::stdout()).await;
+                }
                 let root_type = if rootfs == "/" {
                     lints::RootType::Running
                 } else {
@@ -1074,7 +1433,6 @@ async fn run_from_opt(opt: Opt) -> Result<()> {
                 };
 
                 let root = &Dir::open_ambient_dir(rootfs, cap_std::ambient_authority())?;
-                let skip = skip.iter().map(|s| s.as_str());
                 lints::lint(root, warnings, root_type, skip, std::io::stdout().lock())?;
                 Ok(())
             }
@@ -1094,6 +1452,11 @@ async fn run_from_opt(opt: Opt) -> Result<()> {
                     .pull_from_host_storage(&image)
                     .await
             }
+            ImageOpts::Inspect { image, format } => {
+                crate::image::inspect_entrypoint(&image, format).await
+            }
+            ImageOpts::Verify => crate::image::verify_entrypoint().await,
+            ImageOpts::Prune { dry_run } => crate::image::prune_entrypoint(dry_run).await,
             ImageOpts::Cmd(opt) => {
                 let storage = get_storage().await?;
                 let imgstore = storage.get_ensure_imgstore()?;
@@ -1135,6 +1498,18 @@ async fn run_from_opt(opt: Opt) -> Result<()> {
             crate::install::exec_in_host_mountns(args.as_slice())
         }
         Opt::Status(opts) => super::status::status(opts).await,
+        Opt::Config(opts) => match opts {
+            ConfigOpts::Diff { format, content } => {
+                crate::etcdiff::diff_entrypoint(format, content).await
+            }
+            ConfigOpts::Reset { paths, all, apply } => {
+                anyhow::ensure!(all || !paths.is_empty(), "Provide paths to reset, or --all");
+                record_failures(Operation::ConfigReset, config_reset(&paths, apply)).await
+            }
+        },
+        Opt::FactoryReset(opts) => {
+            record_failures(Operation::FactoryReset, factory_reset(opts)).await
+        }
         Opt::Internals(opts) => match opts {
             InternalsOpts::SystemdGenerator {
                 normal_dir,
@@ -1177,6 +1552,7 @@ async fn run_from_opt(opt: Opt) -> Result<()> {
             InternalsOpts::PrintJsonSchema { of } => {
                 let schema = match of {
                     SchemaType::Host => schema_for!(crate::spec::Host),
+                    SchemaType::HostV2 => schema_for!(crate::spec::v2::Host),
                     SchemaType::Progress => schema_for!(crate::progress_jsonl::Event),
                 };
                 let mut stdout = std::io::stdout().lock();
@@ -1191,8 +1567,13 @@ async fn run_from_opt(opt: Opt) -> Result<()> {
                 let rootfs = &Dir::open_ambient_dir("/", cap_std::ambient_authority())?;
                 crate::install::completion::run_from_ostree(rootfs, &sysroot, &stateroot).await
             }
+            InternalsOpts::Varlink { address } => crate::varlink::serve(address.as_deref()).await,
             #[cfg(feature = "rhsm")]
             InternalsOpts::PublishRhsmFacts => crate::rhsm::publish_facts(&root).await,
+            InternalsOpts::PublishFacts => crate::facts::publish(root).await,
+            InternalsOpts::Metrics { textfile } => {
+                crate::metrics::metrics(textfile.as_deref()).await
+            }
         },
         #[cfg(feature = "docgen")]
         Opt::Man(manopts) => crate::docgen::generate_manpages(&manopts.directory),
@@ -1278,7 +1659,10 @@ mod tests {
                 json: false,
                 format: None,
                 format_version: None,
-                booted: false
+                booted: false,
+                watch: false,
+                history: false,
+                diff: false
             })
         ));
         assert!(matches!(
@@ -1288,6 +1672,87 @@ mod tests {
                 ..
             })
         ));
+        assert!(matches!(
+            Opt::parse_including_static(["bootc", "status", "--watch", "--format=json"]),
+            Opt::Status(StatusOpts {
+                watch: true,
+                format: Some(OutputFormat::Json),
+                ..
+            })
+        ));
+        assert!(matches!(
+            Opt::parse_including_static(["bootc", "status", "--history"]),
+            Opt::Status(StatusOpts { history: true, .. })
+        ));
+        assert!(Opt::try_parse_from(["bootc", "status", "--history", "--watch"]).is_err());
+        assert!(matches!(
+            Opt::parse_including_static(["bootc", "status", "--diff", "--format=json"]),
+            Opt::Status(StatusOpts { diff: true, .. })
+        ));
+    }
+
+    #[test]
+    fn test_parse_image_opts() {
+        assert!(matches!(
+            Opt::parse_including_static(["bootc", "image", "prune", "--dry-run"]),
+            Opt::Image(ImageOpts::Prune { dry_run: true })
+        ));
+        assert!(matches!(
+            Opt::parse_including_static(["bootc", "image", "inspect", "quay.io/example/app:latest"]),
+            Opt::Image(ImageOpts::Inspect { image, format: ImageListFormat::Table }) if image == "quay.io/example/app:latest"
+        ));
+    }
+
+    #[test]
+    fn test_parse_factory_reset() {
+        assert!(matches!(
+            Opt::parse_including_static(["bootc", "factory-reset"]),
+            Opt::FactoryReset(FactoryResetOpts { keep, no_default_keep: false, stateroot: None, apply: false }) if keep.is_empty()
+        ));
+        assert!(matches!(
+            Opt::parse_including_static([
+                "bootc",
+                "factory-reset",
+                "--keep=/var/lib/containers",
+                "--keep=/etc/pki/*",
+                "--stateroot=fresh",
+                "--apply"
+            ]),
+            Opt::FactoryReset(FactoryResetOpts { keep, stateroot: Some(s), apply: true, .. }) if keep == ["/var/lib/containers", "/etc/pki/*"] && s == "fresh"
+        ));
+    }
+
+    #[test]
+    fn test_parse_config_reset() {
+        assert!(matches!(
+            Opt::parse_including_static(["bootc", "config", "reset", "/etc/hostname", "/etc/ssh"]),
+            Opt::Config(ConfigOpts::Reset { paths, all: false, apply: false }) if paths == ["/etc/hostname", "/etc/ssh"]
+        ));
+        assert!(matches!(
+            Opt::parse_including_static(["bootc", "config", "reset", "--all", "--apply"]),
+            Opt::Config(ConfigOpts::Reset { paths, all: true, apply: true }) if paths.is_empty()
+        ));
+        assert!(
+            Opt::try_parse_from(["bootc", "config", "reset", "--all", "/etc/hostname"]).is_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_config_opts() {
+        assert!(matches!(
+            Opt::parse_including_static(["bootc", "config", "diff"]),
+            Opt::Config(ConfigOpts::Diff {
+                format: None,
+                content: false
+            })
+        ));
+        assert!(matches!(
+            Opt::parse_including_static(["bootc", "config", "diff", "--content", "--format=json"]),
+            Opt::Config(ConfigOpts::Diff {
+                format: Some(OutputFormat::Json),
+                content: true
+            })
+        ));
     }
 
     #[test]
@@ -7,6 +7,7 @@ use std::io::{BufRead, Write};
 
 use anyhow::Ok;
 use anyhow::{anyhow, Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
 use cap_std::fs::{Dir, MetadataExt};
 use cap_std_ext::cap_std;
 use cap_std_ext::dirext::CapStdExtDirExt;
@@ -15,17 +16,20 @@ use ostree::{gio, glib};
 use ostree_container::OstreeImageReference;
 use ostree_ext::container as ostree_container;
 use ostree_ext::container::store::{ImportProgress, PrepareResult};
+use ostree_ext::keyfileext::KeyFileExt;
 use ostree_ext::oci_spec::image::{Descriptor, Digest};
 use ostree_ext::ostree::Deployment;
 use ostree_ext::ostree::{self, Sysroot};
 use ostree_ext::sysroot::SysrootLock;
 use ostree_ext::tokio_util::spawn_blocking_cancellable_flatten;
 
+use crate::boundimage::BoundImage;
 use crate::progress_jsonl::{Event, ProgressWriter, SubTaskBytes, SubTaskStep};
 use crate::spec::ImageReference;
-use crate::spec::{BootOrder, HostSpec};
+use crate::spec::{BootOrder, HostSpec, LocalKargs};
 use crate::status::labels_of_config;
 use crate::store::Storage;
+use crate::task::Task;
 use crate::utils::async_task_with_spinner;
 
 // TODO use https://github.com/ostreedev/ostree-rs-ext/pull/493/commits/afc1837ff383681b947de30c0cefc70080a4f87a
@@ -37,6 +41,7 @@ const BOOTC_DERIVED_KEY: &str = "bootc.derived";
 /// Variant of HostSpec but required to be filled out
 pub(crate) struct RequiredHostSpec<'a> {
     pub(crate) image: &'a ImageReference,
+    pub(crate) kargs: &'a LocalKargs,
 }
 
 /// State of a locally fetched image
@@ -44,6 +49,8 @@ pub(crate) struct ImageState {
     pub(crate) manifest_digest: Digest,
     pub(crate) version: Option<String>,
     pub(crate) ostree_commit: String,
+    /// The number of bytes downloaded when pulling the image
+    pub(crate) bytes_fetched: u64,
 }
 
 impl<'a> RequiredHostSpec<'a> {
@@ -54,7 +61,10 @@ impl<'a> RequiredHostSpec<'a> {
             .image
             .as_ref()
             .ok_or_else(|| anyhow::anyhow!("Missing image in specification"))?;
-        Ok(Self { image })
+        Ok(Self {
+            image,
+            kargs: &spec.kargs,
+        })
     }
 }
 
@@ -66,6 +76,7 @@ impl From<ostree_container::store::LayeredImageState> for ImageState {
             manifest_digest: value.manifest_digest,
             version,
             ostree_commit,
+            bytes_fetched: 0,
         }
     }
 }
@@ -379,26 +390,43 @@ pub(crate) async fn pull(
     {
         crate::journal::journal_print(libsystemd::logging::Priority::Notice, &msg);
     }
-    Ok(Box::new((*import).into()))
+    let mut state = ImageState::from(*import);
+    state.bytes_fetched = bytes_to_fetch;
+    Ok(Box::new(state))
 }
 
-/// Gather all bound images in all deployments, then prune the image store,
-/// using the gathered images as the roots (that will not be GC'd).
-pub(crate) async fn prune_container_store(sysroot: &Storage) -> Result<()> {
-    let deployments = sysroot.deployments();
+/// Gather all bound images in all deployments.
+pub(crate) fn query_all_bound_images(sysroot: &Storage) -> Result<Vec<BoundImage>> {
     let mut all_bound_images = Vec::new();
-    for deployment in deployments {
+    for deployment in sysroot.deployments() {
         let bound = crate::boundimage::query_bound_images_for_deployment(sysroot, &deployment)?;
         all_bound_images.extend(bound.into_iter());
     }
+    Ok(all_bound_images)
+}
+
+/// Gather all bound images in all deployments, then prune the image store,
+/// using the gathered images as the roots (that will not be GC'd).
+/// Remove the images in the bootc image storage which aren't bound to any deployment;
+/// return the IDs of the removed images.
+pub(crate) async fn prune_container_store(sysroot: &Storage) -> Result<Vec<String>> {
+    let all_bound_images = query_all_bound_images(sysroot)?;
+    let deployments = sysroot.deployments();
+    crate::boundimage::prune_recorded_bound_images(sysroot, &deployments)?;
+    // Also keep the recorded digests, which are needed to restore the bound images on rollback
+    let recorded = crate::boundimage::recorded_bound_image_references(sysroot, &deployments)?;
     // Convert to a hashset of just the image names
-    let image_names = HashSet::from_iter(all_bound_images.iter().map(|img| img.image.as_str()));
+    let image_names = all_bound_images
+        .iter()
+        .map(|img| img.image.as_str())
+        .chain(recorded.iter().map(String::as_str))
+        .collect::<HashSet<_>>();
     let pruned = sysroot
         .get_ensure_imgstore()?
         .prune_except_roots(&image_names)
         .await?;
     tracing::debug!("Pruned images: {}", pruned.len());
-    Ok(())
+    Ok(pruned)
 }
 
 pub(crate) async fn wipe_ostree(sysroot: Sysroot) -> Result<()> {
@@ -478,6 +506,26 @@ pub(crate) fn get_base_commit(repo: &ostree::Repo, commit: &str) -> Result<Optio
     Ok(r)
 }
 
+/// Compute the kernel arguments of a new deployment of `image`, based on the
+/// merge deployment.
+fn override_kargs(
+    sysroot: &Storage,
+    merge_deployment: &Deployment,
+    image: &ImageState,
+    origin: &glib::KeyFile,
+    local_kargs: &LocalKargs,
+) -> Result<Vec<String>> {
+    let mut kargs = crate::kargs::get_kargs(sysroot, merge_deployment, image, origin)?;
+    // Locally managed kargs are applied relative to what the merge deployment had
+    let previous = merge_deployment
+        .origin()
+        .map(|origin| crate::kargs::local_kargs_from_origin(&origin))
+        .transpose()?
+        .unwrap_or_default();
+    crate::kargs::apply_local_kargs(&mut kargs, &previous, local_kargs);
+    Ok(kargs)
+}
+
 #[context("Writing deployment")]
 async fn deploy(
     sysroot: &Storage,
@@ -485,16 +533,15 @@ async fn deploy(
     stateroot: &str,
     image: &ImageState,
     origin: &glib::KeyFile,
+    local_kargs: &LocalKargs,
 ) -> Result<Deployment> {
     // Compute the kernel argument overrides. In practice today this API is always expecting
     // a merge deployment. The kargs code also always looks at the booted root (which
     // is a distinct minor issue, but not super important as right now the install path
     // doesn't use this API).
-    let override_kargs = if let Some(deployment) = merge_deployment {
-        Some(crate::kargs::get_kargs(sysroot, &deployment, image)?)
-    } else {
-        None
-    };
+    let override_kargs = merge_deployment
+        .map(|deployment| override_kargs(sysroot, deployment, image, origin, local_kargs))
+        .transpose()?;
     // Clone all the things to move to worker thread
     let sysroot_clone = sysroot.sysroot.clone();
     // ostree::Deployment is incorrently !Send 😢 so convert it to an integer
@@ -561,6 +608,8 @@ pub(crate) async fn stage(
     spec: &RequiredHostSpec<'_>,
     prog: ProgressWriter,
 ) -> Result<()> {
+    ensure_no_pending_factory_reset(sysroot, stateroot)?;
+    ensure_no_pending_etc_reset(sysroot)?;
     let mut subtask = SubTaskStep {
         subtask: "merging".into(),
         description: "Merging Image".into(),
@@ -605,12 +654,14 @@ pub(crate) async fn stage(
     })
     .await;
     let origin = origin_from_imageref(spec.image)?;
+    crate::kargs::set_origin_local_kargs(&origin, spec.kargs);
     let deployment = crate::deploy::deploy(
         sysroot,
         merge_deployment.as_ref(),
         stateroot,
         image,
         &origin,
+        spec.kargs,
     )
     .await?;
 
@@ -634,7 +685,13 @@ pub(crate) async fn stage(
             .collect(),
     })
     .await;
-    crate::boundimage::pull_bound_images(sysroot, &deployment).await?;
+    crate::boundimage::pull_bound_images(
+        sysroot,
+        &deployment,
+        merge_deployment.as_ref(),
+        prog.clone(),
+    )
+    .await?;
 
     subtask.completed = true;
     subtasks.push(subtask.clone());
@@ -683,11 +740,233 @@ pub(crate) async fn stage(
     Ok(())
 }
 
+/// Resolve a path given to `bootc config reset` to a path relative to the
+/// deployment root, which must be in `etc`.
+fn etc_reset_path(path: &Utf8Path) -> Result<Utf8PathBuf> {
+    let rel = path
+        .strip_prefix("/")
+        .map_err(|_| anyhow!("Not an absolute path: {path}"))?;
+    let valid = rel.starts_with("etc")
+        && rel.components().count() > 1
+        && rel
+            .components()
+            .all(|c| matches!(c, camino::Utf8Component::Normal(_)));
+    anyhow::ensure!(valid, "Not a path in /etc: {path}");
+    Ok(rel.to_owned())
+}
+
+/// Restore the path `rel` in `etc` of the deployment root to the image
+/// default in `usr/etc`; if there is no default, it is removed.
+#[context("Restoring /{rel}")]
+fn restore_etc_path(root: &Dir, rel: &Utf8Path) -> Result<()> {
+    let pristine = Utf8Path::new("usr").join(rel);
+    let has_default = root.symlink_metadata_optional(&pristine)?.is_some();
+    let removed = root.remove_all_optional(rel)?;
+    anyhow::ensure!(
+        has_default || removed,
+        "Not found in /etc or the image defaults"
+    );
+    if has_default {
+        if let Some(parent) = rel.parent() {
+            let parent_exists = root
+                .symlink_metadata_optional(parent)?
+                .is_some_and(|m| m.is_dir());
+            anyhow::ensure!(parent_exists, "Missing parent directory /{parent}");
+        }
+        // Use cp to also copy extended attributes such as SELinux labels
+        Task::new(format!("Restoring /{rel}"), "cp")
+            .cwd(root)?
+            .args(["-a", "--reflink=auto", "--"])
+            .args([pristine.as_str(), rel.as_str()])
+            .quiet()
+            .run()?;
+    }
+    Ok(())
+}
+
+/// The paths which `bootc config reset --all` preserves in addition to those kept by
+/// a factory reset, as resetting them would lock out users.
+const ETC_RESET_KEEP: &[&str] = &[
+    "/etc/passwd",
+    "/etc/shadow",
+    "/etc/group",
+    "/etc/gshadow",
+    "/etc/subuid",
+    "/etc/subgid",
+];
+
+/// Copy the paths preserved by `bootc config reset --all` from `/etc` in `root` to
+/// the deployment root at `dest`, replacing the image defaults.
+fn copy_etc_keep(root: &Dir, dest: &Utf8Path) -> Result<()> {
+    let paths = crate::factory_reset::DEFAULT_KEEP
+        .iter()
+        .chain(ETC_RESET_KEEP)
+        .map(Utf8Path::new)
+        .filter(|p| p.starts_with("/etc"));
+    for path in paths {
+        for rel in crate::factory_reset::expand_keep(root, path)? {
+            crate::factory_reset::copy_path(root, &rel, dest)?;
+        }
+    }
+    Ok(())
+}
+
+/// The origin key of a deployment created by `bootc config reset`, naming the
+/// deployment it was created from.
+const ORIGIN_ETC_RESET_FROM: &str = "etc-reset-from";
+
+/// Identify a deployment within its stateroot.
+fn deployment_id(deployment: &Deployment) -> String {
+    format!("{}.{}", deployment.csum(), deployment.deployserial())
+}
+
+/// Returns true if `deployment` was created by `bootc config reset` from the booted
+/// deployment, and is queued for the next boot.
+pub(crate) fn is_pending_etc_reset(deployment: &Deployment, booted: &Deployment) -> Result<bool> {
+    if deployment.is_staged()
+        || deployment.index() >= booted.index()
+        || deployment.osname() != booted.osname()
+    {
+        return Ok(false);
+    }
+    let Some(origin) = deployment.origin() else {
+        return Ok(false);
+    };
+    let from = origin.optional_string("bootc", ORIGIN_ETC_RESET_FROM)?;
+    Ok(from.as_deref() == Some(deployment_id(booted).as_str()))
+}
+
+/// Fail if a `bootc config reset` is queued for the next boot; a staged deployment
+/// would replace it, and its `/etc` would be merged from the booted deployment.
+fn ensure_no_pending_etc_reset(sysroot: &Storage) -> Result<()> {
+    let Some(booted) = sysroot.booted_deployment() else {
+        return Ok(());
+    };
+    for deployment in sysroot.deployments() {
+        anyhow::ensure!(
+            !is_pending_etc_reset(&deployment, &booted)?,
+            "A configuration reset is queued for the next boot; reboot into it first"
+        );
+    }
+    Ok(())
+}
+
+/// Fail if a factory reset, i.e. a staged deployment in a stateroot other than
+/// `stateroot`, is queued for the next boot; it would be replaced.
+fn ensure_no_pending_factory_reset(sysroot: &Storage, stateroot: &str) -> Result<()> {
+    if let Some(staged) = sysroot.staged_deployment() {
+        let other = staged.osname();
+        anyhow::ensure!(
+            other == stateroot,
+            "A factory reset into stateroot {other} is queued for the next boot; reboot into it first"
+        );
+    }
+    Ok(())
+}
+
+/// Deploy `image` again with the provided `/etc` paths, or all of `/etc` if empty,
+/// restored to the defaults from the image; when resetting all of `/etc`, the paths
+/// identifying the host and its users are retained. Unlike [`stage`], the configuration merge
+/// happens immediately instead of at shutdown, as the paths need to be reset afterwards;
+/// the new deployment is then written as the default for the next boot.
+/// Any staged deployment is discarded.
+#[context("Resetting configuration")]
+pub(crate) async fn reset_etc(
+    sysroot: &Storage,
+    stateroot: &str,
+    image: &ImageState,
+    spec: &RequiredHostSpec<'_>,
+    paths: &[Utf8PathBuf],
+) -> Result<()> {
+    let paths = paths
+        .iter()
+        .map(|p| etc_reset_path(p))
+        .collect::<Result<Vec<_>>>()?;
+    ensure_no_pending_factory_reset(sysroot, stateroot)?;
+    let merge_deployment = sysroot
+        .merge_deployment(Some(stateroot))
+        .ok_or_else(|| anyhow!("No deployment found for stateroot {stateroot}"))?;
+    let origin = origin_from_imageref(spec.image)?;
+    crate::kargs::set_origin_local_kargs(&origin, spec.kargs);
+    origin.set_string(
+        "bootc",
+        ORIGIN_ETC_RESET_FROM,
+        &deployment_id(&merge_deployment),
+    );
+    let kargs = override_kargs(sysroot, &merge_deployment, image, &origin, spec.kargs)?;
+
+    // Clone all the things to move to worker thread, as in [`deploy`]
+    let sysroot_clone = sysroot.sysroot.clone();
+    let merge_deployment = merge_deployment.index() as usize;
+    let stateroot = stateroot.to_string();
+    let ostree_commit = image.ostree_commit.to_string();
+    let origin_data = origin.to_data();
+    async_task_with_spinner(
+        "Deploying",
+        spawn_blocking_cancellable_flatten(move |cancellable| -> Result<_> {
+            let sysroot = sysroot_clone;
+            let mut opts = ostree::SysrootDeployTreeOpts::default();
+            let kargs = kargs.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+            opts.override_kernel_argv = Some(&kargs);
+            let deployments = sysroot.deployments();
+            let merge_deployment = &deployments[merge_deployment];
+            let origin = glib::KeyFile::new();
+            origin.load_from_data(&origin_data, glib::KeyFileFlags::NONE)?;
+            // Without a merge deployment, `/etc` is a copy of the defaults
+            let d = sysroot.deploy_tree_with_options(
+                Some(&stateroot),
+                &ostree_commit,
+                Some(&origin),
+                (!paths.is_empty()).then_some(merge_deployment),
+                Some(&opts),
+                Some(cancellable),
+            )?;
+            let root = crate::utils::deployment_fd(&sysroot, &d)?;
+            for path in paths.iter() {
+                restore_etc_path(&root, path)?;
+            }
+            if paths.is_empty() {
+                let merge_root = crate::utils::deployment_fd(&sysroot, merge_deployment)?;
+                let sysroot_path = sysroot
+                    .path()
+                    .path()
+                    .ok_or_else(|| anyhow!("Sysroot has no path"))?;
+                let dest = Utf8PathBuf::try_from(sysroot_path)?
+                    .join(sysroot.deployment_dirpath(&d).as_str());
+                copy_etc_keep(&merge_root, &dest)?;
+            }
+            sysroot.simple_write_deployment(
+                Some(&stateroot),
+                &d,
+                Some(merge_deployment),
+                ostree::SysrootSimpleWriteDeploymentFlags::NO_CLEAN,
+                Some(cancellable),
+            )?;
+            Ok(())
+        }),
+    )
+    .await?;
+    sysroot.load(gio::Cancellable::NONE)?;
+    // SAFETY: The new deployment is the default
+    let deployment = sysroot.deployments().into_iter().next().unwrap();
+    crate::boundimage::record_bound_images(sysroot, &deployment)?;
+    crate::deploy::cleanup(sysroot).await?;
+    println!("Queued for next boot: {:#}", spec.image);
+    println!("  Digest: {}", image.manifest_digest);
+    Ok(())
+}
+
 /// Implementation of rollback functionality
 pub(crate) async fn rollback(sysroot: &Storage) -> Result<()> {
     const ROLLBACK_JOURNAL_ID: &str = "26f3b1eb24464d12aa5e7b544a6b5468";
     let repo = &sysroot.repo();
     let (booted_deployment, deployments, host) = crate::status::get_status_require_booted(sysroot)?;
+    let tx = crate::history::Transition::begin(
+        crate::history::Operation::Rollback,
+        Some(&booted_deployment),
+        &deployments,
+        &host,
+    );
 
     let new_spec = {
         let mut new_spec = host.spec.clone();
@@ -733,8 +1012,21 @@ pub(crate) async fn rollback(sysroot: &Storage) -> Result<()> {
         .into_iter()
         .chain(deployments.other)
         .collect::<Vec<_>>();
+    // The bound images are restored on a best effort basis; they must not prevent
+    // changing the boot order.
+    // SAFETY: We have at least two deployments
+    let restore = crate::boundimage::bound_images_to_restore(sysroot, &new_deployments[0])
+        .await
+        .unwrap_or_else(|e| {
+            tracing::warn!("{e:#}");
+            Vec::new()
+        });
     tracing::debug!("Writing new deployments: {new_deployments:?}");
     sysroot.write_deployments(&new_deployments, gio::Cancellable::NONE)?;
+    if let Err(e) = crate::boundimage::restore_bound_images(sysroot, &restore).await {
+        tracing::warn!("{e:#}");
+    }
+    tx.commit(sysroot);
     if reverting {
         println!("Next boot: current deployment");
     } else {
@@ -986,4 +1278,69 @@ UUID=6907-17CA          /boot/efi               vfat    umask=0077,shortname=win
         assert_eq!(tempdir.read_to_string("etc/fstab")?, modified);
         Ok(())
     }
+
+    #[test]
+    fn test_etc_reset_path() {
+        assert_eq!(
+            etc_reset_path(Utf8Path::new("/etc/ssh/sshd_config")).unwrap(),
+            "etc/ssh/sshd_config"
+        );
+        for p in [
+            "etc/foo",
+            "/etc",
+            "/usr/etc/foo",
+            "/etc/../usr/foo",
+            "/etcfoo",
+        ] {
+            assert!(etc_reset_path(Utf8Path::new(p)).is_err(), "{p}");
+        }
+    }
+
+    #[test]
+    fn test_restore_etc_path() -> Result<()> {
+        let td = cap_std_ext::cap_tempfile::tempdir(cap_std::ambient_authority())?;
+        td.create_dir_all("usr/etc/ssh")?;
+        td.create_dir_all("etc/ssh/sshd_config.d")?;
+        td.write("usr/etc/hostname", "default\n")?;
+        td.write("etc/hostname", "modified\n")?;
+        td.write("usr/etc/ssh/sshd_config", "default\n")?;
+        td.write("etc/ssh/sshd_config.d/local.conf", "local\n")?;
+        td.write("etc/added", "x")?;
+
+        restore_etc_path(&td, Utf8Path::new("etc/hostname"))?;
+        assert_eq!(td.read_to_string("etc/hostname")?, "default\n");
+        // Restoring a whole directory drops local additions
+        restore_etc_path(&td, Utf8Path::new("etc/ssh"))?;
+        assert_eq!(td.read_to_string("etc/ssh/sshd_config")?, "default\n");
+        assert!(!td.try_exists("etc/ssh/sshd_config.d")?);
+        // Paths without a default are removed
+        restore_etc_path(&td, Utf8Path::new("etc/added"))?;
+        assert!(!td.try_exists("etc/added")?);
+        assert!(restore_etc_path(&td, Utf8Path::new("etc/missing")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_etc_keep() -> Result<()> {
+        let src = tempfile::tempdir()?;
+        let dest = tempfile::tempdir()?;
+        let root = Dir::open_ambient_dir(src.path(), cap_std::ambient_authority())?;
+        root.create_dir_all("etc/ssh")?;
+        for f in ["fstab", "crypttab", "passwd", "ssh/ssh_host_ed25519_key"] {
+            root.write(format!("etc/{f}"), "local\n")?;
+        }
+        root.write("etc/motd", "local\n")?;
+        let deployment = Dir::open_ambient_dir(dest.path(), cap_std::ambient_authority())?;
+        deployment.create_dir("etc")?;
+        deployment.write("etc/fstab", "default\n")?;
+        deployment.write("etc/motd", "default\n")?;
+
+        copy_etc_keep(&root, Utf8Path::from_path(dest.path()).unwrap())?;
+        for f in ["fstab", "crypttab", "passwd", "ssh/ssh_host_ed25519_key"] {
+            assert_eq!(deployment.read_to_string(format!("etc/{f}"))?, "local\n");
+        }
+        assert_eq!(deployment.read_to_string("etc/motd")?, "default\n");
+        assert!(!deployment.try_exists("etc/shadow")?);
+        Ok(())
+    }
 }
@@ -0,0 +1,333 @@
+//! # Comparing `/etc` with the defaults from the image
+//!
+//! The default configuration shipped in an image is stored in `/usr/etc`,
+//! and `/etc` is a mutable copy of it which is carried across upgrades via
+//! a 3-way merge. This module finds the local changes ("drift") of `/etc`,
+//! and implements `bootc config diff`.
+
+use std::collections::{BTreeMap, HashSet};
+use std::ffi::OsStr;
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use cap_std_ext::cap_std::fs::{Dir, Metadata, MetadataExt};
+use cap_std_ext::dirext::CapStdExtDirExt;
+use fn_error_context::context;
+use serde::Serialize;
+
+use crate::cli::OutputFormat;
+use crate::spec::NamesDiff;
+
+/// The image defaults for `/etc`, relative to the deployment root.
+const USR_ETC: &str = "usr/etc";
+/// The configuration of the deployment, relative to the deployment root.
+const ETC: &str = "etc";
+
+/// Format a path for output; directories have a trailing `/`.
+fn display_path(path: &Path, meta: &Metadata) -> String {
+    if meta.is_dir() {
+        format!("/{}/", path.display())
+    } else {
+        format!("/{}", path.display())
+    }
+}
+
+/// Returns true if the type, ownership and permissions of both entries are identical.
+fn metadata_equal(a: &Metadata, b: &Metadata) -> bool {
+    a.file_type() == b.file_type()
+        && a.mode() == b.mode()
+        && a.uid() == b.uid()
+        && a.gid() == b.gid()
+}
+
+/// Returns true if the metadata and content of the entry `name` are identical in both
+/// directories; for directories, only the metadata is compared.
+fn entry_equal(
+    pristine: &Dir,
+    current: &Dir,
+    name: &OsStr,
+    a: &Metadata,
+    b: &Metadata,
+) -> Result<bool> {
+    if !metadata_equal(a, b) {
+        return Ok(false);
+    }
+    let ty = a.file_type();
+    let r = if ty.is_symlink() {
+        pristine.read_link_contents(name)? == current.read_link_contents(name)?
+    } else if ty.is_file() {
+        a.size() == b.size() && pristine.read(name)? == current.read(name)?
+    } else if ty.is_dir() {
+        true
+    } else {
+        // Device nodes, sockets and FIFOs
+        a.rdev() == b.rdev()
+    };
+    Ok(r)
+}
+
+/// Recursively compare `current` against `pristine`, which are both at `path`.
+fn diff_dirs(pristine: &Dir, current: &Dir, path: &Path, diff: &mut NamesDiff) -> Result<()> {
+    let mut seen = HashSet::new();
+    for ent in pristine.entries()? {
+        let ent = ent?;
+        let name = ent.file_name();
+        let child = path.join(&name);
+        let meta = ent.metadata()?;
+        match current.symlink_metadata_optional(&name)? {
+            None => diff.removed.push(display_path(&child, &meta)),
+            Some(cur) if meta.is_dir() && cur.is_dir() => {
+                if !metadata_equal(&meta, &cur) {
+                    diff.changed.push(display_path(&child, &cur));
+                }
+                diff_dirs(
+                    &pristine.open_dir(&name)?,
+                    &current.open_dir(&name)?,
+                    &child,
+                    diff,
+                )?;
+            }
+            Some(cur) => {
+                if !entry_equal(pristine, current, &name, &meta, &cur)? {
+                    diff.changed.push(display_path(&child, &cur));
+                }
+            }
+        }
+        seen.insert(name);
+    }
+    for ent in current.entries()? {
+        let ent = ent?;
+        let name = ent.file_name();
+        if !seen.contains(&name) {
+            diff.added
+                .push(display_path(&path.join(&name), &ent.metadata()?));
+        }
+    }
+    Ok(())
+}
+
+/// Compute the changes of `/etc` relative to the image defaults in `/usr/etc`
+/// for the provided deployment root. Added or removed directories are listed
+/// without their contents. Returns `None` if either directory does not exist.
+#[context("Comparing /etc")]
+pub(crate) fn diff(root: &Dir) -> Result<Option<NamesDiff>> {
+    let Some(pristine) = root.open_dir_optional(USR_ETC)? else {
+        return Ok(None);
+    };
+    let Some(current) = root.open_dir_optional(ETC)? else {
+        return Ok(None);
+    };
+    let mut r = NamesDiff::default();
+    diff_dirs(&pristine, &current, Path::new(ETC), &mut r)?;
+    r.added.sort();
+    r.removed.sort();
+    r.changed.sort();
+    Ok(Some(r))
+}
+
+/// The output of `bootc config diff`.
+#[derive(Debug, Default, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct Report {
+    /// The changed paths
+    #[serde(flatten)]
+    diff: NamesDiff,
+    /// Unified diffs of the content of changed files, if requested
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    contents: BTreeMap<String, String>,
+}
+
+/// Compute a unified diff for `path` (as returned by [`diff`]) from the image
+/// default. Returns `None` unless it is a regular file in both, with different content.
+fn content_diff(root: &Dir, path: &str) -> Result<Option<String>> {
+    let Some(current) = path.strip_prefix('/') else {
+        return Ok(None);
+    };
+    let pristine = format!("usr/{current}");
+    let is_file = |p: &str| -> Result<bool> {
+        Ok(root
+            .symlink_metadata_optional(p)?
+            .is_some_and(|m| m.is_file()))
+    };
+    if !(is_file(&pristine)? && is_file(current)?) {
+        return Ok(None);
+    }
+    let (old, new) = (root.read(&pristine)?, root.read(current)?);
+    if old == new {
+        return Ok(None);
+    }
+    let old_name = format!("/{pristine}");
+    let r = match (std::str::from_utf8(&old), std::str::from_utf8(&new)) {
+        (Ok(old), Ok(new)) => similar::TextDiff::from_lines(old, new)
+            .unified_diff()
+            .header(&old_name, path)
+            .to_string(),
+        _ => format!("Binary files {old_name} and {path} differ\n"),
+    };
+    Ok(Some(r))
+}
+
+/// Output the changes in the same format as `ostree admin config-diff`, followed
+/// by the content diff of each file if available.
+fn human_readable_output(mut out: impl Write, report: &Report) -> Result<()> {
+    let diff = &report.diff;
+    let mut entries = diff
+        .added
+        .iter()
+        .map(|p| ('A', p))
+        .chain(diff.removed.iter().map(|p| ('D', p)))
+        .chain(diff.changed.iter().map(|p| ('M', p)))
+        .collect::<Vec<_>>();
+    entries.sort_by(|a, b| a.1.cmp(b.1));
+    for (c, path) in entries {
+        writeln!(out, "{c}    {path}")?;
+        if let Some(content) = report.contents.get(path) {
+            write!(out, "{content}")?;
+        }
+    }
+    Ok(())
+}
+
+/// Implementation of `bootc config diff`.
+#[context("Comparing /etc")]
+pub(crate) async fn diff_entrypoint(format: Option<OutputFormat>, content: bool) -> Result<()> {
+    let sysroot = crate::cli::get_storage().await?;
+    let booted_deployment = sysroot.require_booted_deployment()?;
+    let root = crate::utils::deployment_fd(&sysroot, &booted_deployment)?;
+    let diff = diff(&root)?.ok_or_else(|| anyhow::anyhow!("Missing {USR_ETC} or {ETC}"))?;
+    let mut contents = BTreeMap::new();
+    if content {
+        for path in diff.changed.iter() {
+            if let Some(d) = content_diff(&root, path)? {
+                contents.insert(path.clone(), d);
+            }
+        }
+    }
+    let report = Report { diff, contents };
+
+    let mut out = std::io::stdout().lock();
+    match format.unwrap_or(OutputFormat::HumanReadable) {
+        OutputFormat::HumanReadable => human_readable_output(&mut out, &report)?,
+        OutputFormat::Yaml => serde_yaml::to_writer(&mut out, &report)?,
+        OutputFormat::Json => {
+            serde_json::to_writer(&mut out, &report)?;
+            writeln!(out)?;
+        }
+    }
+    out.flush().context("Writing to stdout")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use cap_std_ext::cap_std;
+    use cap_std_ext::cap_std::fs::{Permissions, PermissionsExt};
+
+    use super::*;
+
+    #[test]
+    fn test_diff() -> Result<()> {
+        let td = cap_std_ext::cap_tempfile::tempdir(cap_std::ambient_authority())?;
+        assert_eq!(diff(&td)?, None);
+
+        for d in [USR_ETC, ETC] {
+            td.create_dir_all(format!("{d}/ssh"))?;
+            td.create_dir_all(format!("{d}/removed.d"))?;
+            td.write(format!("{d}/hostname"), "foo\n")?;
+            td.write(format!("{d}/ssh/sshd_config"), "PermitRootLogin no\n")?;
+            td.write(format!("{d}/mode"), "x")?;
+            td.symlink("../usr/share/zoneinfo/UTC", format!("{d}/localtime"))?;
+        }
+        assert_eq!(diff(&td)?, Some(NamesDiff::default()));
+
+        td.write("etc/hostname", "bar\n")?;
+        td.write("etc/ssh/sshd_config", "PermitRootLogin yes\n")?;
+        td.set_permissions("etc/mode", Permissions::from_mode(0o600))?;
+        td.remove_file("etc/localtime")?;
+        td.symlink("../usr/share/zoneinfo/UTC", "etc/localtime")?;
+        td.remove_dir("etc/removed.d")?;
+        td.create_dir_all("etc/added.d/sub")?;
+        td.write("etc/added.d/sub/file", "x")?;
+        td.write("etc/added", "x")?;
+
+        let expected = NamesDiff {
+            added: vec!["/etc/added".into(), "/etc/added.d/".into()],
+            removed: vec!["/etc/removed.d/".into()],
+            changed: vec![
+                "/etc/hostname".into(),
+                "/etc/mode".into(),
+                "/etc/ssh/sshd_config".into(),
+            ],
+        };
+        assert_eq!(diff(&td)?, Some(expected));
+
+        // A symlink with a different target is changed too
+        td.remove_file("etc/localtime")?;
+        td.symlink("../usr/share/zoneinfo/Europe/Berlin", "etc/localtime")?;
+        let changed = diff(&td)?.unwrap().changed;
+        assert!(changed.contains(&"/etc/localtime".to_owned()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_content_diff() -> Result<()> {
+        let td = cap_std_ext::cap_tempfile::tempdir(cap_std::ambient_authority())?;
+        for d in [USR_ETC, ETC] {
+            td.create_dir_all(d)?;
+            td.write(format!("{d}/same"), "x\n")?;
+            td.write(format!("{d}/binary"), b"\xff\x00")?;
+        }
+        td.write("usr/etc/hostname", "foo\n")?;
+        td.write("etc/hostname", "bar\n")?;
+        td.write("etc/binary", b"\xff\x01")?;
+        td.create_dir("etc/dir")?;
+
+        let expected = indoc::indoc! { "
+            --- /usr/etc/hostname
+            +++ /etc/hostname
+            @@ -1 +1 @@
+            -foo
+            +bar
+        "};
+        assert_eq!(
+            content_diff(&td, "/etc/hostname")?.as_deref(),
+            Some(expected)
+        );
+        assert_eq!(
+            content_diff(&td, "/etc/binary")?.as_deref(),
+            Some("Binary files /usr/etc/binary and /etc/binary differ\n")
+        );
+        assert_eq!(content_diff(&td, "/etc/same")?, None);
+        assert_eq!(content_diff(&td, "/etc/dir/")?, None);
+        assert_eq!(content_diff(&td, "/etc/missing")?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_human_readable_output() -> Result<()> {
+        let report = Report {
+            diff: NamesDiff {
+                added: vec!["/etc/b".into()],
+                removed: vec!["/etc/c/".into()],
+                changed: vec!["/etc/a".into()],
+            },
+            contents: [("/etc/a".to_owned(), "--- a\n+++ b\n".to_owned())].into(),
+        };
+        let mut w = Vec::new();
+        human_readable_output(&mut w, &report)?;
+        let expected = indoc::indoc! { "
+            M    /etc/a
+            --- a
+            +++ b
+            A    /etc/b
+            D    /etc/c/
+        "};
+        similar_asserts::assert_eq!(String::from_utf8(w)?, expected);
+
+        let json = serde_json::to_value(&report)?;
+        assert_eq!(json["changed"], serde_json::json!(["/etc/a"]));
+        assert_eq!(json["contents"]["/etc/a"], "--- a\n+++ b\n");
+        Ok(())
+    }
+}
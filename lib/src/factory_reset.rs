@@ -0,0 +1,224 @@
+//! # Resetting a host to a freshly installed state
+//!
+//! A factory reset deploys the booted image into a new stateroot, which has a
+//! fresh `/etc` from the image and an empty `/var`. A small set of paths which
+//! identify the host, such as the SSH host keys, is copied over.
+
+use anyhow::{anyhow, Context, Result};
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+use cap_std_ext::cap_std;
+use cap_std_ext::cap_std::fs::Dir;
+use cap_std_ext::dirext::CapStdExtDirExt;
+use fn_error_context::context;
+use ostree_ext::container as ostree_container;
+use ostree_ext::ostree;
+use ostree_ext::ostree::gio;
+
+use crate::spec::Host;
+use crate::store::Storage;
+use crate::task::Task;
+
+/// The paths which are preserved by default.
+pub(crate) const DEFAULT_KEEP: &[&str] = &[
+    "/etc/machine-id",
+    "/etc/hostname",
+    "/etc/fstab",
+    "/etc/crypttab",
+    "/etc/ssh/ssh_host_*",
+    "/etc/NetworkManager/system-connections",
+    "/etc/systemd/network",
+    "/etc/ostree/auth.json",
+    "/etc/containers/auth.json",
+];
+
+/// Resolve a path to preserve to the matching paths relative to `root`; a trailing
+/// `*` in the file name matches all files with that prefix. Paths which do not
+/// exist are skipped.
+pub(crate) fn expand_keep(root: &Dir, path: &Utf8Path) -> Result<Vec<Utf8PathBuf>> {
+    let rel = path
+        .strip_prefix("/")
+        .map_err(|_| anyhow!("Not an absolute path: {path}"))?;
+    let valid = (rel.starts_with("etc") || rel.starts_with("var"))
+        && rel.components().count() > 1
+        && rel
+            .components()
+            .all(|c| matches!(c, Utf8Component::Normal(_)));
+    anyhow::ensure!(valid, "Not a path in /etc or /var: {path}");
+    // SAFETY: We checked there are at least two components
+    let (parent, name) = rel.parent().zip(rel.file_name()).unwrap();
+    anyhow::ensure!(
+        !parent.as_str().contains('*') && !name.trim_end_matches('*').contains('*'),
+        "Only a trailing * is supported: {path}"
+    );
+    let Some(prefix) = name.strip_suffix('*') else {
+        let exists = root.symlink_metadata_optional(rel)?.is_some();
+        return Ok(exists.then(|| rel.to_owned()).into_iter().collect());
+    };
+    let Some(dir) = root.open_dir_optional(parent)? else {
+        return Ok(Vec::new());
+    };
+    let mut r = Vec::new();
+    for ent in dir.entries()? {
+        let name = ent?.file_name();
+        if let Some(name) = name.to_str().filter(|n| n.starts_with(prefix)) {
+            r.push(parent.join(name));
+        }
+    }
+    r.sort();
+    Ok(r)
+}
+
+/// Copy `rel` from `root` to the same relative path in the directory `dest`,
+/// creating parent directories as needed.
+#[context("Preserving /{rel}")]
+pub(crate) fn copy_path(root: &Dir, rel: &Utf8Path, dest: &Utf8Path) -> Result<()> {
+    // Use cp to also copy extended attributes such as SELinux labels
+    Task::new(format!("Preserving /{rel}"), "cp")
+        .cwd(root)?
+        .args(["-a", "--reflink=auto", "--parents", "--"])
+        .args([rel.as_str(), dest.as_str()])
+        .quiet()
+        .run()
+}
+
+/// Stage a deployment of the booted image in the new stateroot `stateroot`, and copy
+/// the paths in `keep` from the running system into it. The kernel arguments of the
+/// booted deployment are retained.
+#[context("Performing factory reset")]
+pub(crate) async fn factory_reset(
+    sysroot: &Storage,
+    booted_deployment: &ostree::Deployment,
+    host: &Host,
+    stateroot: &str,
+    keep: &[Utf8PathBuf],
+) -> Result<()> {
+    let cancellable = gio::Cancellable::NONE;
+    anyhow::ensure!(
+        !stateroot.is_empty() && !stateroot.contains('/'),
+        "Invalid stateroot name: {stateroot}"
+    );
+    let sysroot_dir = crate::utils::sysroot_dir(sysroot)?;
+    anyhow::ensure!(
+        !sysroot_dir.try_exists(format!("ostree/deploy/{stateroot}"))?,
+        "Stateroot {stateroot} already exists"
+    );
+    // Resolve the paths first, so that invalid paths are rejected early
+    let rootfs = Dir::open_ambient_dir("/", cap_std::ambient_authority())?;
+    let keep = keep
+        .iter()
+        .map(|p| expand_keep(&rootfs, p))
+        .collect::<Result<Vec<_>>>()?
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+
+    let image = host
+        .status
+        .booted
+        .as_ref()
+        .and_then(|e| e.image.as_ref())
+        .ok_or_else(|| anyhow!("The booted deployment is not container image based"))?;
+    let target_imgref = ostree_container::OstreeImageReference::from(image.image.clone());
+    // Deploy exactly the booted image, while tracking the original reference
+    let mut src_imgref = target_imgref.clone();
+    if src_imgref.imgref.transport == ostree_container::Transport::Registry {
+        src_imgref.imgref.name =
+            crate::utils::digested_pullspec(&src_imgref.imgref.name, &image.image_digest);
+    }
+    let kargs = crate::status::deployment_kargs(booted_deployment)
+        .into_iter()
+        .filter(|k| !k.starts_with(crate::status::OSTREE_KARG_PREFIX))
+        .collect::<Vec<_>>();
+    let kargs = kargs.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+
+    sysroot
+        .init_osname(stateroot, cancellable)
+        .context("Initializing stateroot")?;
+    let mut options = ostree_container::deploy::DeployOpts::default();
+    options.kargs = Some(kargs.as_slice());
+    options.target_imgref = Some(&target_imgref);
+    options.skip_completion = true;
+    options.no_clean = true;
+    crate::utils::async_task_with_spinner(
+        "Deploying container image",
+        ostree_container::deploy::deploy(sysroot, stateroot, &src_imgref, Some(options)),
+    )
+    .await?;
+    sysroot.load(cancellable)?;
+    let deployment = sysroot
+        .staged_deployment()
+        .ok_or_else(|| anyhow!("Failed to find staged deployment"))?;
+
+    // The new /etc is in the deployment, and /var is shared by the stateroot
+    let sysroot_path = sysroot
+        .path()
+        .path()
+        .ok_or_else(|| anyhow!("Sysroot has no path"))?;
+    let sysroot_path = Utf8PathBuf::try_from(sysroot_path)?;
+    let deployment_path = sysroot_path.join(sysroot.deployment_dirpath(&deployment).as_str());
+    let stateroot_path = sysroot_path.join(format!("ostree/deploy/{stateroot}"));
+    for path in keep.iter() {
+        let dest = if path.starts_with("etc") {
+            &deployment_path
+        } else {
+            &stateroot_path
+        };
+        copy_path(&rootfs, path, dest)?;
+        println!("Preserved: /{path}");
+    }
+
+    crate::boundimage::record_bound_images(sysroot, &deployment)?;
+    println!(
+        "Queued for next boot: {:#} in stateroot {stateroot}",
+        image.image
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_keep() -> Result<()> {
+        let td = cap_std_ext::cap_tempfile::tempdir(cap_std::ambient_authority())?;
+        td.create_dir_all("etc/ssh")?;
+        for f in ["ssh_host_rsa_key", "ssh_host_rsa_key.pub", "sshd_config"] {
+            td.write(format!("etc/ssh/{f}"), "x")?;
+        }
+        td.write("etc/machine-id", "x")?;
+
+        let expand = |p: &str| expand_keep(&td, Utf8Path::new(p));
+        assert_eq!(expand("/etc/machine-id")?, ["etc/machine-id"]);
+        assert_eq!(
+            expand("/etc/ssh/ssh_host_*")?,
+            ["etc/ssh/ssh_host_rsa_key", "etc/ssh/ssh_host_rsa_key.pub"]
+        );
+        assert!(expand("/etc/hostname")?.is_empty());
+        assert!(expand("/var/lib/missing/*")?.is_empty());
+        for p in [
+            "etc/machine-id",
+            "/etc",
+            "/usr/lib/foo",
+            "/etc/../usr",
+            "/etc/*/foo",
+        ] {
+            assert!(expand(p).is_err(), "{p}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_copy_path() -> Result<()> {
+        let src = tempfile::tempdir()?;
+        let dest = tempfile::tempdir()?;
+        let root = Dir::open_ambient_dir(src.path(), cap_std::ambient_authority())?;
+        root.create_dir_all("var/lib/foo")?;
+        root.write("var/lib/foo/state", "x")?;
+        let dest_path = Utf8Path::from_path(dest.path()).unwrap();
+        copy_path(&root, Utf8Path::new("var/lib/foo"), dest_path)?;
+        let dest = Dir::open_ambient_dir(dest.path(), cap_std::ambient_authority())?;
+        assert_eq!(dest.read_to_string("var/lib/foo/state")?, "x");
+        Ok(())
+    }
+}
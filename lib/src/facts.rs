@@ -0,0 +1,311 @@
+//! # Publishing the host status as facts for configuration management
+//!
+//! Each drop-in file in `/usr/lib/bootc/facts.d` configures a file to which
+//! the images of the host are written in one of several formats, e.g. as
+//! Ansible local facts. This is run via `bootc-publish-facts.service`
+//! whenever the status changes.
+
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use camino::{Utf8Path, Utf8PathBuf};
+use cap_std_ext::cap_std::fs::Dir;
+use cap_std_ext::cap_std::fs_utf8::Dir as DirUtf8;
+use cap_std_ext::dirext::{CapStdExtDirExt, CapStdExtDirExtUtf8};
+use fn_error_context::context;
+use serde::{Deserialize, Serialize, Serializer};
+
+use crate::spec::{BootEntry, HostStatus, ImageStatus};
+
+/// The drop-in directory configuring the publishers.
+const FACTS_CONFIG_PATH: &str = "usr/lib/bootc/facts.d";
+
+/// The output format of a publisher.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Format {
+    /// A JSON object with flat `bootc.<slot>.<field>` keys, as used by subscription-manager.
+    Json,
+    /// Lines of `bootc.<slot>.<field>=<value>`.
+    KeyValue,
+    /// A JSON object with a nested object for each slot, for Ansible local facts.
+    Ansible,
+}
+
+/// A facts.d configuration file.
+#[derive(Deserialize, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct Config {
+    /// The output format
+    format: Format,
+    /// The absolute path to write; it is skipped if the parent directory doesn't exist.
+    path: Utf8PathBuf,
+}
+
+/// The facts about one image of the host. Missing values are empty strings.
+#[derive(Serialize, PartialEq, Eq, Debug, Default)]
+pub(crate) struct ImageFacts {
+    image: String,
+    version: String,
+    digest: String,
+}
+
+/// The facts about the images of the host.
+#[derive(Serialize, PartialEq, Eq, Debug, Default)]
+pub(crate) struct Facts {
+    booted: ImageFacts,
+    staged: ImageFacts,
+    rollback: ImageFacts,
+    /// The update found by `bootc upgrade --check`, if any
+    available: ImageFacts,
+}
+
+impl From<Option<&ImageStatus>> for ImageFacts {
+    fn from(imagestatus: Option<&ImageStatus>) -> Self {
+        let Some(imagestatus) = imagestatus else {
+            return Default::default();
+        };
+        Self {
+            image: imagestatus.image.image.clone(),
+            version: imagestatus.version.clone().unwrap_or_default(),
+            digest: imagestatus.image_digest.clone(),
+        }
+    }
+}
+
+impl From<&HostStatus> for Facts {
+    fn from(hoststatus: &HostStatus) -> Self {
+        let image =
+            |e: Option<&BootEntry>| -> ImageFacts { e.and_then(|e| e.image.as_ref()).into() };
+        Self {
+            booted: image(hoststatus.booted.as_ref()),
+            staged: image(hoststatus.staged.as_ref()),
+            rollback: image(hoststatus.rollback.as_ref()),
+            available: hoststatus
+                .booted
+                .as_ref()
+                .and_then(|e| e.cached_update.as_ref())
+                .into(),
+        }
+    }
+}
+
+impl Facts {
+    /// Iterate over the facts as flat `bootc.<slot>.<field>` keys.
+    fn entries(&self) -> impl Iterator<Item = (String, &str)> {
+        [
+            ("booted", &self.booted),
+            ("staged", &self.staged),
+            ("rollback", &self.rollback),
+            ("available", &self.available),
+        ]
+        .into_iter()
+        .flat_map(|(slot, f)| {
+            [
+                ("image", f.image.as_str()),
+                ("version", f.version.as_str()),
+                ("digest", f.digest.as_str()),
+            ]
+            .into_iter()
+            .map(move |(k, v)| (format!("bootc.{slot}.{k}"), v))
+        })
+    }
+
+    /// Write the facts in the provided format.
+    pub(crate) fn write(&self, mut w: impl Write, format: Format) -> Result<()> {
+        match format {
+            Format::Json => {
+                /// Serializes the facts as a flat map, in order.
+                struct Flat<'a>(&'a Facts);
+                impl Serialize for Flat<'_> {
+                    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+                        s.collect_map(self.0.entries())
+                    }
+                }
+                serde_json::to_writer_pretty(&mut w, &Flat(self))?;
+            }
+            Format::KeyValue => {
+                for (k, v) in self.entries() {
+                    writeln!(w, "{k}={v}")?;
+                }
+            }
+            Format::Ansible => serde_json::to_writer_pretty(&mut w, self)?,
+        }
+        Ok(())
+    }
+}
+
+/// Write the facts to `path` (absolute) in `root`.
+pub(crate) fn write_facts(
+    root: &Dir,
+    facts: &Facts,
+    format: Format,
+    path: &Utf8Path,
+) -> Result<()> {
+    let rel = path.strip_prefix("/").unwrap_or(path);
+    root.atomic_replace_with(rel, |w| facts.write(w, format))
+        .with_context(|| format!("Writing {path}"))
+}
+
+/// Load the facts.d configuration files in `root`, sorted by name.
+fn load_configs(root: &Dir) -> Result<Vec<(String, Config)>> {
+    // If the directory doesn't exist, that's OK.
+    let Some(d) = root
+        .open_dir_optional(FACTS_CONFIG_PATH)?
+        .map(DirUtf8::from_cap_std)
+    else {
+        return Ok(Default::default());
+    };
+    let mut ret = Vec::new();
+    let entries =
+        d.filenames_filtered_sorted(|_, name| Utf8Path::new(name).extension() == Some("toml"))?;
+    for name in entries {
+        let buf = d.read_to_string(&name)?;
+        let config: Config =
+            toml::from_str(&buf).with_context(|| format!("Parsing /{FACTS_CONFIG_PATH}/{name}"))?;
+        anyhow::ensure!(
+            config.path.is_absolute(),
+            "/{FACTS_CONFIG_PATH}/{name}: path must be absolute: {}",
+            config.path
+        );
+        ret.push((name, config));
+    }
+    Ok(ret)
+}
+
+/// Write the facts for each configured publisher whose target directory exists.
+fn publish_to(root: &Dir, facts: &Facts) -> Result<()> {
+    for (name, config) in load_configs(root)? {
+        let rel = config.path.strip_prefix("/")?;
+        let parent_exists = match rel.parent().filter(|p| !p.as_str().is_empty()) {
+            Some(p) => root.symlink_metadata_optional(p)?.is_some(),
+            None => true,
+        };
+        if !parent_exists {
+            tracing::debug!(
+                "{name}: Skipping {}, parent directory is missing",
+                config.path
+            );
+            continue;
+        }
+        write_facts(root, facts, config.format, &config.path)?;
+    }
+    Ok(())
+}
+
+/// Implementation of `bootc internals publish-facts`.
+#[context("Publishing facts")]
+pub(crate) async fn publish(root: &Dir) -> Result<()> {
+    let sysroot = crate::cli::get_storage().await?;
+    let booted_deployment = sysroot.booted_deployment();
+    let (_deployments, host) = crate::status::get_status(&sysroot, booted_deployment.as_ref())?;
+    publish_to(root, &Facts::from(&host.status))
+}
+
+#[cfg(test)]
+mod tests {
+    use cap_std_ext::cap_std;
+
+    use super::*;
+    use crate::spec::Host;
+
+    fn fixture() -> Facts {
+        let host: Host = serde_yaml::from_str(include_str!("fixtures/spec-staged-booted.yaml"))
+            .expect("No spec found");
+        Facts::from(&host.status)
+    }
+
+    #[test]
+    fn test_facts_from_host() {
+        let image = |digest: &str| ImageFacts {
+            image: "quay.io/example/someimage:latest".into(),
+            version: "nightly".into(),
+            digest: digest.into(),
+        };
+        assert_eq!(
+            fixture(),
+            Facts {
+                booted: image(
+                    "sha256:736b359467c9437c1ac915acaae952aad854e07eb4a16a94999a48af08c83c34"
+                ),
+                staged: image(
+                    "sha256:16dc2b6256b4ff0d2ec18d2dbfb06d117904010c8cf9732cdb022818cf7a7566"
+                ),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_formats() -> Result<()> {
+        let facts = fixture();
+        let output = |format| -> Result<String> {
+            let mut w = Vec::new();
+            facts.write(&mut w, format)?;
+            Ok(String::from_utf8(w)?)
+        };
+
+        let json: serde_json::Value = serde_json::from_str(&output(Format::Json)?)?;
+        assert_eq!(json["bootc.booted.version"], "nightly");
+        assert_eq!(json["bootc.rollback.image"], "");
+        assert_eq!(json.as_object().unwrap().len(), 12);
+
+        let kv = output(Format::KeyValue)?;
+        let mut lines = kv.lines();
+        assert_eq!(
+            lines.next(),
+            Some("bootc.booted.image=quay.io/example/someimage:latest")
+        );
+        assert_eq!(kv.lines().count(), 12);
+        assert!(kv.contains("\nbootc.available.digest=\n"));
+
+        let ansible: serde_json::Value = serde_json::from_str(&output(Format::Ansible)?)?;
+        assert_eq!(ansible["staged"]["version"], "nightly");
+        assert_eq!(ansible["available"]["image"], "");
+        Ok(())
+    }
+
+    #[test]
+    fn test_publish_to() -> Result<()> {
+        let td = cap_std_ext::cap_tempfile::tempdir(cap_std::ambient_authority())?;
+        let facts = fixture();
+        // No configuration is fine
+        publish_to(&td, &facts)?;
+
+        td.create_dir_all(FACTS_CONFIG_PATH)?;
+        td.write(
+            format!("{FACTS_CONFIG_PATH}/50-ansible.toml"),
+            "format = \"ansible\"\npath = \"/etc/ansible/facts.d/bootc.fact\"\n",
+        )?;
+        td.write(
+            format!("{FACTS_CONFIG_PATH}/60-env.toml"),
+            "format = \"key-value\"\npath = \"/run/bootc-facts.env\"\n",
+        )?;
+        td.write(format!("{FACTS_CONFIG_PATH}/README"), "not a config")?;
+        td.create_dir("run")?;
+        publish_to(&td, &facts)?;
+        // The Ansible facts directory doesn't exist, so it is skipped
+        assert!(!td.try_exists("etc/ansible/facts.d/bootc.fact")?);
+        assert!(td
+            .read_to_string("run/bootc-facts.env")?
+            .contains("bootc.staged.version=nightly\n"));
+
+        td.create_dir_all("etc/ansible/facts.d")?;
+        publish_to(&td, &facts)?;
+        let ansible: serde_json::Value =
+            serde_json::from_str(&td.read_to_string("etc/ansible/facts.d/bootc.fact")?)?;
+        assert_eq!(ansible["booted"]["version"], "nightly");
+
+        td.write(
+            format!("{FACTS_CONFIG_PATH}/70-invalid.toml"),
+            "format = \"xml\"\npath = \"/run/x\"\n",
+        )?;
+        assert!(publish_to(&td, &facts).is_err());
+        td.write(
+            format!("{FACTS_CONFIG_PATH}/70-invalid.toml"),
+            "format = \"json\"\npath = \"run/x\"\n",
+        )?;
+        assert!(publish_to(&td, &facts).is_err());
+        Ok(())
+    }
+}
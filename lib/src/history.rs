@@ -0,0 +1,456 @@
+//! # A persistent history of changes to the host
+//!
+//! Each upgrade, switch, rollback or other change of the deployment queued for
+//! the next boot is recorded as a JSON line in a file in the physical root,
+//! and can be queried via `bootc status --history`.
+
+use std::io::{BufRead, Write};
+
+use anyhow::Result;
+use camino::Utf8Path;
+use cap_std_ext::cap_std::fs::Dir;
+use cap_std_ext::dirext::CapStdExtDirExt;
+use chrono::{DateTime, Utc};
+use fn_error_context::context;
+use ostree_ext::ostree;
+use ostree_ext::ostree::gio;
+use serde::{Deserialize, Serialize};
+
+use crate::cli::OutputFormat;
+use crate::spec::{BootEntry, Host, ImageStatus};
+use crate::status::Deployments;
+use crate::store::Storage;
+
+/// The history, relative to the physical root.
+const HISTORY_PATH: &str = "ostree/bootc/state/history.jsonl";
+/// Only this many of the most recent entries are retained.
+const MAX_ENTRIES: usize = 1000;
+
+/// An operation changing the host.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) enum Operation {
+    Upgrade,
+    Switch,
+    Rollback,
+    Edit,
+    Kargs,
+    ConfigReset,
+    FactoryReset,
+    /// An operation recorded by a newer version
+    #[serde(other)]
+    Unknown,
+}
+
+impl std::fmt::Display for Operation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let s = match self {
+            Operation::Upgrade => "upgrade",
+            Operation::Switch => "switch",
+            Operation::Rollback => "rollback",
+            Operation::Edit => "edit",
+            Operation::Kargs => "kargs",
+            Operation::ConfigReset => "config reset",
+            Operation::FactoryReset => "factory reset",
+            Operation::Unknown => "unknown",
+        };
+        f.write_str(s)
+    }
+}
+
+/// The image of the deployment queued for the next boot.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HistoryImage {
+    image: String,
+    digest: String,
+    version: Option<String>,
+}
+
+impl From<&ImageStatus> for HistoryImage {
+    fn from(value: &ImageStatus) -> Self {
+        Self {
+            image: value.image.image.clone(),
+            digest: value.image_digest.clone(),
+            version: value.version.clone(),
+        }
+    }
+}
+
+/// The kernel arguments which were added and removed.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct KargsChange {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    added: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    removed: Vec<String>,
+}
+
+/// Who initiated an operation.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct Initiator {
+    /// The user ID of the process
+    uid: u32,
+    /// The audit login user ID, identifying the user who logged in even across `sudo`
+    login_uid: Option<u32>,
+    /// The systemd unit (e.g. a service or a login session scope)
+    unit: Option<String>,
+}
+
+impl Initiator {
+    fn current() -> Self {
+        let login_uid = std::fs::read_to_string("/proc/self/loginuid")
+            .ok()
+            .and_then(|s| s.trim().parse::<u32>().ok())
+            // This is the "unset" value
+            .filter(|&uid| uid != u32::MAX);
+        let unit = std::fs::read_to_string("/proc/self/cgroup")
+            .ok()
+            .and_then(|s| unit_of_cgroup(&s).map(ToOwned::to_owned));
+        Self {
+            uid: rustix::process::getuid().as_raw(),
+            login_uid,
+            unit,
+        }
+    }
+}
+
+/// Find the systemd unit in the contents of `/proc/self/cgroup`.
+fn unit_of_cgroup(contents: &str) -> Option<&str> {
+    contents
+        .lines()
+        .find_map(|l| l.strip_prefix("0::"))
+        .and_then(|path| {
+            path.split('/')
+                .rev()
+                .find(|c| c.ends_with(".service") || c.ends_with(".scope"))
+        })
+}
+
+/// An entry in the history.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct HistoryEntry {
+    timestamp: DateTime<Utc>,
+    operation: Operation,
+    /// The image queued for the next boot before the operation
+    from: Option<HistoryImage>,
+    /// The image queued for the next boot after the operation
+    to: Option<HistoryImage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    kargs: Option<KargsChange>,
+    initiator: Initiator,
+    success: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Return the boot entry which is queued for the next boot.
+fn next_boot_entry(host: &Host) -> Option<&BootEntry> {
+    let status = &host.status;
+    status.staged.as_ref().or_else(|| {
+        if status.rollback_queued {
+            status.rollback.as_ref()
+        } else {
+            status.booted.as_ref()
+        }
+    })
+}
+
+fn image_of(entry: Option<&BootEntry>) -> Option<HistoryImage> {
+    entry.and_then(|e| e.image.as_ref()).map(HistoryImage::from)
+}
+
+/// The kernel arguments of the deployment which is queued for the next boot; the
+/// `ostree=` argument is omitted as it differs for each deployment.
+fn next_boot_kargs(
+    booted_deployment: Option<&ostree::Deployment>,
+    deployments: &Deployments,
+    host: &Host,
+) -> Option<Vec<String>> {
+    let deployment = deployments
+        .staged
+        .as_ref()
+        .or(if host.status.rollback_queued {
+            deployments.rollback.as_ref()
+        } else {
+            booted_deployment
+        })?;
+    let r = crate::status::deployment_kargs(deployment)
+        .into_iter()
+        .filter(|k| !k.starts_with(crate::status::OSTREE_KARG_PREFIX))
+        .collect();
+    Some(r)
+}
+
+fn kargs_change(from: Option<&[String]>, to: Option<&[String]>) -> Option<KargsChange> {
+    let (from, to) = (from?, to?);
+    let added = to.iter().filter(|k| !from.contains(k)).cloned().collect();
+    let removed = from.iter().filter(|k| !to.contains(k)).cloned().collect();
+    let change = KargsChange { added, removed };
+    (change != KargsChange::default()).then_some(change)
+}
+
+/// Append an entry to the history, pruning old entries.
+#[context("Recording history")]
+fn append(sysroot_dir: &Dir, entry: &HistoryEntry) -> Result<()> {
+    // Existing entries are retained verbatim, so that we don't lose fields
+    // written by a newer version.
+    let mut lines = match sysroot_dir.open_optional(HISTORY_PATH)? {
+        Some(f) => std::io::BufReader::new(f)
+            .lines()
+            .collect::<std::io::Result<Vec<_>>>()?,
+        None => Vec::new(),
+    };
+    lines.push(serde_json::to_string(entry)?);
+    let skip = lines.len().saturating_sub(MAX_ENTRIES);
+    if let Some(parent) = Utf8Path::new(HISTORY_PATH).parent() {
+        sysroot_dir.create_dir_all(parent)?;
+    }
+    sysroot_dir.atomic_replace_with(HISTORY_PATH, |w| {
+        for line in lines.iter().skip(skip) {
+            writeln!(w, "{line}")?;
+        }
+        anyhow::Ok(())
+    })?;
+    Ok(())
+}
+
+/// Load all entries of the history, oldest first; entries which can't be parsed
+/// are skipped with a warning.
+fn load(sysroot_dir: &Dir) -> Result<Vec<HistoryEntry>> {
+    let Some(f) = sysroot_dir.open_optional(HISTORY_PATH)? else {
+        return Ok(Vec::new());
+    };
+    let mut r = Vec::new();
+    for (i, line) in std::io::BufReader::new(f).lines().enumerate() {
+        match serde_json::from_str(&line?) {
+            Ok(entry) => r.push(entry),
+            Err(e) => tracing::warn!("Skipping invalid history entry {}: {e}", i + 1),
+        }
+    }
+    Ok(r)
+}
+
+/// An operation in progress, which is recorded in the history once complete.
+#[derive(Debug)]
+pub(crate) struct Transition {
+    operation: Operation,
+    from: Option<BootEntry>,
+    from_kargs: Option<Vec<String>>,
+}
+
+impl Transition {
+    /// Begin an operation on a host in the provided state.
+    pub(crate) fn begin(
+        operation: Operation,
+        booted_deployment: Option<&ostree::Deployment>,
+        deployments: &Deployments,
+        host: &Host,
+    ) -> Self {
+        Self {
+            operation,
+            from: next_boot_entry(host).cloned(),
+            from_kargs: next_boot_kargs(booted_deployment, deployments, host),
+        }
+    }
+
+    fn entry(
+        &self,
+        to: Option<&BootEntry>,
+        to_kargs: Option<&[String]>,
+        error: Option<String>,
+    ) -> HistoryEntry {
+        HistoryEntry {
+            timestamp: Utc::now(),
+            operation: self.operation,
+            from: image_of(self.from.as_ref()),
+            to: image_of(to),
+            kargs: kargs_change(self.from_kargs.as_deref(), to_kargs),
+            initiator: Initiator::current(),
+            success: error.is_none(),
+            error,
+        }
+    }
+
+    fn commit_impl(&self, sysroot: &Storage) -> Result<()> {
+        // Pick up the changed deployments
+        sysroot.load(gio::Cancellable::NONE)?;
+        let booted_deployment = sysroot.booted_deployment();
+        let (deployments, host) = crate::status::get_status(sysroot, booted_deployment.as_ref())?;
+        let kargs = next_boot_kargs(booted_deployment.as_ref(), &deployments, &host);
+        let entry = self.entry(next_boot_entry(&host), kargs.as_deref(), None);
+        append(&crate::utils::sysroot_dir(sysroot)?, &entry)
+    }
+
+    /// Record the successful completion of the operation. Failing to
+    /// record the history is not fatal, and only logged.
+    pub(crate) fn commit(self, sysroot: &Storage) {
+        if let Err(e) = self.commit_impl(sysroot) {
+            tracing::warn!("{e:#}");
+        }
+    }
+}
+
+/// Record a failed operation; this is invoked after the operation has
+/// released the sysroot lock.
+async fn record_failure(operation: Operation, err: &anyhow::Error) -> Result<()> {
+    let sysroot = crate::cli::get_storage().await?;
+    let booted_deployment = sysroot.booted_deployment();
+    let (deployments, host) = crate::status::get_status(&sysroot, booted_deployment.as_ref())?;
+    let tx = Transition::begin(operation, booted_deployment.as_ref(), &deployments, &host);
+    let entry = tx.entry(None, None, Some(format!("{err:#}")));
+    append(&crate::utils::sysroot_dir(&sysroot)?, &entry)
+}
+
+/// Run an operation, recording it in the history if it fails. Successful
+/// operations are recorded via [`Transition::commit`].
+pub(crate) async fn record_failures(
+    operation: Operation,
+    f: impl std::future::Future<Output = Result<()>>,
+) -> Result<()> {
+    let r = f.await;
+    if let Err(e) = r.as_ref() {
+        if let Err(e) = record_failure(operation, e).await {
+            tracing::debug!("Failed to record history: {e:#}");
+        }
+    }
+    r
+}
+
+fn human_readable_output(mut out: impl Write, entries: &[HistoryEntry]) -> Result<()> {
+    let image_str = |i: Option<&HistoryImage>| {
+        i.map(|i| match i.version.as_deref() {
+            Some(v) => format!("{} ({v})", i.digest),
+            None => i.digest.clone(),
+        })
+        .unwrap_or_else(|| "-".to_owned())
+    };
+    for entry in entries {
+        let timestamp = entry.timestamp.format("%Y-%m-%d %H:%M:%S UTC");
+        write!(out, "{timestamp} {}: ", entry.operation)?;
+        if entry.success {
+            write!(
+                out,
+                "{} -> {}",
+                image_str(entry.from.as_ref()),
+                image_str(entry.to.as_ref())
+            )?;
+            if let Some(kargs) = entry.kargs.as_ref() {
+                for k in kargs.added.iter() {
+                    write!(out, " +{k}")?;
+                }
+                for k in kargs.removed.iter() {
+                    write!(out, " -{k}")?;
+                }
+            }
+        } else {
+            write!(
+                out,
+                "failed: {}",
+                entry.error.as_deref().unwrap_or("unknown error")
+            )?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Implementation of `bootc status --history`.
+pub(crate) async fn print(format: &OutputFormat) -> Result<()> {
+    let sysroot = crate::cli::get_storage().await?;
+    let entries = load(&crate::utils::sysroot_dir(&sysroot)?)?;
+    let mut out = std::io::stdout().lock();
+    match format {
+        OutputFormat::Json => serde_json::to_writer(&mut out, &entries)?,
+        OutputFormat::Yaml => serde_yaml::to_writer(&mut out, &entries)?,
+        OutputFormat::HumanReadable => human_readable_output(&mut out, &entries)?,
+    }
+    out.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_unit_of_cgroup() {
+        assert_eq!(
+            unit_of_cgroup("0::/system.slice/bootc-fetch-apply-updates.service\n"),
+            Some("bootc-fetch-apply-updates.service")
+        );
+        assert_eq!(
+            unit_of_cgroup("0::/user.slice/user-1000.slice/session-3.scope\n"),
+            Some("session-3.scope")
+        );
+        assert_eq!(unit_of_cgroup("0::/\n"), None);
+        assert_eq!(unit_of_cgroup(""), None);
+    }
+
+    #[test]
+    fn test_transition() -> Result<()> {
+        let before: Host = serde_yaml::from_str(include_str!("fixtures/spec-only-booted.yaml"))?;
+        let after: Host = serde_yaml::from_str(include_str!("fixtures/spec-staged-booted.yaml"))?;
+        let tx = Transition {
+            operation: Operation::Upgrade,
+            from: next_boot_entry(&before).cloned(),
+            from_kargs: None,
+        };
+        let entry = tx.entry(next_boot_entry(&after), None, None);
+        assert!(entry.success);
+        assert_eq!(
+            entry.to.as_ref().unwrap().digest,
+            "sha256:16dc2b6256b4ff0d2ec18d2dbfb06d117904010c8cf9732cdb022818cf7a7566"
+        );
+        assert_ne!(entry.from, entry.to);
+
+        let td =
+            cap_std_ext::cap_tempfile::TempDir::new(cap_std_ext::cap_std::ambient_authority())?;
+        for _ in 0..MAX_ENTRIES + 2 {
+            append(&td, &entry)?;
+        }
+        let failed = tx.entry(None, None, Some("oops".into()));
+        append(&td, &failed)?;
+        let entries = load(&td)?;
+        assert_eq!(entries.len(), MAX_ENTRIES);
+        assert_eq!(entries.last(), Some(&failed));
+        assert!(!failed.success);
+
+        let mut w = Vec::new();
+        human_readable_output(&mut w, &entries[entries.len() - 2..])?;
+        let w = String::from_utf8(w)?;
+        assert!(w.contains("upgrade: "));
+        assert!(w.contains("upgrade: failed: oops\n"));
+
+        // Unknown operations and invalid entries don't prevent loading the history
+        let mut unknown = serde_json::to_value(&failed)?;
+        unknown["operation"] = "someFutureOperation".into();
+        td.write(
+            HISTORY_PATH,
+            format!("{unknown}\nnot json\n{}\n", serde_json::to_string(&entry)?),
+        )?;
+        let entries = load(&td)?;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].operation, Operation::Unknown);
+        assert_eq!(entries[1], entry);
+        Ok(())
+    }
+
+    #[test]
+    fn test_kargs_change() {
+        let a: &[String] = &["quiet".to_owned()];
+        let b: &[String] = &["quiet".to_owned(), "console=ttyS0".to_owned()];
+        assert_eq!(kargs_change(Some(a), Some(a)), None);
+        assert_eq!(
+            kargs_change(Some(a), Some(b)),
+            Some(KargsChange {
+                added: vec!["console=ttyS0".into()],
+                removed: Vec::new()
+            })
+        );
+        assert_eq!(kargs_change(None, Some(b)), None);
+    }
+}
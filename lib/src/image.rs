@@ -2,6 +2,8 @@
 //!
 //! APIs for operating on container images in the bootc storage.
 
+use std::collections::HashSet;
+
 use anyhow::{bail, Context, Result};
 use bootc_utils::CommandRunExt;
 use cap_std_ext::cap_std::{self, fs::Dir};
@@ -9,10 +11,14 @@ use clap::ValueEnum;
 use comfy_table::{presets::NOTHING, Table};
 use fn_error_context::context;
 use ostree_ext::container::{ImageReference, Transport};
+use ostree_ext::glib;
+use ostree_ext::ostree::Deployment;
 use serde::Serialize;
 
 use crate::{
-    boundimage::query_bound_images,
+    boundimage::{
+        query_bound_images, query_bound_images_for_deployment, query_recorded_bound_images,
+    },
     cli::{ImageListFormat, ImageListType},
     imgstorage::ensure_floating_c_storage_initialized,
 };
@@ -180,3 +186,213 @@ pub(crate) async fn imgcmd_entrypoint(
     cmd.args(args);
     cmd.run()
 }
+
+/// A deployment referencing a bound image.
+#[derive(Serialize)]
+struct DeploymentReference {
+    role: &'static str,
+    stateroot: String,
+    checksum: String,
+    deploy_serial: i32,
+    /// The digest of the image resolved when the deployment was staged
+    recorded_digest: Option<String>,
+}
+
+/// Details of a bound image in the bootc storage.
+#[derive(Serialize)]
+struct BoundImageInspect {
+    image: String,
+    present: bool,
+    digest: Option<String>,
+    size: Option<u64>,
+    deployments: Vec<DeploymentReference>,
+}
+
+/// Return all deployments, along with their role (`staged`, `booted`, `rollback`
+/// or `other`).
+fn deployments_with_role(sysroot: &crate::store::Storage) -> Vec<(&'static str, Deployment)> {
+    let booted = sysroot.booted_deployment();
+    let stateroot = booted.as_ref().map(|d| d.osname());
+    let mut have_rollback = false;
+    sysroot
+        .deployments()
+        .into_iter()
+        .map(|d| {
+            let role = if d.is_staged() {
+                "staged"
+            } else if booted.as_ref().is_some_and(|b| b.equal(&d)) {
+                "booted"
+            } else if !have_rollback && Some(d.osname()) == stateroot {
+                // This matches the logic in get_status()
+                have_rollback = true;
+                "rollback"
+            } else {
+                "other"
+            };
+            (role, d)
+        })
+        .collect()
+}
+
+/// Implementation of `bootc image inspect`.
+#[context("Inspecting {image}")]
+pub(crate) async fn inspect_entrypoint(image: &str, format: ImageListFormat) -> Result<()> {
+    let sysroot = crate::cli::get_storage().await?;
+    let sysroot_dir = &crate::utils::sysroot_dir(&sysroot)?;
+    let imgstore = sysroot.get_imgstore_optional()?;
+
+    let mut deployments = Vec::new();
+    for (role, deployment) in deployments_with_role(&sysroot) {
+        let bound = query_bound_images_for_deployment(&sysroot, &deployment)?;
+        if !bound.iter().any(|b| b.image == image) {
+            continue;
+        }
+        let recorded_digest = query_recorded_bound_images(sysroot_dir, &deployment)?
+            .into_iter()
+            .flatten()
+            .find(|r| r.image == image)
+            .map(|r| r.digest);
+        deployments.push(DeploymentReference {
+            role,
+            stateroot: deployment.osname().into(),
+            checksum: deployment.csum().into(),
+            deploy_serial: deployment.deployserial(),
+            recorded_digest,
+        });
+    }
+    let present = match imgstore {
+        Some(imgstore) => imgstore.exists(image).await?,
+        None => false,
+    };
+    let imgstore = imgstore.filter(|_| present);
+    if !present && deployments.is_empty() {
+        bail!("Image not found: {image}");
+    }
+    let (digest, size) = if let Some(imgstore) = imgstore {
+        let digest = imgstore.image_digest(image)?;
+        let size = imgstore.image_size(image).await?;
+        (Some(digest), Some(size))
+    } else {
+        (None, None)
+    };
+    let r = BoundImageInspect {
+        image: image.to_owned(),
+        present,
+        digest,
+        size,
+        deployments,
+    };
+
+    match format {
+        ImageListFormat::Table => {
+            let mut table = Table::new();
+            table
+                .load_preset(NOTHING)
+                .set_content_arrangement(comfy_table::ContentArrangement::Dynamic);
+            table.add_row(["Image:", r.image.as_str()]);
+            table.add_row(["Digest:", r.digest.as_deref().unwrap_or("(not present)")]);
+            if let Some(size) = r.size {
+                table.add_row(["Size:", glib::format_size(size).as_str()]);
+            }
+            for (i, d) in r.deployments.iter().enumerate() {
+                let name = if i == 0 { "Deployments:" } else { "" };
+                let mut desc = format!(
+                    "{} ({} {}.{})",
+                    d.role, d.stateroot, d.checksum, d.deploy_serial
+                );
+                if let Some(recorded) = d.recorded_digest.as_deref() {
+                    desc.push_str(&format!(", recorded {recorded}"));
+                }
+                table.add_row([name, desc.as_str()]);
+            }
+            println!("{table}");
+        }
+        ImageListFormat::Json => {
+            let mut stdout = std::io::stdout();
+            serde_json::to_writer_pretty(&mut stdout, &r)?;
+        }
+    }
+    Ok(())
+}
+
+/// Implementation of `bootc image verify`: check that the bound images of the staged,
+/// booted and rollback deployments are present, and match any recorded digest.
+#[context("Verifying bound images")]
+pub(crate) async fn verify_entrypoint() -> Result<()> {
+    let sysroot = crate::cli::get_storage().await?;
+    let sysroot_dir = &crate::utils::sysroot_dir(&sysroot)?;
+    // If the image storage doesn't exist, no images are present
+    let imgstore = sysroot.get_imgstore_optional()?;
+
+    let mut n = 0;
+    let mut problems = Vec::new();
+    for (role, deployment) in deployments_with_role(&sysroot)
+        .into_iter()
+        .filter(|(role, _)| *role != "other")
+    {
+        let recorded = query_recorded_bound_images(sysroot_dir, &deployment)?.unwrap_or_default();
+        for bound in query_bound_images_for_deployment(&sysroot, &deployment)? {
+            n += 1;
+            let image = bound.image.as_str();
+            let present = match imgstore {
+                Some(imgstore) => imgstore.exists(image).await?,
+                None => false,
+            };
+            let Some(imgstore) = imgstore.filter(|_| present) else {
+                problems.push(format!("{role}: {image}: Not present"));
+                continue;
+            };
+            let digest = match imgstore.image_digest(image) {
+                Ok(d) => d,
+                Err(e) => {
+                    problems.push(format!("{role}: {image}: {e:#}"));
+                    continue;
+                }
+            };
+            if let Some(r) = recorded.iter().find(|r| r.image == image) {
+                if r.digest != digest {
+                    problems.push(format!(
+                        "{role}: {image}: Found {digest}, expected recorded {}",
+                        r.digest
+                    ));
+                    continue;
+                }
+            }
+            println!("{role}: {image}: ok ({digest})");
+        }
+    }
+    if !problems.is_empty() {
+        bail!(
+            "Found {} problems with bound images:\n{}",
+            problems.len(),
+            problems.join("\n")
+        );
+    }
+    println!("Verified bound images: {n}");
+    Ok(())
+}
+
+/// Implementation of `bootc image prune`.
+#[context("Pruning images")]
+pub(crate) async fn prune_entrypoint(dry_run: bool) -> Result<()> {
+    let sysroot = crate::cli::get_storage().await?;
+    // If the image storage doesn't exist, there's nothing to prune
+    let Some(imgstore) = sysroot.get_imgstore_optional()? else {
+        println!("No images to remove");
+        return Ok(());
+    };
+    if dry_run {
+        let bound = crate::deploy::query_all_bound_images(&sysroot)?;
+        let roots = HashSet::from_iter(bound.iter().map(|img| img.image.as_str()));
+        let garbage = imgstore.images_except_roots(&roots).await?;
+        for image in garbage.iter() {
+            let names = image.names.as_deref().unwrap_or_default().join(", ");
+            println!("Would remove: {} {names}", image.id);
+        }
+        println!("Would remove images: {}", garbage.len());
+    } else {
+        let pruned = crate::deploy::prune_container_store(&sysroot).await?;
+        println!("Removed images: {}", pruned.len());
+    }
+    Ok(())
+}
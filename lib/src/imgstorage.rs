@@ -34,12 +34,16 @@ const SUBCMD_ARGV_CHUNKING: usize = 100;
 pub(crate) const STORAGE_ALIAS_DIR: &str = "/run/bootc/storage";
 /// We pass this via /proc/self/fd to the child process.
 const STORAGE_RUN_FD: i32 = 3;
+/// The authentication file for a pull is passed via /proc/self/fd to the child process.
+const AUTHFILE_FD: i32 = 4;
 
 /// The path to the image storage, relative to the bootc root directory.
 pub(crate) const SUBPATH: &str = "storage";
 /// The path to the "runroot" with transient runtime state; this is
 /// relative to the /run directory
 const RUNROOT: &str = "bootc/storage";
+/// The default container storage of the host, used as a lookaside cache when pulling.
+const HOST_STORAGE: &str = "/var/lib/containers/storage";
 pub(crate) struct Storage {
     /// The root directory
     sysroot: Dir,
@@ -196,6 +200,14 @@ impl Storage {
         Self::open(sysroot, run)
     }
 
+    /// Return true if the image storage has been created in the sysroot.
+    pub(crate) fn is_initialized(sysroot: &Dir) -> Result<bool> {
+        let subpath = &Self::subpath();
+        sysroot
+            .try_exists(subpath)
+            .with_context(|| format!("Querying {subpath}"))
+    }
+
     #[context("Opening imgstorage")]
     pub(crate) fn open(sysroot: &Dir, run: &Dir) -> Result<Self> {
         tracing::trace!("Opening container image store");
@@ -245,21 +257,34 @@ impl Storage {
         .map_err(Into::into)
     }
 
-    #[context("Pruning")]
-    pub(crate) async fn prune_except_roots(&self, roots: &HashSet<&str>) -> Result<Vec<String>> {
+    /// Return the images which would be removed by [`Self::prune_except_roots`].
+    pub(crate) async fn images_except_roots(
+        &self,
+        roots: &HashSet<&str>,
+    ) -> Result<Vec<crate::podman::ImageListEntry>> {
         let all_images = self.list_images().await?;
         tracing::debug!("Images total: {}", all_images.len(),);
-        let mut garbage = Vec::new();
-        for image in all_images {
-            if image
-                .names
-                .iter()
-                .flatten()
-                .any(|name| !roots.contains(name.as_str()))
-            {
-                garbage.push(image.id);
-            }
-        }
+        let garbage = all_images
+            .into_iter()
+            .filter(|image| {
+                image
+                    .names
+                    .iter()
+                    .flatten()
+                    .any(|name| !roots.contains(name.as_str()))
+            })
+            .collect();
+        Ok(garbage)
+    }
+
+    #[context("Pruning")]
+    pub(crate) async fn prune_except_roots(&self, roots: &HashSet<&str>) -> Result<Vec<String>> {
+        let garbage = self
+            .images_except_roots(roots)
+            .await?
+            .into_iter()
+            .map(|image| image.id)
+            .collect::<Vec<_>>();
         tracing::debug!("Images to prune: {}", garbage.len());
         for garbage in garbage.chunks(SUBCMD_ARGV_CHUNKING) {
             let mut cmd = self.new_image_cmd()?;
@@ -281,9 +306,55 @@ impl Storage {
         Ok(cmd.status().await?.success())
     }
 
+    /// Return the size in bytes of an image in the storage.
+    #[context("Querying size of {image}")]
+    pub(crate) async fn image_size(&self, image: &str) -> Result<u64> {
+        let mut cmd = self.new_image_cmd()?;
+        cmd.stdin(Stdio::null());
+        cmd.args(["inspect", "--format", "{{.Size}}", image]);
+        let o = AsyncCommand::from(cmd).output().await?;
+        if !o.status.success() {
+            anyhow::bail!(
+                "Failed to inspect image: {}",
+                String::from_utf8_lossy(&o.stderr)
+            );
+        }
+        let size = std::str::from_utf8(&o.stdout)?.trim().parse()?;
+        Ok(size)
+    }
+
+    /// Return the manifest digest of an image in the storage.
+    #[context("Querying digest of {image}")]
+    pub(crate) fn image_digest(&self, image: &str) -> Result<String> {
+        let mut cmd = self.new_image_cmd()?;
+        cmd.stdin(Stdio::null());
+        cmd.args(["inspect", "--format", "{{.Digest}}", image]);
+        let digest = cmd.run_get_string()?;
+        Ok(digest.trim().to_owned())
+    }
+
+    /// Add the name `target` to the image `source`.
+    #[context("Tagging {source} as {target}")]
+    pub(crate) async fn tag(&self, source: &str, target: &str) -> Result<()> {
+        let mut cmd = self.new_image_cmd()?;
+        cmd.stdin(Stdio::null());
+        cmd.stdout(Stdio::null());
+        cmd.args(["tag", source, target]);
+        AsyncCommand::from(cmd).run().await
+    }
+
     /// Fetch the image if it is not already present; return whether
     /// or not the image was fetched.
-    pub(crate) async fn pull(&self, image: &str, mode: PullMode) -> Result<bool> {
+    ///
+    /// If `authfile` is not provided, the global bootc authentication file
+    /// (if any) is used.
+    pub(crate) async fn pull(
+        &self,
+        image: &str,
+        mode: PullMode,
+        authfile: Option<&std::fs::File>,
+        oci_mirror: Option<&Utf8Path>,
+    ) -> Result<bool> {
         match mode {
             PullMode::IfNotExists => {
                 if self.exists(image).await? {
@@ -293,13 +364,28 @@ impl Storage {
             }
             PullMode::Always => {}
         };
+        // Unless we're explicitly checking for an update, try local copies first.
+        if mode == PullMode::IfNotExists {
+            match self.pull_from_lookaside(image, oci_mirror).await {
+                Ok(true) => return Ok(true),
+                Ok(false) => {}
+                Err(e) => {
+                    tracing::warn!("Failed to copy {image} from lookaside, fetching it: {e:#}")
+                }
+            }
+        }
         let mut cmd = self.new_image_cmd()?;
         cmd.stdin(Stdio::null());
         cmd.stdout(Stdio::null());
         cmd.args(["pull", image]);
-        let authfile = ostree_ext::globals::get_global_authfile(&self.sysroot)?
-            .map(|(authfile, _fd)| authfile);
+        let authfile = match authfile {
+            Some(f) => Some(f.try_clone().context("Cloning authfile")?),
+            None => ostree_ext::globals::get_global_authfile(&self.sysroot)?.map(|(_path, fd)| fd),
+        };
         if let Some(authfile) = authfile {
+            let authfile: Arc<OwnedFd> = Arc::new(authfile.into());
+            cmd.take_fd_n(authfile, AUTHFILE_FD);
+            let authfile = format!("/proc/self/fd/{AUTHFILE_FD}");
             cmd.args(["--authfile", authfile.as_str()]);
         }
         tracing::debug!("Pulling image: {image}");
@@ -308,6 +394,61 @@ impl Storage {
         Ok(true)
     }
 
+    /// Copy the image from the host's default container storage or the OCI mirror
+    /// directory, if present in either; return whether it was found. This is only
+    /// done for digest-pinned references, as a tag may refer to a different image
+    /// locally than in the registry.
+    async fn pull_from_lookaside(
+        &self,
+        image: &str,
+        oci_mirror: Option<&Utf8Path>,
+    ) -> Result<bool> {
+        let Some((_, digest)) = image.split_once('@') else {
+            return Ok(false);
+        };
+        if exists_in_host_storage(image).await? {
+            tracing::debug!("Copying {image} from host storage");
+            self.pull_from_host_storage(image).await?;
+            return Ok(true);
+        }
+        if let Some(mirror) = oci_mirror {
+            let mirror_dir = Dir::open_ambient_dir(mirror, cap_std::ambient_authority())
+                .with_context(|| format!("Opening {mirror}"))?;
+            if let Some(index) = oci_mirror_find(&mirror_dir, digest)? {
+                tracing::debug!("Copying {image} from {mirror}");
+                self.pull_from_oci_mirror(mirror, index, image).await?;
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    /// Copy the image at position `index` in an OCI image layout directory to this
+    /// storage, naming it `image`.
+    #[context("Pulling {image} from {mirror}")]
+    async fn pull_from_oci_mirror(
+        &self,
+        mirror: &Utf8Path,
+        index: usize,
+        image: &str,
+    ) -> Result<()> {
+        let mut cmd = self.new_image_cmd()?;
+        cmd.stdin(Stdio::null());
+        cmd.args(["pull", "--quiet"])
+            .arg(format!("oci:{mirror}:@{index}"));
+        let o = AsyncCommand::from(cmd).output().await?;
+        if !o.status.success() {
+            anyhow::bail!(
+                "Failed to pull image: {}",
+                String::from_utf8_lossy(&o.stderr)
+            );
+        }
+        let id = std::str::from_utf8(&o.stdout)?.trim();
+        // Images pulled from an OCI directory are not named after the reference,
+        // so add the name we expect.
+        self.tag(id, image).await
+    }
+
     /// Copy an image from the default container storage (/var/lib/containers/)
     /// to this storage.
     #[context("Pulling from host storage: {image}")]
@@ -336,8 +477,75 @@ impl Storage {
     }
 }
 
+/// Return true if the image exists in the host's default container storage.
+async fn exists_in_host_storage(image: &str) -> Result<bool> {
+    // Avoid implicitly initializing the host storage if it doesn't exist.
+    if !Utf8Path::new(HOST_STORAGE).try_exists()? {
+        return Ok(false);
+    }
+    let mut cmd = AsyncCommand::new("podman");
+    cmd.stdin(Stdio::null());
+    cmd.args(["image", "exists", image]);
+    Ok(cmd.status().await?.success())
+}
+
+/// Return the position in the index of the OCI image layout directory of the
+/// image with the provided digest, if present.
+fn oci_mirror_find(mirror: &Dir, digest: &str) -> Result<Option<usize>> {
+    let Some(index) = mirror.open_optional("index.json")? else {
+        return Ok(None);
+    };
+    let index =
+        ostree_ext::oci_spec::image::ImageIndex::from_reader(std::io::BufReader::new(index))
+            .context("Parsing index.json")?;
+    let r = index
+        .manifests()
+        .iter()
+        .position(|m| m.digest().to_string() == digest);
+    Ok(r)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     static_assertions::assert_not_impl_any!(Storage: Sync);
+
+    #[test]
+    fn test_oci_mirror_find() -> Result<()> {
+        let td = &cap_std_ext::cap_tempfile::TempDir::new(cap_std::ambient_authority())?;
+        let digest = "sha256:1e5c1b2a7d3f4e6a8b9c0d1e2f3a4b5c6d7e8f9a0b1c2d3e4f5a6b7c8d9e0f1a";
+        assert_eq!(oci_mirror_find(td, digest)?, None);
+        td.write(
+            "index.json",
+            indoc::indoc! { r#"
+            {
+              "schemaVersion": 2,
+              "manifests": [
+                {
+                  "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                  "digest": "sha256:0e5c1b2a7d3f4e6a8b9c0d1e2f3a4b5c6d7e8f9a0b1c2d3e4f5a6b7c8d9e0f1a",
+                  "size": 1024,
+                  "annotations": {
+                    "org.opencontainers.image.ref.name": "quay.io/example/app:latest"
+                  }
+                },
+                {
+                  "mediaType": "application/vnd.oci.image.manifest.v1+json",
+                  "digest": "sha256:1e5c1b2a7d3f4e6a8b9c0d1e2f3a4b5c6d7e8f9a0b1c2d3e4f5a6b7c8d9e0f1a",
+                  "size": 1024
+                }
+              ]
+            }
+        "# },
+        )?;
+        assert_eq!(oci_mirror_find(td, digest)?, Some(1));
+        assert_eq!(
+            oci_mirror_find(
+                td,
+                "sha256:2e5c1b2a7d3f4e6a8b9c0d1e2f3a4b5c6d7e8f9a0b1c2d3e4f5a6b7c8d9e0f1a"
+            )?,
+            None
+        );
+        Ok(())
+    }
 }
@@ -145,9 +145,9 @@ pub(crate) enum BoundImagesOpt {
     #[clap(hide = true)]
     /// Do not resolve any "logically bound" images at install time.
     Skip,
-    // TODO: Once we implement https://github.com/containers/bootc/issues/863 update this comment
-    // to mention source's root container storage being used as lookaside cache
-    /// Bound images will be pulled and stored directly in the target's bootc container storage
+    /// Bound images will be pulled and stored directly in the target's bootc container storage;
+    /// images referenced by digest which are present in the source's root container storage
+    /// are copied from there instead
     Pull,
 }
 
@@ -760,7 +760,9 @@ async fn install_container(
     let kargsd = crate::kargs::get_kargs_from_ostree_root(
         &sysroot.repo(),
         merged_ostree_root.downcast_ref().unwrap(),
-        std::env::consts::ARCH,
+        // The hardware is the one of the installing host, so only the architecture
+        // is matched; files matching the hardware are applied once installed.
+        &crate::kargs::HostFacts::for_arch(std::env::consts::ARCH),
     )?;
     let kargsd = kargsd.iter().map(|s| s.as_str());
 
@@ -1325,7 +1327,7 @@ async fn install_with_sysroot(
 ) -> Result<()> {
     // And actually set up the container in that root, returning a deployment and
     // the aleph state (see below).
-    let (_deployment, aleph) = install_container(state, rootfs, &sysroot, has_ostree).await?;
+    let (deployment, aleph) = install_container(state, rootfs, &sysroot, has_ostree).await?;
     // Write the aleph data that captures the system state at the time of provisioning for aid in future debugging.
     rootfs
         .physical_root
@@ -1353,6 +1355,7 @@ async fn install_with_sysroot(
     // today.
     let imgstore = sysroot.get_ensure_imgstore()?;
 
+    let record_bound_images = !matches!(bound_images, BoundImages::Skip);
     match bound_images {
         BoundImages::Skip => {}
         BoundImages::Resolved(resolved_bound_images) => {
@@ -1363,11 +1366,20 @@ async fn install_with_sysroot(
             }
         }
         BoundImages::Unresolved(bound_images) => {
-            crate::boundimage::pull_images_impl(imgstore, bound_images)
-                .await
-                .context("pulling bound images")?;
+            crate::boundimage::pull_images_impl(
+                imgstore,
+                &state.container_root,
+                None,
+                bound_images,
+                Default::default(),
+            )
+            .await
+            .context("pulling bound images")?;
         }
     }
+    if record_bound_images {
+        crate::boundimage::record_bound_images(sysroot, &deployment)?;
+    }
 
     Ok(())
 }
@@ -63,7 +63,8 @@ fn reconcile_kargs(sysroot: &ostree::Sysroot, deployment: &ostree::Deployment) -
         .flatten()
         .map(|s| s.as_str())
         .collect::<Vec<_>>();
-    let kargsd = crate::kargs::get_kargs_in_root(deployment_root, std::env::consts::ARCH)?;
+    let facts = &crate::kargs::HostFacts::for_arch(std::env::consts::ARCH);
+    let kargsd = crate::kargs::get_kargs_in_root(deployment_root, facts)?;
     let kargsd = kargsd.iter().map(|s| s.as_str()).collect::<Vec<_>>();
 
     current_kargs.append_argv(&install_config_kargs);
@@ -287,14 +288,21 @@ pub(crate) async fn impl_completion(
     reconcile_kargs(&sysroot, deployment)?;
 
     // ostree-ext doesn't do logically bound images
-    let bound_images = crate::boundimage::query_bound_images_for_deployment(sysroot, deployment)?;
+    let deployment_root = &crate::utils::deployment_fd(sysroot, deployment)?;
+    let bound_images = crate::boundimage::query_bound_images(deployment_root)?;
     if !bound_images.is_empty() {
         // When we're run through ostree, we only lazily initialize the podman storage to avoid
         // having a hard dependency on it.
         let imgstorage = &crate::imgstorage::Storage::create(&sysroot_dir, &rundir)?;
-        crate::boundimage::pull_images_impl(imgstorage, bound_images)
-            .await
-            .context("pulling bound images")?;
+        crate::boundimage::pull_images_impl(
+            imgstorage,
+            deployment_root,
+            None,
+            bound_images,
+            Default::default(),
+        )
+        .await
+        .context("pulling bound images")?;
     }
 
     Ok(())
@@ -1,10 +1,16 @@
+use std::cell::OnceCell;
+use std::io::Read;
+
 use anyhow::{Context, Result};
 use camino::Utf8Path;
+use cap_std_ext::cap_std;
 use cap_std_ext::cap_std::fs::Dir;
 use cap_std_ext::cap_std::fs_utf8::Dir as DirUtf8;
 use cap_std_ext::dirext::CapStdExtDirExt;
 use cap_std_ext::dirext::CapStdExtDirExtUtf8;
-use ostree::gio;
+use fn_error_context::context;
+use ostree::{gio, glib};
+use ostree_ext::keyfileext::map_keyfile_optional;
 use ostree_ext::ostree;
 use ostree_ext::ostree::Deployment;
 use ostree_ext::prelude::Cast;
@@ -13,19 +19,279 @@ use ostree_ext::prelude::FileExt;
 use serde::Deserialize;
 
 use crate::deploy::ImageState;
+use crate::kernel::{Cmdline, Karg};
+use crate::spec::LocalKargs;
 use crate::store::Storage;
 
 const KARGS_PATH: &str = "usr/lib/bootc/kargs.d";
+/// Host-local kargs.d files, which are applied on top of the image's.
+const HOST_KARGS_PATH: &str = "etc/bootc/kargs.d";
+/// The sysfs directory with DMI identification.
+const DMI_PATH: &str = "sys/class/dmi/id";
+/// The sysfs directory with PCI devices.
+const PCI_DEVICES_PATH: &str = "sys/bus/pci/devices";
+/// The group in the origin file for bootc-specific state.
+const ORIGIN_BOOTC_GROUP: &str = "bootc";
+/// Origin key for locally added kernel arguments.
+const ORIGIN_KARGS_APPEND: &str = "kargs-append";
+/// Origin key for locally removed kernel arguments.
+const ORIGIN_KARGS_DELETE: &str = "kargs-delete";
+/// Origin key for the kernel arguments from /usr/lib/bootc/kargs.d which
+/// applied to the deployment.
+const ORIGIN_KARGS_IMAGE: &str = "kargs-image";
+/// Origin key for the kernel arguments added by /etc/bootc/kargs.d.
+const ORIGIN_KARGS_HOST_APPEND: &str = "kargs-host-append";
+/// Origin key for the kernel arguments removed by /etc/bootc/kargs.d.
+const ORIGIN_KARGS_HOST_REMOVE: &str = "kargs-host-remove";
 
 /// The kargs.d configuration file.
 #[derive(Deserialize)]
 #[serde(rename_all = "kebab-case", deny_unknown_fields)]
 struct Config {
-    /// Ordered list of kernel arguments.
-    kargs: Vec<String>,
+    /// Ordered list of kernel arguments; this is required except in
+    /// /etc/bootc/kargs.d.
+    kargs: Option<Vec<String>>,
+    /// Kernel arguments to remove; only supported in /etc/bootc/kargs.d.
+    /// An argument without a value (e.g. `console`) removes all instances
+    /// of that key.
+    #[serde(default)]
+    remove: Vec<String>,
     /// Optional list of architectures (using the Rust naming conventions);
     /// if present and the current architecture doesn't match, the file is skipped.
     match_architectures: Option<Vec<String>>,
+    /// Optional list of DMI system vendors (`/sys/class/dmi/id/sys_vendor`).
+    match_dmi_sys_vendor: Option<Vec<String>>,
+    /// Optional list of DMI product names (`/sys/class/dmi/id/product_name`).
+    match_dmi_product_name: Option<Vec<String>>,
+    /// Optional list of PCI devices in `vendor:device` form (e.g. `10de:2204`,
+    /// or `10de:*` for any device of a vendor); the file applies if any is present.
+    match_pci_devices: Option<Vec<String>>,
+    /// If present, the file only applies to virtual machines (`true`) or
+    /// to bare metal systems (`false`).
+    match_virtualized: Option<bool>,
+}
+
+/// A parsed kargs.d file.
+#[derive(Debug)]
+pub(crate) struct KargsFile {
+    /// The file name
+    pub(crate) name: String,
+    /// The kernel arguments in the file
+    pub(crate) kargs: Vec<String>,
+    /// Whether the file applies to the host
+    pub(crate) matched: bool,
+    /// Why the file applies to the host or not
+    pub(crate) reason: String,
+}
+
+/// Properties of the host used to evaluate the `match-*` keys of kargs.d files.
+#[derive(Debug, Default)]
+pub(crate) struct HostFacts {
+    /// The architecture, using the Rust naming conventions
+    arch: String,
+    /// Whether this is the running host, whose hardware can be queried
+    live: bool,
+    /// The hardware properties, queried on first use; unset if unknown
+    hardware: OnceCell<Option<HardwareFacts>>,
+}
+
+/// Hardware properties of the running host.
+#[derive(Debug, Clone, Default)]
+struct HardwareFacts {
+    /// The DMI system vendor
+    sys_vendor: Option<String>,
+    /// The DMI product name
+    product_name: Option<String>,
+    /// The PCI devices present, in `vendor:device` form
+    pci_devices: Vec<String>,
+    /// Whether this is a virtual machine
+    virtualized: bool,
+}
+
+impl HostFacts {
+    /// Only the architecture is known; files using the hardware `match-*` keys
+    /// never match. This is used at install time, where the hardware is the one
+    /// of the installing host, and for the image (e.g. when linting).
+    pub(crate) fn for_arch(arch: &str) -> Self {
+        Self {
+            arch: arch.to_owned(),
+            live: false,
+            hardware: OnceCell::from(None),
+        }
+    }
+
+    /// The running host; its hardware is only queried if a kargs.d file uses
+    /// a hardware `match-*` key.
+    pub(crate) fn running() -> Self {
+        Self {
+            arch: std::env::consts::ARCH.to_owned(),
+            live: true,
+            hardware: OnceCell::new(),
+        }
+    }
+
+    /// Return the hardware properties, querying them if needed.
+    fn hardware(&self) -> Result<Option<&HardwareFacts>> {
+        if let Some(hw) = self.hardware.get() {
+            return Ok(hw.as_ref());
+        }
+        let hw = self.live.then(HardwareFacts::query).transpose()?;
+        Ok(self.hardware.get_or_init(|| hw).as_ref())
+    }
+}
+
+impl HardwareFacts {
+    /// Gather the hardware properties of the running host.
+    #[context("Querying host properties")]
+    fn query() -> Result<Self> {
+        let root = &Dir::open_ambient_dir("/", cap_std::ambient_authority())?;
+        // If systemd-detect-virt isn't available, assume bare metal
+        let virtualized = std::process::Command::new("systemd-detect-virt")
+            .args(["--vm", "--quiet"])
+            .status()
+            .map(|st| st.success())
+            .unwrap_or_default();
+        Self::from_root(root, virtualized)
+    }
+
+    /// Gather the properties of the host from the sysfs mounted in `root`.
+    fn from_root(root: &Dir, virtualized: bool) -> Result<Self> {
+        let read = |path: &str| -> Result<Option<String>> {
+            let Some(mut f) = root.open_optional(path)? else {
+                return Ok(None);
+            };
+            let mut buf = String::new();
+            f.read_to_string(&mut buf)
+                .with_context(|| format!("Reading {path}"))?;
+            Ok(Some(buf.trim().to_owned()))
+        };
+        let dmi = |name: &str| read(&format!("{DMI_PATH}/{name}"));
+        let mut pci_devices = Vec::new();
+        if let Some(d) = root.open_dir_optional(PCI_DEVICES_PATH)? {
+            for ent in d.entries()? {
+                let name = ent?.file_name();
+                let Some(name) = name.to_str() else {
+                    continue;
+                };
+                let id = |attr: &str| -> Result<Option<String>> {
+                    // The device entries are symlinks, so these need to be resolved from the root
+                    let v = read(&format!("{PCI_DEVICES_PATH}/{name}/{attr}"))?;
+                    Ok(v.map(|v| v.trim_start_matches("0x").to_ascii_lowercase()))
+                };
+                if let (Some(vendor), Some(device)) = (id("vendor")?, id("device")?) {
+                    pci_devices.push(format!("{vendor}:{device}"));
+                }
+            }
+        }
+        pci_devices.sort();
+        pci_devices.dedup();
+        Ok(Self {
+            sys_vendor: dmi("sys_vendor")?,
+            product_name: dmi("product_name")?,
+            pci_devices,
+            virtualized,
+        })
+    }
+}
+
+/// The combined host-local configuration from /etc/bootc/kargs.d.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub(crate) struct HostKargs {
+    /// Kernel arguments to add
+    append: Vec<String>,
+    /// Kernel arguments to remove
+    remove: Vec<String>,
+}
+
+impl HostKargs {
+    /// Apply this on top of the kernel arguments from the image's kargs.d.
+    fn apply(&self, kargs: &mut Cmdline) {
+        for pattern in self.remove.iter() {
+            kargs.remove(&Karg::from(pattern.as_str()));
+        }
+        for karg in self.append.iter() {
+            kargs.add(Karg::from(karg.as_str()));
+        }
+    }
+}
+
+/// Load and parse the host-local kargs.d files in /etc/bootc/kargs.d of the given root.
+fn get_host_kargs_in_root(d: &Dir, facts: &HostFacts) -> Result<HostKargs> {
+    let Some(d) = d
+        .open_dir_optional(HOST_KARGS_PATH)?
+        .map(DirUtf8::from_cap_std)
+    else {
+        return Ok(Default::default());
+    };
+    let mut ret = HostKargs::default();
+    let entries = d.filenames_filtered_sorted(|_, name| Config::filename_matches(name))?;
+    for name in entries {
+        let buf = d.read_to_string(&name)?;
+        let de: Config =
+            toml::from_str(&buf).with_context(|| format!("Parsing /{HOST_KARGS_PATH}/{name}"))?;
+        if !de.evaluate(facts)?.matched {
+            continue;
+        }
+        ret.append.extend(de.kargs.unwrap_or_default());
+        ret.remove.extend(de.remove);
+    }
+    Ok(ret)
+}
+
+/// Read the /etc/bootc/kargs.d configuration which was applied to a deployment.
+fn host_kargs_from_origin(origin: &glib::KeyFile) -> Result<HostKargs> {
+    Ok(HostKargs {
+        append: origin_string_list(origin, ORIGIN_KARGS_HOST_APPEND)?,
+        remove: origin_string_list(origin, ORIGIN_KARGS_HOST_REMOVE)?,
+    })
+}
+
+/// Record the /etc/bootc/kargs.d configuration applied to a deployment in its origin.
+fn set_origin_host_kargs(origin: &glib::KeyFile, host: &HostKargs) {
+    set_origin_string_lists(
+        origin,
+        [
+            (ORIGIN_KARGS_HOST_APPEND, &host.append),
+            (ORIGIN_KARGS_HOST_REMOVE, &host.remove),
+        ],
+    )
+}
+
+/// Read the kernel arguments from /usr/lib/bootc/kargs.d which were applied to a
+/// deployment, if recorded.
+pub(crate) fn image_kargs_from_origin(origin: &glib::KeyFile) -> Result<Option<Vec<String>>> {
+    let v = map_keyfile_optional(origin.string_list(ORIGIN_BOOTC_GROUP, ORIGIN_KARGS_IMAGE))?;
+    Ok(v.map(|v| v.into_iter().map(Into::into).collect()))
+}
+
+/// Record the kernel arguments from /usr/lib/bootc/kargs.d applied to a deployment
+/// in its origin; unlike the other keys, this is also set if empty.
+fn set_origin_image_kargs(origin: &glib::KeyFile, kargs: &Cmdline) {
+    let kargs = kargs
+        .args()
+        .iter()
+        .map(|k| k.to_string())
+        .collect::<Vec<_>>();
+    let kargs = kargs.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+    origin.set_string_list(ORIGIN_BOOTC_GROUP, ORIGIN_KARGS_IMAGE, &kargs);
+}
+
+/// Returns true if the PCI id (`vendor:device`) matches `pattern`, where
+/// the device may be `*`.
+fn pci_id_matches(id: &str, pattern: &str) -> bool {
+    let Some((vendor, device)) = id.split_once(':') else {
+        return false;
+    };
+    let (pvendor, pdevice) = pattern.split_once(':').unwrap_or((pattern, "*"));
+    vendor.eq_ignore_ascii_case(pvendor) && (pdevice == "*" || device.eq_ignore_ascii_case(pdevice))
+}
+
+/// Whether a kargs.d file applies to the host.
+#[derive(Debug, PartialEq, Eq)]
+struct Match {
+    matched: bool,
+    /// A human readable description of why the file matched or not
+    reason: String,
 }
 
 impl Config {
@@ -33,11 +299,118 @@ impl Config {
     fn filename_matches(name: &str) -> bool {
         matches!(Utf8Path::new(name).extension(), Some("toml"))
     }
+
+    /// Returns true if any of the `match-*` keys which depend on the hardware is present.
+    fn uses_hardware(&self) -> bool {
+        self.match_dmi_sys_vendor.is_some()
+            || self.match_dmi_product_name.is_some()
+            || self.match_pci_devices.is_some()
+            || self.match_virtualized.is_some()
+    }
+
+    /// Evaluate the `match-*` keys against the host. All keys which are
+    /// present must match.
+    fn evaluate(&self, facts: &HostFacts) -> Result<Match> {
+        let unmatched = |reason: String| {
+            Ok(Match {
+                matched: false,
+                reason,
+            })
+        };
+        let mut reasons = Vec::new();
+        if let Some(arches) = self.match_architectures.as_ref() {
+            let arch = &facts.arch;
+            if !arches.iter().any(|a| a == arch) {
+                return unmatched(format!(
+                    "architecture {arch} is not one of: {}",
+                    arches.join(", ")
+                ));
+            }
+            reasons.push(format!("architecture {arch}"));
+        }
+        // Only query the hardware if needed; otherwise the (unused) defaults are fine
+        let unused = HardwareFacts::default();
+        let hw = if self.uses_hardware() {
+            let Some(hw) = facts.hardware()? else {
+                return unmatched("hardware is only matched on the installed host".to_owned());
+            };
+            hw
+        } else {
+            &unused
+        };
+        for (name, wanted, actual) in [
+            (
+                "DMI sys_vendor",
+                &self.match_dmi_sys_vendor,
+                hw.sys_vendor.as_deref(),
+            ),
+            (
+                "DMI product_name",
+                &self.match_dmi_product_name,
+                hw.product_name.as_deref(),
+            ),
+        ] {
+            let Some(wanted) = wanted.as_ref() else {
+                continue;
+            };
+            match actual.filter(|v| wanted.iter().any(|w| w == v)) {
+                Some(v) => reasons.push(format!("{name} {v}")),
+                None => {
+                    return unmatched(format!(
+                        "{name} {} is not one of: {}",
+                        actual.unwrap_or("(unknown)"),
+                        wanted.join(", ")
+                    ))
+                }
+            }
+        }
+        if let Some(wanted) = self.match_pci_devices.as_ref() {
+            let found = hw
+                .pci_devices
+                .iter()
+                .find(|id| wanted.iter().any(|w| pci_id_matches(id, w)));
+            match found {
+                Some(id) => reasons.push(format!("PCI device {id}")),
+                None => return unmatched(format!("no PCI device matching: {}", wanted.join(", "))),
+            }
+        }
+        if let Some(wanted) = self.match_virtualized {
+            let desc = if hw.virtualized {
+                "system is a virtual machine"
+            } else {
+                "system is bare metal"
+            };
+            if wanted != hw.virtualized {
+                return unmatched(desc.to_owned());
+            }
+            reasons.push(desc.to_owned());
+        }
+        let reason = if reasons.is_empty() {
+            "unconditional".to_owned()
+        } else {
+            reasons.join(", ")
+        };
+        Ok(Match {
+            matched: true,
+            reason,
+        })
+    }
 }
 
 /// Load and parse all bootc kargs.d files in the specified root, returning
 /// a combined list.
-pub(crate) fn get_kargs_in_root(d: &Dir, sys_arch: &str) -> Result<Vec<String>> {
+pub(crate) fn get_kargs_in_root(d: &Dir, facts: &HostFacts) -> Result<Vec<String>> {
+    let r = get_kargs_files_in_root(d, facts)?
+        .into_iter()
+        .filter(|f| f.matched)
+        .flat_map(|f| f.kargs)
+        .collect();
+    Ok(r)
+}
+
+/// Load and parse all bootc kargs.d files in the specified root, returning
+/// for each file whether it applies to the host, and why.
+pub(crate) fn get_kargs_files_in_root(d: &Dir, facts: &HostFacts) -> Result<Vec<KargsFile>> {
     // If the directory doesn't exist, that's OK.
     let Some(d) = d.open_dir_optional(KARGS_PATH)?.map(DirUtf8::from_cap_std) else {
         return Ok(Default::default());
@@ -46,8 +419,8 @@ pub(crate) fn get_kargs_in_root(d: &Dir, sys_arch: &str) -> Result<Vec<String>>
     let entries = d.filenames_filtered_sorted(|_, name| Config::filename_matches(name))?;
     for name in entries {
         let buf = d.read_to_string(&name)?;
-        let kargs = parse_kargs_toml(&buf, sys_arch).with_context(|| format!("Parsing {name}"))?;
-        ret.extend(kargs)
+        let f = parse_kargs_file(&name, &buf, facts).with_context(|| format!("Parsing {name}"))?;
+        ret.push(f)
     }
     Ok(ret)
 }
@@ -56,21 +429,21 @@ pub(crate) fn get_kargs_in_root(d: &Dir, sys_arch: &str) -> Result<Vec<String>>
 pub(crate) fn get_kargs_from_ostree_root(
     repo: &ostree::Repo,
     root: &ostree::RepoFile,
-    sys_arch: &str,
+    facts: &HostFacts,
 ) -> Result<Vec<String>> {
     let kargsd = root.resolve_relative_path(KARGS_PATH);
     let kargsd = kargsd.downcast_ref::<ostree::RepoFile>().expect("downcast");
     if !kargsd.query_exists(gio::Cancellable::NONE) {
         return Ok(Default::default());
     }
-    get_kargs_from_ostree(repo, kargsd, sys_arch)
+    get_kargs_from_ostree(repo, kargsd, facts)
 }
 
 /// Load kargs.d files from the target dir
 fn get_kargs_from_ostree(
     repo: &ostree::Repo,
     fetched_tree: &ostree::RepoFile,
-    sys_arch: &str,
+    facts: &HostFacts,
 ) -> Result<Vec<String>> {
     let cancellable = gio::Cancellable::NONE;
     let queryattrs = "standard::name,standard::type";
@@ -99,7 +472,7 @@ fn get_kargs_from_ostree(
             ostree_ext::prelude::InputStreamExtManual::into_read(file_content.unwrap());
         let s = std::io::read_to_string(&mut reader)?;
         let parsed_kargs =
-            parse_kargs_toml(&s, sys_arch).with_context(|| format!("Parsing {name}"))?;
+            parse_kargs_toml(&s, facts).with_context(|| format!("Parsing {name}"))?;
         ret.extend(parsed_kargs);
     }
     Ok(ret)
@@ -108,27 +481,43 @@ fn get_kargs_from_ostree(
 /// Compute the kernel arguments for the new deployment. This starts from the booted
 /// karg, but applies the diff between the bootc karg files in /usr/lib/bootc/kargs.d
 /// between the booted deployment and the new one.
+///
+/// The kargs.d arguments of the merge deployment are the ones recorded in its origin
+/// when it was created, as the `match-*` keys may evaluate differently now; e.g. files
+/// matching the hardware are skipped at install time, and so are added by the first
+/// deployment on the installed host.
+///
+/// The host-local configuration in /etc/bootc/kargs.d is applied on top of both;
+/// the state used for the new deployment is recorded in `origin`.
 pub(crate) fn get_kargs(
     sysroot: &Storage,
     merge_deployment: &Deployment,
     fetched: &ImageState,
+    origin: &glib::KeyFile,
 ) -> Result<Vec<String>> {
     let cancellable = gio::Cancellable::NONE;
     let repo = &sysroot.repo();
-    let mut kargs = vec![];
-    let sys_arch = std::env::consts::ARCH;
+    let facts = &HostFacts::running();
 
     // Get the kargs used for the merge in the bootloader config
-    if let Some(bootconfig) = ostree::Deployment::bootconfig(merge_deployment) {
-        if let Some(options) = ostree::BootconfigParser::get(&bootconfig, "options") {
-            let options = options.split_whitespace().map(|s| s.to_owned());
-            kargs.extend(options);
-        }
-    };
+    let mut kargs = ostree::Deployment::bootconfig(merge_deployment)
+        .and_then(|bootconfig| ostree::BootconfigParser::get(&bootconfig, "options"))
+        .map(|options| Cmdline::parse(&options))
+        .unwrap_or_default();
 
-    // Get the kargs in kargs.d of the merge
+    // Get the kargs in kargs.d of the merge; if they weren't recorded, the deployment
+    // was created before the hardware `match-*` keys were supported, or at install time.
     let merge_root = &crate::utils::deployment_fd(sysroot, merge_deployment)?;
-    let existing_kargs = get_kargs_in_root(merge_root, sys_arch)?;
+    let recorded = merge_deployment
+        .origin()
+        .map(|origin| image_kargs_from_origin(&origin))
+        .transpose()?
+        .flatten();
+    let existing_kargs = match recorded {
+        Some(kargs) => kargs,
+        None => get_kargs_in_root(merge_root, &HostFacts::for_arch(std::env::consts::ARCH))?,
+    };
+    let mut existing_kargs = Cmdline::from_args(existing_kargs.iter().map(|s| s.as_str()));
 
     // Get the kargs in kargs.d of the pending image
     let (fetched_tree, _) = repo.read_commit(fetched.ostree_commit.as_str(), cancellable)?;
@@ -137,55 +526,220 @@ pub(crate) fn get_kargs(
         .downcast::<ostree::RepoFile>()
         .expect("downcast");
     // A special case: if there's no kargs.d directory in the pending (fetched) image,
-    // then we can just use the combined current kargs + kargs from booted
-    if !fetched_tree.query_exists(cancellable) {
-        kargs.extend(existing_kargs);
-        return Ok(kargs);
-    }
+    // then we just keep the current kargs
+    let mut remote_kargs = if fetched_tree.query_exists(cancellable) {
+        // Fetch the kernel arguments from the new root
+        let remote_kargs = get_kargs_from_ostree(repo, &fetched_tree, facts)?;
+        Cmdline::from_args(remote_kargs.iter().map(|s| s.as_str()))
+    } else {
+        existing_kargs.clone()
+    };
 
-    // Fetch the kernel arguments from the new root
-    let remote_kargs = get_kargs_from_ostree(repo, &fetched_tree, sys_arch)?;
+    // The /etc/bootc/kargs.d state when the merge deployment was created, and now;
+    // /etc is carried forward from the merge deployment.
+    let previous_host = merge_deployment
+        .origin()
+        .map(|origin| host_kargs_from_origin(&origin))
+        .transpose()?
+        .unwrap_or_default();
+    let host = get_host_kargs_in_root(merge_root, facts)?;
+    set_origin_image_kargs(origin, &remote_kargs);
+    previous_host.apply(&mut existing_kargs);
+    host.apply(&mut remote_kargs);
+    set_origin_host_kargs(origin, &host);
 
-    // get the diff between the existing and remote kargs
-    let mut added_kargs = remote_kargs
-        .clone()
-        .into_iter()
-        .filter(|item| !existing_kargs.contains(item))
-        .collect::<Vec<_>>();
-    let removed_kargs = existing_kargs
-        .clone()
-        .into_iter()
-        .filter(|item| !remote_kargs.contains(item))
-        .collect::<Vec<_>>();
+    merge_kargs(&mut kargs, &existing_kargs, &remote_kargs);
+    Ok(kargs.into_args())
+}
 
+/// Apply the change between the `existing` and `remote` kargs.d arguments to `kargs`.
+///
+/// Arguments which were removed from kargs.d are removed; added arguments are
+/// merged by key (see [`Cmdline::add`]), so e.g. changing `mitigations=off` to
+/// `mitigations=auto` replaces the value, even if it was changed locally.
+fn merge_kargs(kargs: &mut Cmdline, existing: &Cmdline, remote: &Cmdline) {
+    let removed = existing.args().iter().filter(|a| !remote.contains(a));
+    let added = remote.args().iter().filter(|a| !existing.contains(a));
     tracing::debug!(
         "kargs: added={:?} removed={:?}",
-        &added_kargs,
-        removed_kargs
+        added.clone().map(|a| a.to_string()).collect::<Vec<_>>(),
+        removed.clone().map(|a| a.to_string()).collect::<Vec<_>>()
     );
 
-    // apply the diff to the system kargs
-    kargs.retain(|x| !removed_kargs.contains(x));
-    kargs.append(&mut added_kargs);
+    // Add first, so that changed values are replaced in place
+    for arg in added {
+        kargs.add(arg.clone());
+    }
+    for arg in removed {
+        kargs.remove(arg);
+    }
+}
+
+/// Read the locally managed kernel arguments from a deployment origin.
+pub(crate) fn local_kargs_from_origin(origin: &glib::KeyFile) -> Result<LocalKargs> {
+    Ok(LocalKargs {
+        append: origin_string_list(origin, ORIGIN_KARGS_APPEND)?,
+        delete: origin_string_list(origin, ORIGIN_KARGS_DELETE)?,
+    })
+}
+
+/// Get a string list key in the bootc group of the origin, if present.
+fn origin_string_list(origin: &glib::KeyFile, key: &str) -> Result<Vec<String>> {
+    let v = map_keyfile_optional(origin.string_list(ORIGIN_BOOTC_GROUP, key))?.unwrap_or_default();
+    Ok(v.into_iter().map(Into::into).collect())
+}
 
-    Ok(kargs)
+/// Record the locally managed kernel arguments in a deployment origin.
+pub(crate) fn set_origin_local_kargs(origin: &glib::KeyFile, local: &LocalKargs) {
+    set_origin_string_lists(
+        origin,
+        [
+            (ORIGIN_KARGS_APPEND, &local.append),
+            (ORIGIN_KARGS_DELETE, &local.delete),
+        ],
+    )
+}
+
+/// Set string list keys in the bootc group of the origin, removing empty ones.
+fn set_origin_string_lists<'a>(
+    origin: &glib::KeyFile,
+    values: impl IntoIterator<Item = (&'a str, &'a Vec<String>)>,
+) {
+    for (key, value) in values {
+        if value.is_empty() {
+            // Ignore the error if the key doesn't exist
+            let _ = origin.remove_key(ORIGIN_BOOTC_GROUP, key);
+        } else {
+            let value = value.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+            origin.set_string_list(ORIGIN_BOOTC_GROUP, key, &value);
+        }
+    }
+}
+
+/// Returns true if the kernel argument `karg` matches `pattern`; a pattern without
+/// a value (e.g. `console`) matches all instances of that key.
+fn karg_matches(karg: &str, pattern: &str) -> bool {
+    Karg::from(karg).matches(&Karg::from(pattern))
+}
+
+/// Apply the locally managed kernel arguments on top of `kargs`. `previous` is
+/// the local state of the merge deployment; arguments which were previously
+/// added locally but are no longer wanted are removed.
+pub(crate) fn apply_local_kargs(
+    kargs: &mut Vec<String>,
+    previous: &LocalKargs,
+    local: &LocalKargs,
+) {
+    let mut cmdline = Cmdline::from_args(kargs.iter().map(|s| s.as_str()));
+    for prev in previous.append.iter() {
+        if !local.append.contains(prev) {
+            cmdline.remove(&Karg::from(prev.as_str()));
+        }
+    }
+    for pattern in local.delete.iter() {
+        cmdline.remove(&Karg::from(pattern.as_str()));
+    }
+    for karg in local.append.iter() {
+        cmdline.add(Karg::from(karg.as_str()));
+    }
+    *kargs = cmdline.into_args();
+}
+
+/// Update the locally managed kernel arguments for a requested change; `current`
+/// holds the current full set of kernel arguments, and is updated as well.
+///
+/// A replacement is either of the form `KEY=VALUE`, which replaces the single
+/// existing value of `KEY`, or `KEY=OLD=NEW`, which replaces a specific value.
+pub(crate) fn edit_local_kargs(
+    current: &mut Vec<String>,
+    local: &mut LocalKargs,
+    append: &[String],
+    delete: &[String],
+    replace: &[String],
+) -> Result<()> {
+    fn delete_karg(current: &mut Vec<String>, local: &mut LocalKargs, pattern: &str) -> Result<()> {
+        if !current.iter().any(|k| karg_matches(k, pattern)) {
+            anyhow::bail!("No kernel argument matching {pattern}");
+        }
+        // If the argument isn't solely from a local addition, we need to remember to delete it
+        let from_elsewhere = current
+            .iter()
+            .filter(|k| karg_matches(k, pattern))
+            .any(|k| !local.append.contains(k));
+        current.retain(|k| !karg_matches(k, pattern));
+        local.append.retain(|k| !karg_matches(k, pattern));
+        if from_elsewhere && !local.delete.iter().any(|d| d == pattern) {
+            local.delete.push(pattern.to_owned());
+        }
+        Ok(())
+    }
+
+    fn append_karg(current: &mut Vec<String>, local: &mut LocalKargs, karg: &str) {
+        local.delete.retain(|d| d != karg);
+        if !local.append.iter().any(|k| k == karg) {
+            local.append.push(karg.to_owned());
+        }
+        if !current.iter().any(|k| k == karg) {
+            current.push(karg.to_owned());
+        }
+    }
+
+    for pattern in delete {
+        delete_karg(current, local, pattern)?;
+    }
+    for r in replace {
+        let (key, value) = r
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("Invalid replacement {r}, expected KEY=VALUE"))?;
+        let old_new = value
+            .split_once('=')
+            .map(|(old, new)| (format!("{key}={old}"), new))
+            .filter(|(old, _)| current.contains(old));
+        let (old, new) = if let Some((old, new)) = old_new {
+            (old, new)
+        } else {
+            let mut existing = current.iter().filter(|k| karg_matches(k, key));
+            let (Some(old), None) = (existing.next(), existing.next()) else {
+                anyhow::bail!("Expected exactly one instance of kernel argument {key} to replace");
+            };
+            (old.clone(), value)
+        };
+        delete_karg(current, local, &old)?;
+        append_karg(current, local, &format!("{key}={new}"));
+    }
+    for karg in append {
+        append_karg(current, local, karg);
+    }
+    Ok(())
 }
 
 /// This parses a bootc kargs.d toml file, returning the resulting
-/// vector of kernel arguments. Architecture matching is performed using
-/// `sys_arch`.
-fn parse_kargs_toml(contents: &str, sys_arch: &str) -> Result<Vec<String>> {
-    let de: Config = toml::from_str(contents)?;
-    // if arch specified, apply kargs only if the arch matches
-    // if arch not specified, apply kargs unconditionally
-    let matched = de
-        .match_architectures
-        .map(|arches| arches.iter().any(|s| s == sys_arch))
-        .unwrap_or(true);
-    let r = if matched { de.kargs } else { Vec::new() };
+/// vector of kernel arguments. The `match-*` keys are evaluated
+/// against `facts`.
+fn parse_kargs_toml(contents: &str, facts: &HostFacts) -> Result<Vec<String>> {
+    let f = parse_kargs_file("", contents, facts)?;
+    let r = if f.matched { f.kargs } else { Vec::new() };
     Ok(r)
 }
 
+/// Parse a bootc kargs.d toml file, and evaluate whether it applies to the host.
+fn parse_kargs_file(name: &str, contents: &str, facts: &HostFacts) -> Result<KargsFile> {
+    let de: Config = toml::from_str(contents)?;
+    let Some(kargs) = de.kargs.as_ref() else {
+        anyhow::bail!("Missing kargs");
+    };
+    if !de.remove.is_empty() {
+        anyhow::bail!("remove is only supported in /{HOST_KARGS_PATH}");
+    }
+    let Match { matched, reason } = de.evaluate(facts)?;
+    Ok(KargsFile {
+        name: name.to_owned(),
+        kargs: kargs.clone(),
+        matched,
+        reason,
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use cap_std_ext::cap_std;
@@ -200,10 +754,10 @@ mod tests {
         // no arch specified, kargs ensure that kargs are applied unconditionally
         let sys_arch = "x86_64";
         let file_content = r##"kargs = ["console=tty0", "nosmt"]"##.to_string();
-        let parsed_kargs = parse_kargs_toml(&file_content, sys_arch).unwrap();
+        let parsed_kargs = parse_kargs_toml(&file_content, &HostFacts::for_arch(sys_arch)).unwrap();
         assert_eq!(parsed_kargs, ["console=tty0", "nosmt"]);
         let sys_arch = "aarch64";
-        let parsed_kargs = parse_kargs_toml(&file_content, sys_arch).unwrap();
+        let parsed_kargs = parse_kargs_toml(&file_content, &HostFacts::for_arch(sys_arch)).unwrap();
         assert_eq!(parsed_kargs, ["console=tty0", "nosmt"]);
 
         // one arch matches and one doesn't, ensure that kargs are only applied for the matching arch
@@ -212,13 +766,13 @@ mod tests {
 match-architectures = ["x86_64"]
 "##
         .to_string();
-        let parsed_kargs = parse_kargs_toml(&file_content, sys_arch).unwrap();
+        let parsed_kargs = parse_kargs_toml(&file_content, &HostFacts::for_arch(sys_arch)).unwrap();
         assert_eq!(parsed_kargs, [] as [String; 0]);
         let file_content = r##"kargs = ["console=tty0", "nosmt"]
 match-architectures = ["aarch64"]
 "##
         .to_string();
-        let parsed_kargs = parse_kargs_toml(&file_content, sys_arch).unwrap();
+        let parsed_kargs = parse_kargs_toml(&file_content, &HostFacts::for_arch(sys_arch)).unwrap();
         assert_eq!(parsed_kargs, ["console=tty0", "nosmt"]);
 
         // multiple arch specified, ensure that kargs are applied to both archs
@@ -227,21 +781,363 @@ match-architectures = ["aarch64"]
 match-architectures = ["x86_64", "aarch64"]
 "##
         .to_string();
-        let parsed_kargs = parse_kargs_toml(&file_content, sys_arch).unwrap();
+        let parsed_kargs = parse_kargs_toml(&file_content, &HostFacts::for_arch(sys_arch)).unwrap();
         assert_eq!(parsed_kargs, ["console=tty0", "nosmt"]);
         let sys_arch = "aarch64";
-        let parsed_kargs = parse_kargs_toml(&file_content, sys_arch).unwrap();
+        let parsed_kargs = parse_kargs_toml(&file_content, &HostFacts::for_arch(sys_arch)).unwrap();
         assert_eq!(parsed_kargs, ["console=tty0", "nosmt"]);
     }
 
+    #[test]
+    fn test_match_predicates() {
+        let facts = HostFacts {
+            arch: "x86_64".into(),
+            live: false,
+            hardware: OnceCell::from(Some(HardwareFacts {
+                sys_vendor: Some("Dell Inc.".into()),
+                product_name: Some("PowerEdge R750".into()),
+                pci_devices: vec!["10de:2204".into(), "8086:1572".into()],
+                virtualized: false,
+            })),
+        };
+        let eval = |contents: &str| {
+            let f = parse_kargs_file("test.toml", contents, &facts).unwrap();
+            (f.matched, f.reason)
+        };
+        let kargs = r#"kargs = ["nomodeset"]"#;
+        assert_eq!(eval(kargs), (true, "unconditional".into()));
+        assert_eq!(
+            eval(&format!(
+                "{kargs}\nmatch-dmi-sys-vendor = [\"Lenovo\", \"Dell Inc.\"]\nmatch-pci-devices = [\"10DE:*\"]"
+            )),
+            (true, "DMI sys_vendor Dell Inc., PCI device 10de:2204".into())
+        );
+        assert_eq!(
+            eval(&format!(
+                "{kargs}\nmatch-dmi-product-name = [\"ThinkPad\"]\nmatch-virtualized = false"
+            )),
+            (
+                false,
+                "DMI product_name PowerEdge R750 is not one of: ThinkPad".into()
+            )
+        );
+        assert_eq!(
+            eval(&format!(
+                "{kargs}\nmatch-pci-devices = [\"10de:1111\", \"1002\"]"
+            )),
+            (false, "no PCI device matching: 10de:1111, 1002".into())
+        );
+        assert_eq!(
+            eval(&format!("{kargs}\nmatch-virtualized = true")),
+            (false, "system is bare metal".into())
+        );
+        assert_eq!(
+            eval(&format!(
+                "{kargs}\nmatch-architectures = [\"x86_64\"]\nmatch-virtualized = false"
+            )),
+            (true, "architecture x86_64, system is bare metal".into())
+        );
+        // Without hardware facts (e.g. at install time), hardware predicates never match
+        for pred in [
+            "match-dmi-sys-vendor = [\"Dell Inc.\"]",
+            "match-virtualized = false",
+        ] {
+            let f = parse_kargs_file(
+                "test.toml",
+                &format!("{kargs}\n{pred}"),
+                &HostFacts::for_arch("x86_64"),
+            )
+            .unwrap();
+            assert!(!f.matched);
+            assert_eq!(f.reason, "hardware is only matched on the installed host");
+        }
+        // But the architecture is
+        let f = parse_kargs_file(
+            "test.toml",
+            &format!("{kargs}\nmatch-architectures = [\"x86_64\"]"),
+            &HostFacts::for_arch("x86_64"),
+        )
+        .unwrap();
+        assert_eq!(
+            (f.matched, f.reason.as_str()),
+            (true, "architecture x86_64")
+        );
+        // Unknown keys are still an error
+        assert!(parse_kargs_file("test.toml", "kargs = []\nmatch-foo = 1", &facts).is_err());
+    }
+
+    #[test]
+    fn test_host_facts_from_root() -> Result<()> {
+        let td = cap_std_ext::cap_tempfile::TempDir::new(cap_std::ambient_authority())?;
+        let facts = HardwareFacts::from_root(&td, true)?;
+        assert_eq!(facts.sys_vendor, None);
+        assert!(facts.pci_devices.is_empty());
+
+        td.create_dir_all(DMI_PATH)?;
+        td.write(format!("{DMI_PATH}/sys_vendor"), "QEMU\n")?;
+        for (dev, vendor, device) in [
+            ("0000:00:02.0", "0x1AF4", "0x1050"),
+            ("0000:00:01.0", "0x8086", "0x1237"),
+        ] {
+            let path = format!("{PCI_DEVICES_PATH}/{dev}");
+            td.create_dir_all(&path)?;
+            td.write(format!("{path}/vendor"), format!("{vendor}\n"))?;
+            td.write(format!("{path}/device"), format!("{device}\n"))?;
+        }
+        let facts = HardwareFacts::from_root(&td, true)?;
+        assert_eq!(facts.sys_vendor.as_deref(), Some("QEMU"));
+        assert_eq!(facts.product_name, None);
+        assert_eq!(facts.pci_devices, ["1af4:1050", "8086:1237"]);
+        assert!(facts.virtualized);
+        Ok(())
+    }
+
+    #[test]
+    fn test_get_kargs_files_in_root() -> Result<()> {
+        let td = cap_std_ext::cap_tempfile::TempDir::new(cap_std::ambient_authority())?;
+        td.create_dir_all(KARGS_PATH)?;
+        td.write(
+            format!("{KARGS_PATH}/10-vm.toml"),
+            "kargs = [\"console=ttyS0\"]\nmatch-virtualized = true",
+        )?;
+        td.write(format!("{KARGS_PATH}/20-all.toml"), r#"kargs = ["quiet"]"#)?;
+        let facts = &HostFacts::for_arch("x86_64");
+        let files = get_kargs_files_in_root(&td, facts)?;
+        let files = files
+            .iter()
+            .map(|f| (f.name.as_str(), f.matched, f.reason.as_str()))
+            .collect::<Vec<_>>();
+        similar_asserts::assert_eq!(
+            files,
+            [
+                (
+                    "10-vm.toml",
+                    false,
+                    "hardware is only matched on the installed host"
+                ),
+                ("20-all.toml", true, "unconditional")
+            ]
+        );
+        similar_asserts::assert_eq!(get_kargs_in_root(&td, facts)?, ["quiet"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_host_kargs() -> Result<()> {
+        let td = cap_std_ext::cap_tempfile::TempDir::new(cap_std::ambient_authority())?;
+        let facts = &HostFacts::for_arch("x86_64");
+        assert_eq!(get_host_kargs_in_root(&td, facts)?, HostKargs::default());
+
+        td.create_dir_all(HOST_KARGS_PATH)?;
+        td.write(
+            format!("{HOST_KARGS_PATH}/10-gpu.toml"),
+            "remove = [\"nomodeset\"]\nkargs = [\"console=ttyS1\"]",
+        )?;
+        td.write(
+            format!("{HOST_KARGS_PATH}/20-other.toml"),
+            "remove = [\"quiet\"]\nmatch-architectures = [\"aarch64\"]",
+        )?;
+        let host = get_host_kargs_in_root(&td, facts)?;
+        assert_eq!(
+            host,
+            HostKargs {
+                append: vec!["console=ttyS1".into()],
+                remove: vec!["nomodeset".into()],
+            }
+        );
+
+        // Adding the host configuration removes the image karg
+        let image = Cmdline::parse("nomodeset quiet");
+        let mut kargs = Cmdline::parse("root=UUID=1234 nomodeset quiet");
+        let mut remote = image.clone();
+        host.apply(&mut remote);
+        merge_kargs(&mut kargs, &image, &remote);
+        assert_eq!(kargs.to_string(), "root=UUID=1234 quiet console=ttyS1");
+
+        // And it persists across an image update, using the state recorded in the origin
+        let origin = glib::KeyFile::new();
+        set_origin_host_kargs(&origin, &host);
+        let previous = host_kargs_from_origin(&origin)?;
+        assert_eq!(previous, host);
+        let mut existing = image;
+        previous.apply(&mut existing);
+        let mut remote = Cmdline::parse("nomodeset quiet mitigations=auto");
+        host.apply(&mut remote);
+        merge_kargs(&mut kargs, &existing, &remote);
+        assert_eq!(
+            kargs.to_string(),
+            "root=UUID=1234 quiet console=ttyS1 mitigations=auto"
+        );
+
+        // Removing the host configuration restores the image kargs
+        let mut remote = Cmdline::parse("nomodeset quiet mitigations=auto");
+        HostKargs::default().apply(&mut remote);
+        let mut existing = Cmdline::parse("nomodeset quiet mitigations=auto");
+        previous.apply(&mut existing);
+        merge_kargs(&mut kargs, &existing, &remote);
+        assert_eq!(
+            kargs.to_string(),
+            "root=UUID=1234 quiet mitigations=auto nomodeset"
+        );
+
+        set_origin_host_kargs(&origin, &Default::default());
+        assert_eq!(host_kargs_from_origin(&origin)?, HostKargs::default());
+
+        // remove isn't supported in the image
+        let facts = &HostFacts::for_arch("x86_64");
+        assert!(parse_kargs_toml("kargs = []\nremove = [\"quiet\"]", facts).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_merge_kargs() {
+        let merge = |kargs: &str, existing: &str, remote: &str| {
+            let mut kargs = Cmdline::parse(kargs);
+            merge_kargs(
+                &mut kargs,
+                &Cmdline::parse(existing),
+                &Cmdline::parse(remote),
+            );
+            kargs.to_string()
+        };
+        // A changed value replaces the previous one rather than being duplicated
+        assert_eq!(
+            merge(
+                "root=UUID=1234 mitigations=off quiet",
+                "mitigations=off",
+                "mitigations=auto"
+            ),
+            "root=UUID=1234 mitigations=auto quiet"
+        );
+        // Repeatable keys are handled individually
+        assert_eq!(
+            merge(
+                "console=tty0 console=ttyS0 quiet",
+                "console=tty0 console=ttyS0",
+                "console=ttyS1 console=tty0"
+            ),
+            "console=tty0 quiet console=ttyS1"
+        );
+        // Duplicated flags are not added again
+        assert_eq!(merge("quiet rhgb", "", "quiet"), "quiet rhgb");
+        // Unchanged kargs.d entries which were removed locally stay removed
+        assert_eq!(merge("root=UUID=1234", "nosmt", "nosmt"), "root=UUID=1234");
+        // Init arguments are preserved
+        assert_eq!(
+            merge("quiet -- single", "quiet", "splash"),
+            "splash -- single"
+        );
+    }
+
+    #[test]
+    fn test_apply_local_kargs() {
+        let local = LocalKargs {
+            append: vec!["foo=bar".into(), "quiet".into()],
+            delete: vec!["console".into()],
+        };
+        let mut kargs = ["root=UUID=1234", "console=tty0", "console=ttyS0", "quiet"]
+            .map(String::from)
+            .to_vec();
+        apply_local_kargs(&mut kargs, &Default::default(), &local);
+        similar_asserts::assert_eq!(kargs, ["root=UUID=1234", "quiet", "foo=bar"]);
+
+        // Dropping a previously appended karg removes it
+        let previous = local;
+        let local = LocalKargs {
+            append: vec!["quiet".into()],
+            delete: vec![],
+        };
+        apply_local_kargs(&mut kargs, &previous, &local);
+        similar_asserts::assert_eq!(kargs, ["root=UUID=1234", "quiet"]);
+    }
+
+    #[test]
+    fn test_edit_local_kargs() {
+        let mut current = ["root=UUID=1234", "console=tty0", "nosmt", "foo=1"]
+            .map(String::from)
+            .to_vec();
+        let mut local = LocalKargs {
+            append: vec!["foo=1".into()],
+            delete: vec![],
+        };
+        let v = |s: &[&str]| s.iter().map(|&s| String::from(s)).collect::<Vec<_>>();
+
+        edit_local_kargs(
+            &mut current,
+            &mut local,
+            &v(&["quiet"]),
+            &v(&["nosmt", "foo"]),
+            &v(&["console=ttyS0"]),
+        )
+        .unwrap();
+        similar_asserts::assert_eq!(current, ["root=UUID=1234", "console=ttyS0", "quiet"]);
+        similar_asserts::assert_eq!(local.append, ["console=ttyS0", "quiet"]);
+        // foo=1 was only added locally, so there's nothing to remember for it
+        similar_asserts::assert_eq!(local.delete, ["nosmt", "console=tty0"]);
+
+        // KEY=OLD=NEW form
+        edit_local_kargs(
+            &mut current,
+            &mut local,
+            &[],
+            &[],
+            &v(&["console=ttyS0=ttyS1"]),
+        )
+        .unwrap();
+        similar_asserts::assert_eq!(current, ["root=UUID=1234", "quiet", "console=ttyS1"]);
+        similar_asserts::assert_eq!(local.append, ["quiet", "console=ttyS1"]);
+
+        // Values can contain `=`
+        edit_local_kargs(&mut current, &mut local, &[], &[], &v(&["root=UUID=5678"])).unwrap();
+        assert!(current.iter().any(|k| k == "root=UUID=5678"));
+        assert!(local.delete.iter().any(|k| k == "root=UUID=1234"));
+
+        // Error cases
+        assert!(edit_local_kargs(&mut current, &mut local, &[], &v(&["nosuchkarg"]), &[]).is_err());
+        assert!(
+            edit_local_kargs(&mut current, &mut local, &[], &[], &v(&["nosuchkarg=1"])).is_err()
+        );
+        assert!(edit_local_kargs(&mut current, &mut local, &[], &[], &v(&["quiet"])).is_err());
+    }
+
+    #[test]
+    fn test_origin_image_kargs() -> Result<()> {
+        let origin = glib::KeyFile::new();
+        assert_eq!(image_kargs_from_origin(&origin)?, None);
+        // An empty list is recorded too
+        set_origin_image_kargs(&origin, &Cmdline::parse(""));
+        assert_eq!(image_kargs_from_origin(&origin)?, Some(vec![]));
+        set_origin_image_kargs(&origin, &Cmdline::parse("nomodeset console=ttyS0"));
+        assert_eq!(
+            image_kargs_from_origin(&origin)?.unwrap(),
+            ["nomodeset", "console=ttyS0"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_origin_local_kargs() -> Result<()> {
+        let origin = glib::KeyFile::new();
+        assert_eq!(local_kargs_from_origin(&origin)?, LocalKargs::default());
+        let local = LocalKargs {
+            append: vec!["console=ttyS0".into()],
+            delete: vec!["quiet".into()],
+        };
+        set_origin_local_kargs(&origin, &local);
+        assert_eq!(local_kargs_from_origin(&origin)?, local);
+        set_origin_local_kargs(&origin, &Default::default());
+        assert_eq!(local_kargs_from_origin(&origin)?, LocalKargs::default());
+        Ok(())
+    }
+
     #[test]
     /// Verify some error cases
     fn test_invalid() {
         let test_invalid_extra = r#"kargs = ["console=tty0", "nosmt"]\nfoo=bar"#;
-        assert!(parse_kargs_toml(test_invalid_extra, "x86_64").is_err());
+        assert!(parse_kargs_toml(test_invalid_extra, &HostFacts::for_arch("x86_64")).is_err());
 
         let test_missing = r#"foo=bar"#;
-        assert!(parse_kargs_toml(test_missing, "x86_64").is_err());
+        assert!(parse_kargs_toml(test_missing, &HostFacts::for_arch("x86_64")).is_err());
     }
 
     #[context("writing test kargs")]
@@ -263,17 +1159,32 @@ match-architectures = ["x86_64", "aarch64"]
         let td = cap_std_ext::cap_tempfile::TempDir::new(cap_std::ambient_authority())?;
 
         // No directory
-        assert_eq!(get_kargs_in_root(&td, "x86_64").unwrap().len(), 0);
+        assert_eq!(
+            get_kargs_in_root(&td, &HostFacts::for_arch("x86_64"))
+                .unwrap()
+                .len(),
+            0
+        );
         // Empty directory
         td.create_dir_all("usr/lib/bootc/kargs.d")?;
-        assert_eq!(get_kargs_in_root(&td, "x86_64").unwrap().len(), 0);
+        assert_eq!(
+            get_kargs_in_root(&td, &HostFacts::for_arch("x86_64"))
+                .unwrap()
+                .len(),
+            0
+        );
         // Non-toml file
         td.write("usr/lib/bootc/kargs.d/somegarbage", "garbage")?;
-        assert_eq!(get_kargs_in_root(&td, "x86_64").unwrap().len(), 0);
+        assert_eq!(
+            get_kargs_in_root(&td, &HostFacts::for_arch("x86_64"))
+                .unwrap()
+                .len(),
+            0
+        );
 
         write_test_kargs(&td)?;
 
-        let args = get_kargs_in_root(&td, "x86_64").unwrap();
+        let args = get_kargs_in_root(&td, &HostFacts::for_arch("x86_64")).unwrap();
         similar_asserts::assert_eq!(args, ["console=tty0", "nosmt", "console=ttyS1"]);
 
         Ok(())
@@ -342,7 +1253,7 @@ match-architectures = ["x86_64", "aarch64"]
             if !fetched_tree.query_exists(cancellable) {
                 return Ok(Default::default());
             }
-            get_kargs_from_ostree(repo, &fetched_tree, sys_arch)
+            get_kargs_from_ostree(repo, &fetched_tree, &HostFacts::for_arch(sys_arch))
         };
 
         // rootfs is empty
@@ -1,3 +1,5 @@
+use std::fmt::Display;
+
 use anyhow::Result;
 use fn_error_context::context;
 
@@ -5,22 +7,239 @@ use fn_error_context::context;
 pub(crate) const INITRD_ARG_PREFIX: &str = "rd.";
 /// The kernel argument for configuring the rootfs flags.
 pub(crate) const ROOTFLAGS: &str = "rootflags=";
+/// Everything after this on the command line is passed to init.
+const INIT_SEPARATOR: &str = "--";
 
-/// Parse the kernel command line.  This is strictly
-/// speaking not a correct parser, as the Linux kernel
-/// supports quotes.  However, we don't yet need that here.
-///
-/// See systemd's code for one userspace parser.
+/// Kernel arguments which are commonly specified multiple times with
+/// different values. For all other keys, only one value is kept.
+const REPEATABLE_KARGS: &[&str] = &[
+    "bond",
+    "bridge",
+    "console",
+    "hugepages",
+    "hugepagesz",
+    "ip",
+    "memmap",
+    "modprobe.blacklist",
+    "module_blacklist",
+    "nameserver",
+    "rd.dasd",
+    "rd.driver.blacklist",
+    "rd.driver.pre",
+    "rd.luks.key",
+    "rd.luks.name",
+    "rd.luks.options",
+    "rd.luks.uuid",
+    "rd.lvm.lv",
+    "rd.md.uuid",
+    "rd.systemd.mask",
+    "rd.systemd.wants",
+    "rd.zfcp",
+    "rd.znet",
+    "systemd.mask",
+    "systemd.mount-extra",
+    "systemd.swap-extra",
+    "systemd.wants",
+    "vlan",
+];
+
+/// Parse the kernel command line, honoring double quotes.
 #[context("Reading /proc/cmdline")]
 pub(crate) fn parse_cmdline() -> Result<Vec<String>> {
     let cmdline = std::fs::read_to_string("/proc/cmdline")?;
-    let r = cmdline
-        .split_ascii_whitespace()
-        .map(ToOwned::to_owned)
-        .collect();
+    let r = split_args(&cmdline).map(ToOwned::to_owned).collect();
     Ok(r)
 }
 
+/// Split a kernel command line into arguments; whitespace inside
+/// double quotes does not separate arguments.
+fn split_args(s: &str) -> impl Iterator<Item = &str> {
+    let mut quoted = false;
+    s.split(move |c: char| {
+        if c == '"' {
+            quoted = !quoted;
+        }
+        !quoted && c.is_ascii_whitespace()
+    })
+    .filter(|s| !s.is_empty())
+}
+
+/// Remove surrounding double quotes, if any.
+fn unquote(s: &str) -> &str {
+    s.strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(s)
+}
+
+/// Compare two kernel argument keys; like the kernel and systemd,
+/// `-` and `_` are treated as equivalent.
+fn key_eq(a: &str, b: &str) -> bool {
+    let norm = |c: u8| if c == b'-' { b'_' } else { c };
+    a.len() == b.len() && a.bytes().zip(b.bytes()).all(|(a, b)| norm(a) == norm(b))
+}
+
+/// A single kernel argument, such as `quiet` or `console=ttyS0`.
+#[derive(Debug, Clone)]
+pub(crate) struct Karg(String);
+
+impl Karg {
+    /// Split into the key and the (unquoted) value.
+    fn split(&self) -> (&str, Option<&str>) {
+        let s = unquote(&self.0);
+        match s.split_once('=') {
+            Some((k, v)) => (k, Some(unquote(v))),
+            None => (s, None),
+        }
+    }
+
+    /// The key of this argument.
+    pub(crate) fn key(&self) -> &str {
+        self.split().0
+    }
+
+    /// The value of this argument, if any.
+    pub(crate) fn value(&self) -> Option<&str> {
+        self.split().1
+    }
+
+    /// Whether this argument may be specified multiple times with different values.
+    fn is_repeatable(&self) -> bool {
+        let key = self.key();
+        REPEATABLE_KARGS.iter().any(|k| key_eq(k, key))
+    }
+
+    /// Returns true if this argument matches `pattern`; a pattern without a
+    /// value (e.g. `console`) matches all instances of that key.
+    pub(crate) fn matches(&self, pattern: &Karg) -> bool {
+        match pattern.value() {
+            Some(_) => self == pattern,
+            None => key_eq(self.key(), pattern.key()),
+        }
+    }
+}
+
+impl PartialEq for Karg {
+    fn eq(&self, other: &Self) -> bool {
+        key_eq(self.key(), other.key()) && self.value() == other.value()
+    }
+}
+
+impl Eq for Karg {}
+
+impl From<&str> for Karg {
+    fn from(value: &str) -> Self {
+        Self(value.to_owned())
+    }
+}
+
+impl Display for Karg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// A parsed kernel command line.
+///
+/// Arguments which are flags (e.g. `quiet`) or keys listed in [`REPEATABLE_KARGS`]
+/// may appear multiple times with distinct values; for all other keys, adding
+/// a new value replaces the existing one.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Cmdline {
+    args: Vec<Karg>,
+    /// Arguments after `--`, which are passed to init unchanged.
+    init_args: Vec<String>,
+}
+
+impl Cmdline {
+    /// Parse a kernel command line.
+    pub(crate) fn parse(s: &str) -> Self {
+        Self::from_args(split_args(s))
+    }
+
+    /// Build a command line from a sequence of arguments.
+    pub(crate) fn from_args<'a>(args: impl IntoIterator<Item = &'a str>) -> Self {
+        let mut r = Self::default();
+        let mut args = args.into_iter().flat_map(split_args);
+        for arg in args.by_ref() {
+            if arg == INIT_SEPARATOR {
+                break;
+            }
+            r.args.push(arg.into());
+        }
+        r.init_args = args.map(ToOwned::to_owned).collect();
+        r
+    }
+
+    /// The kernel arguments (excluding those passed to init).
+    pub(crate) fn args(&self) -> &[Karg] {
+        &self.args
+    }
+
+    /// Returns true if this exact argument is present.
+    pub(crate) fn contains(&self, arg: &Karg) -> bool {
+        self.args.contains(arg)
+    }
+
+    /// Remove all arguments matching `pattern` (see [`Karg::matches`]), returning
+    /// true if any were found.
+    pub(crate) fn remove(&mut self, pattern: &Karg) -> bool {
+        let n = self.args.len();
+        self.args.retain(|a| !a.matches(pattern));
+        self.args.len() != n
+    }
+
+    /// Add an argument. If it is already present this does nothing; if the key is
+    /// not repeatable, the first existing instance is replaced and any others removed.
+    pub(crate) fn add(&mut self, arg: Karg) {
+        if self.contains(&arg) {
+            return;
+        }
+        if arg.value().is_none() || arg.is_repeatable() {
+            self.args.push(arg);
+            return;
+        }
+        let key = arg.key().to_owned();
+        let Some(first) = self.args.iter().position(|a| key_eq(a.key(), &key)) else {
+            self.args.push(arg);
+            return;
+        };
+        self.args[first] = arg;
+        let mut i = 0;
+        self.args.retain(|a| {
+            let keep = i <= first || !key_eq(a.key(), &key);
+            i += 1;
+            keep
+        });
+    }
+
+    /// Convert into a list of arguments, including any init arguments.
+    pub(crate) fn into_args(self) -> Vec<String> {
+        let mut r: Vec<String> = self.args.into_iter().map(|a| a.0).collect();
+        if !self.init_args.is_empty() {
+            r.push(INIT_SEPARATOR.to_owned());
+            r.extend(self.init_args);
+        }
+        r
+    }
+}
+
+impl Display for Cmdline {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let args = self.args.iter().map(|a| a.0.as_str());
+        let init = (!self.init_args.is_empty())
+            .then_some(INIT_SEPARATOR)
+            .into_iter()
+            .chain(self.init_args.iter().map(|s| s.as_str()));
+        for (i, arg) in args.chain(init).enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            f.write_str(arg)?;
+        }
+        Ok(())
+    }
+}
+
 /// Return the value for the string in the vector which has the form target_key=value
 pub(crate) fn find_first_cmdline_arg<'a>(
     args: impl Iterator<Item = &'a str>,
@@ -48,4 +267,73 @@ mod tests {
         assert_eq!(find_first_cmdline_arg(kargs(), "root"), Some("/dev/vda"));
         assert_eq!(find_first_cmdline_arg(kargs(), "nonexistent"), None);
     }
+
+    #[test]
+    fn test_cmdline_parse() {
+        let cmdline = Cmdline::parse(
+            r#"root=UUID=1234 quiet dyndbg="file foo.c +p" "acpi=off nosmt" -- single  "a b""#,
+        );
+        let args = cmdline
+            .args()
+            .iter()
+            .map(|a| (a.key(), a.value()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            args,
+            [
+                ("root", Some("UUID=1234")),
+                ("quiet", None),
+                ("dyndbg", Some("file foo.c +p")),
+                ("acpi", Some("off nosmt")),
+            ]
+        );
+        assert_eq!(
+            cmdline.to_string(),
+            r#"root=UUID=1234 quiet dyndbg="file foo.c +p" "acpi=off nosmt" -- single "a b""#
+        );
+        assert_eq!(cmdline.clone().into_args().len(), 7);
+        assert_eq!(Cmdline::parse("  "), Cmdline::default());
+    }
+
+    #[test]
+    fn test_karg_matches() {
+        let k = Karg::from("rd.lvm-foo=\"x\"");
+        assert!(k.matches(&"rd.lvm_foo".into()));
+        assert!(k.matches(&"rd.lvm_foo=x".into()));
+        assert!(!k.matches(&"rd.lvm_foo=y".into()));
+        assert!(!k.matches(&"rd.lvm".into()));
+    }
+
+    #[test]
+    fn test_cmdline_add_remove() {
+        let mut cmdline = Cmdline::parse("quiet console=tty0 mitigations=off foo mitigations=auto");
+        // Flags and exact duplicates are not added twice
+        cmdline.add("quiet".into());
+        cmdline.add("console=tty0".into());
+        assert_eq!(
+            cmdline.to_string(),
+            "quiet console=tty0 mitigations=off foo mitigations=auto"
+        );
+        // Repeatable keys are appended
+        cmdline.add("console=ttyS0".into());
+        // Other keys are replaced in place
+        cmdline.add("mitigations=auto,nosmt".into());
+        cmdline.add("bar=1".into());
+        assert_eq!(
+            cmdline.to_string(),
+            "quiet console=tty0 mitigations=auto,nosmt foo console=ttyS0 bar=1"
+        );
+        assert!(cmdline.remove(&"console".into()));
+        assert!(!cmdline.remove(&"bar=2".into()));
+        assert!(cmdline.remove(&"bar=1".into()));
+        assert_eq!(cmdline.to_string(), "quiet mitigations=auto,nosmt foo");
+
+        // s390x storage devices are all kept
+        let mut cmdline = Cmdline::parse("rd.zfcp=0.0.fc00,0x5005076300c213e9,0x5022000000000000");
+        cmdline.add("rd.zfcp=0.0.fc00,0x5005076300c213e9,0x5022000000000001".into());
+        assert_eq!(
+            cmdline.to_string(),
+            "rd.zfcp=0.0.fc00,0x5005076300c213e9,0x5022000000000000 rd.zfcp=0.0.fc00,0x5005076300c213e9,0x5022000000000001"
+        );
+    }
 }
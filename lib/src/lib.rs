@@ -7,8 +7,12 @@
 mod boundimage;
 pub mod cli;
 pub(crate) mod deploy;
+mod etcdiff;
+mod factory_reset;
+mod facts;
 pub(crate) mod generator;
 mod glyph;
+mod history;
 mod image;
 mod imgstorage;
 pub(crate) mod journal;
@@ -17,6 +21,7 @@ pub(crate) mod kargs;
 mod lints;
 mod lsm;
 pub(crate) mod metadata;
+mod metrics;
 mod podman;
 mod progress_jsonl;
 mod reboot;
@@ -26,6 +31,7 @@ mod status;
 mod store;
 mod task;
 mod utils;
+mod varlink;
 
 #[cfg(feature = "docgen")]
 mod docgen;
@@ -5,34 +5,170 @@
 // Unfortunately needed here to work with linkme
 #![allow(unsafe_code)]
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::env::consts::ARCH;
 use std::fmt::Write as WriteFmt;
 use std::os::unix::ffi::OsStrExt;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use bootc_utils::PathQuotedDisplay;
-use camino::{Utf8Path, Utf8PathBuf};
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
 use cap_std::fs::Dir;
 use cap_std_ext::cap_std;
 use cap_std_ext::cap_std::fs::MetadataExt;
 use cap_std_ext::dirext::CapStdExtDirExt as _;
+use cap_std_ext::RootDir;
 use fn_error_context::context;
 use indoc::indoc;
 use linkme::distributed_slice;
+use ostree_ext::containers_image_proxy;
 use ostree_ext::ostree_prepareroot;
 use serde::Serialize;
 
 /// Reference to embedded default baseimage content that should exist.
 const BASEIMAGE_REF: &str = "usr/share/doc/bootc/baseimage/base";
+/// The kernel binary, in /usr/lib/modules/$kver.
+const VMLINUZ: &str = "vmlinuz";
+/// The initramfs, alongside the kernel.
+const INITRAMFS: &str = "initramfs.img";
+/// Generated by depmod, alongside the kernel.
+const MODULES_DEP: &str = "modules.dep";
+/// Filename suffixes for (possibly compressed) kernel modules.
+const KMOD_SUFFIXES: &[&str] = &[".ko", ".ko.xz", ".ko.zst", ".ko.gz"];
+/// Changes to dracut configuration affect the generated initramfs.
+const DRACUT_CONFDIRS: &[&str] = &["usr/lib/dracut/dracut.conf.d", "etc/dracut.conf.d"];
+/// Directories (relative to both /etc and /usr/lib) where a file in /etc entirely
+/// replaces a vendor default of the same name.
+const ETC_VENDOR_OVERRIDE_DIRS: &[&str] = &[
+    "systemd/system",
+    "systemd/user",
+    "tmpfiles.d",
+    "sysusers.d",
+    "sysctl.d",
+    "modules-load.d",
+    "udev/rules.d",
+];
+/// Directories in /etc where systemd units are enabled via symbolic links.
+const ETC_SYSTEMD_UNITDIRS: &[&str] = &["etc/systemd/system", "etc/systemd/user"];
+/// Suffixes of the directories in a unit directory holding symbolic links for dependencies.
+const UNIT_DEPS_DIR_SUFFIXES: &[&str] = &[".wants", ".requires", ".upholds"];
+/// API filesystems which are only populated at runtime.
+const RUNTIME_API_FILESYSTEMS: &[&str] = &["/run", "/proc", "/sys"];
+/// Directories containing systemd system units that we validate.
+const SYSTEMD_SYSTEM_UNITDIRS: &[&str] = &["usr/lib/systemd/system", "etc/systemd/system"];
+/// Filename extensions for systemd unit files.
+const SYSTEMD_UNIT_SUFFIXES: &[&str] = &[
+    "service",
+    "socket",
+    "target",
+    "timer",
+    "mount",
+    "automount",
+    "swap",
+    "path",
+    "slice",
+    "scope",
+    "device",
+];
+/// Known sections in unit files; `X-` prefixed sections are also allowed.
+const SYSTEMD_UNIT_SECTIONS: &[&str] = &[
+    "Unit",
+    "Install",
+    "Service",
+    "Socket",
+    "Timer",
+    "Mount",
+    "Automount",
+    "Swap",
+    "Path",
+    "Slice",
+    "Scope",
+];
+/// Known directives in the `[Unit]` section, excluding `Condition*` and `Assert*`.
+const SYSTEMD_UNIT_DIRECTIVES: &[&str] = &[
+    "Description",
+    "Documentation",
+    "Wants",
+    "Requires",
+    "Requisite",
+    "BindsTo",
+    "BindTo",
+    "PartOf",
+    "Upholds",
+    "Conflicts",
+    "Before",
+    "After",
+    "OnFailure",
+    "OnSuccess",
+    "PropagatesReloadTo",
+    "PropagateReloadTo",
+    "ReloadPropagatedFrom",
+    "PropagateReloadFrom",
+    "PropagatesStopTo",
+    "StopPropagatedFrom",
+    "JoinsNamespaceOf",
+    "RequiresMountsFor",
+    "WantsMountsFor",
+    "OnFailureJobMode",
+    "OnSuccessJobMode",
+    "OnFailureIsolate",
+    "IgnoreOnIsolate",
+    "StopWhenUnneeded",
+    "RefuseManualStart",
+    "RefuseManualStop",
+    "AllowIsolate",
+    "DefaultDependencies",
+    "SurviveFinalKillSignal",
+    "CollectMode",
+    "FailureAction",
+    "SuccessAction",
+    "FailureActionExitStatus",
+    "SuccessActionExitStatus",
+    "JobTimeoutSec",
+    "JobRunningTimeoutSec",
+    "JobTimeoutAction",
+    "JobTimeoutRebootArgument",
+    "StartLimitIntervalSec",
+    "StartLimitInterval",
+    "StartLimitBurst",
+    "StartLimitAction",
+    "RebootArgument",
+    "SourcePath",
+];
+/// Known directives in the `[Install]` section.
+const SYSTEMD_INSTALL_DIRECTIVES: &[&str] = &[
+    "Alias",
+    "WantedBy",
+    "RequiredBy",
+    "UpheldBy",
+    "Also",
+    "DefaultInstance",
+];
+/// Directives which specify a command line to execute.
+const SYSTEMD_EXEC_DIRECTIVES: &[&str] = &[
+    "ExecCondition",
+    "ExecStartPre",
+    "ExecStart",
+    "ExecStartPost",
+    "ExecReload",
+    "ExecStop",
+    "ExecStopPre",
+    "ExecStopPost",
+];
+/// The fixed search path systemd uses for `Exec*=` commands which are not absolute.
+const SYSTEMD_EXEC_SEARCH_PATH: &[&str] =
+    &["usr/local/sbin", "usr/local/bin", "usr/sbin", "usr/bin"];
 
 /// A lint check has failed.
-#[derive(thiserror::Error, Debug)]
+#[derive(thiserror::Error, Debug, Clone, PartialEq, Eq)]
 struct LintError(String);
 
 /// The outer error is for unexpected fatal runtime problems; the
 /// inner error is for the lint failing in an expected way.
-type LintResult = Result<std::result::Result<(), LintError>>;
+type LintResult = Result<LintOutcome>;
+
+/// The outcome of a single lint which ran successfully.
+type LintOutcome = std::result::Result<(), LintError>;
 
 /// Everything is OK - we didn't encounter a runtime error, and
 /// the targeted check passed.
@@ -96,6 +232,10 @@ struct Lint {
     // Set if this only applies to a specific root type.
     #[serde(skip_serializing_if = "Option::is_none")]
     root_type: Option<RootType>,
+    // The paths (relative to the root) this lint reads, if it is limited to some;
+    // used to avoid running it again for image layers which don't change them.
+    #[serde(skip)]
+    inputs: Option<&'static [&'static str]>,
 }
 
 impl Lint {
@@ -110,6 +250,7 @@ impl Lint {
             f: f,
             description: description,
             root_type: None,
+            inputs: None,
         }
     }
 
@@ -124,6 +265,7 @@ impl Lint {
             f: f,
             description: description,
             root_type: None,
+            inputs: None,
         }
     }
 
@@ -131,6 +273,11 @@ impl Lint {
         self.root_type = Some(v);
         self
     }
+
+    const fn set_inputs(mut self, v: &'static [&'static str]) -> Self {
+        self.inputs = Some(v);
+        self
+    }
 }
 
 pub(crate) fn lint_list(output: impl std::io::Write) -> Result<()> {
@@ -147,38 +294,55 @@ struct LintExecutionResult {
     fatal: usize,
 }
 
-fn lint_inner<'skip>(
+/// Run each lint applicable to this root type, returning the outcome for each;
+/// skipped lints have no outcome.
+fn run_lints(
     root: &Dir,
     root_type: RootType,
-    skip: impl IntoIterator<Item = &'skip str>,
+    skip: &HashSet<&str>,
+) -> Result<Vec<(&'static Lint, Option<LintOutcome>)>> {
+    let mut r = Vec::new();
+    for lint in LINTS {
+        let name = lint.name;
+
+        let skipped = skip.contains(name)
+            || lint
+                .root_type
+                .is_some_and(|lint_root_type| lint_root_type != root_type);
+        if skipped {
+            r.push((lint, None));
+            continue;
+        }
+
+        r.push((lint, Some(run_lint(lint, root)?)));
+    }
+    Ok(r)
+}
+
+/// Run a single lint.
+fn run_lint(lint: &Lint, root: &Dir) -> Result<LintOutcome> {
+    (lint.f)(root)
+        .map_err(|e| anyhow::anyhow!("Unexpected runtime error running lint {}: {e}", lint.name))
+}
+
+/// Write the failures from a lint run, and tally the results. The `annotate` function
+/// may return additional context to print for a failed lint.
+fn report_lints(
+    outcomes: &[(&'static Lint, Option<LintOutcome>)],
+    annotate: impl Fn(&Lint) -> Option<String>,
     mut output: impl std::io::Write,
 ) -> Result<LintExecutionResult> {
     let mut fatal = 0usize;
     let mut warnings = 0usize;
     let mut passed = 0usize;
     let mut skipped = 0usize;
-    let skip: std::collections::HashSet<_> = skip.into_iter().collect();
-    for lint in LINTS {
+    for (lint, outcome) in outcomes {
         let name = lint.name;
-
-        if skip.contains(name) {
+        let Some(outcome) = outcome else {
             skipped += 1;
             continue;
-        }
-
-        if let Some(lint_root_type) = lint.root_type {
-            if lint_root_type != root_type {
-                skipped += 1;
-                continue;
-            }
-        }
-
-        let r = match (lint.f)(&root) {
-            Ok(r) => r,
-            Err(e) => anyhow::bail!("Unexpected runtime error running lint {name}: {e}"),
         };
-
-        if let Err(e) = r {
+        if let Err(e) = outcome {
             match lint.ty {
                 LintType::Fatal => {
                     writeln!(output, "Failed lint: {name}: {e}")?;
@@ -189,6 +353,9 @@ fn lint_inner<'skip>(
                     warnings += 1;
                 }
             }
+            if let Some(annotation) = annotate(lint) {
+                writeln!(output, "  {annotation}")?;
+            }
         } else {
             // We'll be quiet for now
             tracing::debug!("OK {name} (type={:?})", lint.ty);
@@ -204,18 +371,23 @@ fn lint_inner<'skip>(
     })
 }
 
-/// check for the existence of the /var/run directory
-/// if it exists we need to check that it links to /run if not error
-/// if it does not exist error.
-#[context("Linting")]
-pub(crate) fn lint<'skip>(
+fn lint_inner<'skip>(
     root: &Dir,
-    warning_disposition: WarningDisposition,
     root_type: RootType,
     skip: impl IntoIterator<Item = &'skip str>,
+    output: impl std::io::Write,
+) -> Result<LintExecutionResult> {
+    let skip: HashSet<_> = skip.into_iter().collect();
+    let outcomes = run_lints(root, root_type, &skip)?;
+    report_lints(&outcomes, |_| None, output)
+}
+
+/// Print the summary of a lint run, and return an error if it failed.
+fn finish_lint(
+    r: LintExecutionResult,
+    warning_disposition: WarningDisposition,
     mut output: impl std::io::Write,
 ) -> Result<()> {
-    let r = lint_inner(root, root_type, skip, &mut output)?;
     writeln!(output, "Checks passed: {}", r.passed)?;
     if r.skipped > 0 {
         writeln!(output, "Checks skipped: {}", r.skipped)?;
@@ -234,12 +406,245 @@ pub(crate) fn lint<'skip>(
     Ok(())
 }
 
+/// check for the existence of the /var/run directory
+/// if it exists we need to check that it links to /run if not error
+/// if it does not exist error.
+#[context("Linting")]
+pub(crate) fn lint<'skip>(
+    root: &Dir,
+    warning_disposition: WarningDisposition,
+    root_type: RootType,
+    skip: impl IntoIterator<Item = &'skip str>,
+    mut output: impl std::io::Write,
+) -> Result<()> {
+    let r = lint_inner(root, root_type, skip, &mut output)?;
+    finish_lint(r, warning_disposition, output)
+}
+
+/// The paths changed by an image layer.
+#[derive(Debug, Default)]
+struct LayerChanges {
+    /// Paths which were added or modified by the layer
+    modified: BTreeSet<Utf8PathBuf>,
+    /// Paths which were removed from lower layers, including their contents
+    removed: BTreeSet<Utf8PathBuf>,
+}
+
+impl LayerChanges {
+    /// Returns true if content at or underneath any of the `inputs` may have changed.
+    fn affects(&self, inputs: &[&str]) -> bool {
+        inputs.iter().map(Utf8Path::new).any(|input| {
+            self.modified.iter().any(|p| p.starts_with(input))
+                || self
+                    .removed
+                    .iter()
+                    .any(|p| p.starts_with(input) || input.starts_with(p))
+        })
+    }
+}
+
+/// Returns true if this tar entry is an overlay-style whiteout, which
+/// should never be written to disk. Content in `changes.modified` is from the
+/// current layer, and is hence not affected by an opaque whiteout.
+fn unpack_whiteout(dest: &Dir, path: &Utf8Path, changes: &mut LayerChanges) -> Result<bool> {
+    let Some(name) = path.file_name() else {
+        return Ok(false);
+    };
+    let Some(target) = name.strip_prefix(".wh.") else {
+        return Ok(false);
+    };
+    let parent = path.parent().unwrap_or(Utf8Path::new(""));
+    if target == ".wh..opq" {
+        // An opaque whiteout hides all content from lower layers in this directory.
+        let parent_dir = if parent.as_str().is_empty() {
+            Utf8Path::new(".")
+        } else {
+            parent
+        };
+        if let Some(d) = dest.open_dir_optional(parent_dir)? {
+            for entry in d.entries_utf8()? {
+                let entry = entry?;
+                let name = entry.file_name()?;
+                if changes.modified.contains(&parent.join(&name)) {
+                    continue;
+                }
+                if entry.file_type()?.is_dir() {
+                    d.remove_dir_all(&name)?;
+                } else {
+                    d.remove_file(&name)?;
+                }
+            }
+        }
+        changes.removed.insert(parent.to_owned());
+    } else {
+        let target = parent.join(target);
+        match dest.symlink_metadata_optional(&target)? {
+            Some(meta) if meta.is_dir() => dest.remove_dir_all(&target)?,
+            Some(_) => dest.remove_file(&target)?,
+            None => {}
+        }
+        changes.removed.insert(target);
+    }
+    Ok(true)
+}
+
+/// Apply a single (decompressed) image layer on top of the target directory,
+/// processing whiteouts, and return the paths it changed.
+fn unpack_layer(
+    dest: &Dir,
+    destpath: &Utf8Path,
+    src: &mut dyn std::io::Read,
+) -> Result<LayerChanges> {
+    let mut archive = tar::Archive::new(src);
+    archive.set_overwrite(true);
+    let mut changes = LayerChanges::default();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?;
+        let path = Utf8Path::from_path(&path)
+            .ok_or_else(|| anyhow::anyhow!("Invalid non-UTF8 path: {path:?}"))?;
+        let path = Utf8PathBuf::from(path.as_str().trim_start_matches("./"));
+        if path.as_str().is_empty() || unpack_whiteout(dest, &path, &mut changes)? {
+            continue;
+        }
+        let ty = entry.header().entry_type();
+        if matches!(
+            ty,
+            tar::EntryType::Block | tar::EntryType::Char | tar::EntryType::Fifo
+        ) {
+            tracing::debug!("Skipping special file: {path}");
+            continue;
+        }
+        // A non-directory in this layer replaces a directory from a lower layer.
+        if !ty.is_dir() {
+            if let Some(meta) = dest.symlink_metadata_optional(&path)? {
+                if meta.is_dir() {
+                    dest.remove_dir_all(&path)?;
+                    changes.removed.insert(path.clone());
+                }
+            }
+        }
+        entry
+            .unpack_in(destpath)
+            .with_context(|| format!("Unpacking {path}"))?;
+        changes.modified.insert(path);
+    }
+    Ok(changes)
+}
+
+/// The default directory in which images are unpacked for linting, unless `TMPDIR` is set.
+const LINT_IMAGE_TMPDIR: &str = "/var/tmp";
+
+/// Run all lints against a container image (e.g. `oci-archive:foo.tar` or
+/// `containers-storage:quay.io/example/os`) without running it. Each layer is
+/// applied in turn, and for each finding we report the layer which introduced it.
+#[context("Linting {imgref}")]
+pub(crate) async fn lint_image<'skip>(
+    imgref: &str,
+    warning_disposition: WarningDisposition,
+    skip: impl IntoIterator<Item = &'skip str>,
+    mut output: impl std::io::Write,
+) -> Result<()> {
+    let skip: HashSet<_> = skip.into_iter().collect();
+    let imgref = ostree_ext::container::ImageReference::try_from(imgref)?;
+    let proxy = containers_image_proxy::ImageProxy::new().await?;
+    let img = proxy.open_image(&imgref.to_string()).await?;
+    let (_, manifest) = proxy.fetch_manifest(&img).await?;
+    let config = proxy.fetch_config(&img).await?;
+    let layer_info = proxy.get_layer_info(&img).await?;
+    // Pair each layer with the history entry that created it, if available.
+    let mut history = config
+        .history()
+        .iter()
+        .filter(|h| !h.empty_layer().unwrap_or_default())
+        .map(|h| h.created_by().as_deref());
+
+    // The full root filesystem is unpacked, which may not fit in a tmpfs /tmp.
+    let tmpdir = std::env::var_os("TMPDIR").unwrap_or_else(|| LINT_IMAGE_TMPDIR.into());
+    let tempdir = tempfile::tempdir_in(tmpdir)?;
+    let destpath: Utf8PathBuf = tempdir.path().to_owned().try_into()?;
+    let dest = Dir::open_ambient_dir(&destpath, cap_std::ambient_authority())?;
+
+    let layers = manifest.layers();
+    let n_layers = layers.len();
+    let mut layer_descriptions = Vec::with_capacity(n_layers);
+    // For each failing lint, the index of the layer which last changed its outcome.
+    let mut introduced = BTreeMap::new();
+    let mut previous = BTreeMap::new();
+    let mut outcomes = Vec::new();
+    for (i, layer) in layers.iter().enumerate() {
+        let created_by = history.next().flatten();
+        let mut desc = format!("layer {}/{n_layers} ({})", i + 1, layer.digest());
+        if let Some(created_by) = created_by {
+            write!(desc, ": {created_by}")?;
+        }
+        tracing::debug!("Applying {desc}");
+        layer_descriptions.push(desc);
+
+        let layer_dest = dest.try_clone()?;
+        let layer_destpath = destpath.clone();
+        let changes = ostree_ext::container::unencapsulate::fetch_layer_with(
+            &proxy,
+            &img,
+            &manifest,
+            layer,
+            layer_info.as_ref(),
+            imgref.transport,
+            move |src| unpack_layer(&layer_dest, &layer_destpath, src),
+        )
+        .await?;
+
+        outcomes = if i == 0 {
+            run_lints(&dest, RootType::Alternative, &skip)?
+        } else {
+            // Only run the lints again whose inputs were changed by this layer
+            outcomes
+                .into_iter()
+                .map(|(lint, outcome)| {
+                    let outcome = match outcome {
+                        Some(_) if lint.inputs.is_none_or(|inputs| changes.affects(inputs)) => {
+                            Some(run_lint(lint, &dest)?)
+                        }
+                        o => o,
+                    };
+                    anyhow::Ok((lint, outcome))
+                })
+                .collect::<Result<Vec<_>>>()?
+        };
+        for (lint, outcome) in outcomes.iter() {
+            let name = lint.name;
+            match outcome {
+                Some(Err(e)) => {
+                    if previous.get(name) != Some(e) {
+                        introduced.insert(name, i);
+                    }
+                    previous.insert(name, e.clone());
+                }
+                _ => {
+                    introduced.remove(name);
+                    previous.remove(name);
+                }
+            }
+        }
+    }
+    proxy.close_image(&img).await?;
+
+    let annotate = |lint: &Lint| {
+        introduced
+            .get(lint.name)
+            .map(|&i| format!("Introduced in {}", layer_descriptions[i]))
+    };
+    let r = report_lints(&outcomes, annotate, &mut output)?;
+    finish_lint(r, warning_disposition, output)
+}
+
 #[distributed_slice(LINTS)]
 static LINT_VAR_RUN: Lint = Lint::new_fatal(
     "var-run",
     "Check for /var/run being a physical directory; this is always a bug.",
     check_var_run,
-);
+)
+.set_inputs(&["var/run"]);
 fn check_var_run(root: &Dir) -> LintResult {
     if let Some(meta) = root.symlink_metadata_optional("var/run")? {
         if !meta.is_symlink() {
@@ -259,7 +664,8 @@ static LINT_BUILDAH_INJECTED: Lint = Lint::new_warning(
 )
 // This one doesn't make sense to run looking at the running root,
 // because we do expect /etc/hostname to be injected as
-.set_root_type(RootType::Alternative);
+.set_root_type(RootType::Alternative)
+.set_inputs(&["etc/hostname", "etc/resolv.conf"]);
 fn check_buildah_injected(root: &Dir) -> LintResult {
     const RUNTIME_INJECTED: &[&str] = &["etc/hostname", "etc/resolv.conf"];
     for ent in RUNTIME_INJECTED {
@@ -297,19 +703,350 @@ fn check_usretc(root: &Dir) -> LintResult {
     lint_ok()
 }
 
+/// Return true if this symlink target is `/dev/null`, which is commonly used
+/// to mask e.g. systemd units; it is expected not to resolve in a container build root.
+fn is_devnull_link(target: &std::path::Path) -> bool {
+    target.as_os_str().as_bytes() == b"/dev/null"
+}
+
+/// Return true if the symbolic link at `path` points into an API filesystem such as
+/// `/run`, which is only populated at runtime (e.g. `/etc/resolv.conf`). Relative
+/// targets are resolved lexically.
+fn is_runtime_link(path: &Utf8Path, target: &std::path::Path) -> bool {
+    let Some(target) = target.to_str().map(Utf8Path::new) else {
+        return false;
+    };
+    let mut abs = Utf8PathBuf::from("/");
+    if target.is_relative() {
+        if let Some(parent) = path.parent() {
+            abs.push(parent);
+        }
+    }
+    for component in target.components() {
+        match component {
+            Utf8Component::ParentDir => {
+                abs.pop();
+            }
+            Utf8Component::Normal(name) => abs.push(name),
+            Utf8Component::RootDir | Utf8Component::CurDir | Utf8Component::Prefix(_) => {}
+        }
+    }
+    RUNTIME_API_FILESYSTEMS.iter().any(|fs| abs.starts_with(fs))
+}
+
+/// Return true if `path` is in a dependency directory of a unit directory in /etc,
+/// e.g. `etc/systemd/system/multi-user.target.wants/foo.service`.
+fn is_unit_deps_link(path: &Utf8Path) -> bool {
+    let Some(depsdir) = path.parent() else {
+        return false;
+    };
+    let is_deps_dir = depsdir.file_name().is_some_and(|name| {
+        UNIT_DEPS_DIR_SUFFIXES
+            .iter()
+            .any(|suffix| name.ends_with(suffix))
+    });
+    is_deps_dir
+        && depsdir
+            .parent()
+            .is_some_and(|unitdir| ETC_SYSTEMD_UNITDIRS.iter().any(|d| unitdir == *d))
+}
+
+/// Return true if the target path resolves, following symbolic links and interpreting
+/// absolute links relative to the root.
+fn path_resolves(absroot: &RootDir, path: &Utf8Path) -> Result<bool> {
+    match absroot.open(path) {
+        Ok(_) => Ok(true),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(e) if e.raw_os_error() == Some(libc::ELOOP) => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Recursively gather all symbolic links in the target directory.
+fn collect_symlinks(root: &Dir, path: &Utf8Path, out: &mut Vec<Utf8PathBuf>) -> Result<()> {
+    for entry in root.entries_utf8()? {
+        let entry = entry?;
+        let ty = entry.file_type()?;
+        let path = path.join(entry.file_name()?);
+        if ty.is_symlink() {
+            out.push(path);
+        } else if ty.is_dir() {
+            let d = entry.open_dir()?;
+            collect_symlinks(d.as_cap_std(), &path, out)?;
+        }
+    }
+    Ok(())
+}
+
+#[distributed_slice(LINTS)]
+static LINT_ETC_SHADOWS_USR: Lint = Lint::new_warning(
+    "etc-shadows-usr",
+    indoc! { r#"
+Check for files in /etc which entirely replace a vendor default of the same name
+in /usr/lib, such as a copied systemd unit in /etc/systemd/system. Because
+/etc is subject to a 3-way merge, a file here will continue to shadow any
+future changes to the vendor default in /usr/lib, even if the container image
+later stops shipping it. Prefer drop-in files (e.g. foo.service.d/override.conf)
+or placing content directly in /usr/lib.
+"#},
+    check_etc_shadows_usr,
+);
+fn check_etc_shadows_usr(root: &Dir) -> LintResult {
+    let mut shadowing = Vec::new();
+    for dir in ETC_VENDOR_OVERRIDE_DIRS {
+        let Some(etcdir) = root.open_dir_optional(format!("etc/{dir}"))? else {
+            continue;
+        };
+        let Some(usrdir) = root.open_dir_optional(format!("usr/lib/{dir}"))? else {
+            continue;
+        };
+        for entry in etcdir.entries_utf8()? {
+            let entry = entry?;
+            // Symbolic links are usually masks or enablement, and
+            // directories are drop-ins; neither of these are a problem.
+            if !entry.file_type()?.is_file() {
+                continue;
+            }
+            let name = entry.file_name()?;
+            if usrdir.symlink_metadata_optional(&name)?.is_some() {
+                shadowing.push(Utf8PathBuf::from(format!("/etc/{dir}/{name}")));
+            }
+        }
+    }
+    if shadowing.is_empty() {
+        return lint_ok();
+    }
+    shadowing.sort();
+    let header = "Found files in /etc shadowing a vendor default in /usr/lib:";
+    lint_err(format_samples(header, shadowing.iter())?)
+}
+
+#[distributed_slice(LINTS)]
+static LINT_ETC_BROKEN_SYMLINKS: Lint = Lint::new_warning(
+    "etc-broken-symlinks",
+    indoc! { r#"
+Check for symbolic links in /etc whose target does not exist. Absolute
+links are resolved relative to the root. Links to /dev/null (commonly used
+for masking) and into the runtime API filesystems /run, /proc and /sys
+(e.g. /etc/resolv.conf) are ignored.
+"#},
+    check_etc_broken_symlinks,
+);
+fn check_etc_broken_symlinks(root: &Dir) -> LintResult {
+    let Some(etc) = root.open_dir_optional("etc")? else {
+        return lint_ok();
+    };
+    let mut symlinks = Vec::new();
+    collect_symlinks(&etc, "etc".into(), &mut symlinks)?;
+    let absroot = &RootDir::new(root, ".")?;
+    let mut broken = Vec::new();
+    for path in symlinks {
+        // Unit enablement is covered by a more specific lint.
+        if is_unit_deps_link(&path) {
+            continue;
+        }
+        let target = root.read_link_contents(&path)?;
+        if is_devnull_link(&target) || is_runtime_link(&path, &target) {
+            continue;
+        }
+        if !path_resolves(absroot, &path)? {
+            broken.push(Utf8Path::new("/").join(path));
+        }
+    }
+    if broken.is_empty() {
+        return lint_ok();
+    }
+    broken.sort();
+    let header = "Found broken symbolic links in /etc:";
+    lint_err(format_samples(header, broken.iter())?)
+}
+
+#[distributed_slice(LINTS)]
+static LINT_ETC_SYSTEMD_ENABLEMENT: Lint = Lint::new_warning(
+    "etc-systemd-enablement",
+    indoc! { r#"
+Check that systemd units enabled via symbolic links in /etc/systemd/system
+(e.g. in multi-user.target.wants) refer to units which exist. This commonly
+happens when a unit is enabled and the package providing it is later removed.
+"#},
+    check_etc_systemd_enablement,
+);
+fn check_etc_systemd_enablement(root: &Dir) -> LintResult {
+    let absroot = &RootDir::new(root, ".")?;
+    let mut missing = Vec::new();
+    for unitdir in ETC_SYSTEMD_UNITDIRS {
+        let Some(d) = root.open_dir_optional(unitdir)? else {
+            continue;
+        };
+        for entry in d.entries_utf8()? {
+            let entry = entry?;
+            let name = entry.file_name()?;
+            let is_deps_dir = UNIT_DEPS_DIR_SUFFIXES
+                .iter()
+                .any(|suffix| name.ends_with(suffix));
+            if !(is_deps_dir && entry.file_type()?.is_dir()) {
+                continue;
+            }
+            let depsdir = Utf8Path::new(unitdir).join(&name);
+            for entry in entry.open_dir()?.entries()? {
+                let entry = entry?;
+                if !entry.file_type()?.is_symlink() {
+                    continue;
+                }
+                let path = depsdir.join(entry.file_name()?);
+                if is_devnull_link(&root.read_link_contents(&path)?) {
+                    continue;
+                }
+                if !path_resolves(absroot, &path)? {
+                    missing.push(Utf8Path::new("/").join(path));
+                }
+            }
+        }
+    }
+    if missing.is_empty() {
+        return lint_ok();
+    }
+    missing.sort();
+    let header = "Found enabled systemd units which do not exist:";
+    lint_err(format_samples(header, missing.iter())?)
+}
+
+#[distributed_slice(LINTS)]
+static LINT_ETC_FSTAB_USR: Lint = Lint::new_warning(
+    "etc-fstab-usr",
+    indoc! { r#"
+Check for entries in /etc/fstab with a mount point underneath /usr. The /usr
+filesystem is read-only at runtime, so mount points cannot be created there;
+additionally content in /usr is expected to come from the container image.
+"#},
+    check_etc_fstab_usr,
+)
+.set_inputs(&["etc/fstab"]);
+fn check_etc_fstab_usr(root: &Dir) -> LintResult {
+    let Some(f) = root.open_optional("etc/fstab")? else {
+        return lint_ok();
+    };
+    let buf = std::io::read_to_string(f)?;
+    let mountpoints = buf
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_ascii_whitespace().nth(1))
+        .map(Utf8Path::new)
+        .filter(|mountpoint| mountpoint.starts_with("/usr"))
+        .map(ToOwned::to_owned)
+        .collect::<Vec<_>>();
+    if mountpoints.is_empty() {
+        return lint_ok();
+    }
+    let header = "Found /etc/fstab entries with mount points under read-only /usr:";
+    lint_err(format_samples(header, mountpoints.iter())?)
+}
+
 /// Validate that we can parse the /usr/lib/bootc/kargs.d files.
 #[distributed_slice(LINTS)]
 static LINT_KARGS: Lint = Lint::new_fatal(
     "bootc-kargs",
     "Verify syntax of /usr/lib/bootc/kargs.d.",
     check_parse_kargs,
-);
+)
+.set_inputs(&["usr/lib/bootc/kargs.d"]);
 fn check_parse_kargs(root: &Dir) -> LintResult {
-    let args = crate::kargs::get_kargs_in_root(root, ARCH)?;
+    let facts = &crate::kargs::HostFacts::for_arch(ARCH);
+    let args = crate::kargs::get_kargs_in_root(root, facts)?;
     tracing::debug!("found kargs: {args:?}");
     lint_ok()
 }
 
+#[distributed_slice(LINTS)]
+static LINT_BOUND_IMAGES: Lint = Lint::new_fatal(
+    "bound-images",
+    indoc! { r#"
+Verify the logically bound images in /usr/lib/bootc/bound-images.d: each entry
+must be a symbolic link to a `.image`, `.container`, `.pod` or `.kube` file which exists and parses.
+Otherwise, fetching the bound images will fail on upgrade.
+"#},
+    check_bound_images,
+);
+fn check_bound_images(root: &Dir) -> LintResult {
+    let mut problems = crate::boundimage::parse_bound_image_entries(root)?
+        .into_iter()
+        .filter_map(|(name, r)| r.err().map(|e| format!("{name}: {e:#}")))
+        .collect::<Vec<_>>();
+    if problems.is_empty() {
+        return lint_ok();
+    }
+    problems.sort();
+    let header = "Found invalid bound images:";
+    lint_err(format_samples(header, problems.iter())?)
+}
+
+#[distributed_slice(LINTS)]
+static LINT_BOUND_IMAGES_REFERENCES: Lint = Lint::new_warning(
+    "bound-images-references",
+    indoc! { r#"
+Check that logically bound images are fully qualified (include a registry), and
+that any authentication file exists in the image. Short names depend on the
+registries configuration of the host, and an authentication file may also be
+provisioned at runtime.
+"#},
+    check_bound_images_references,
+);
+fn check_bound_images_references(root: &Dir) -> LintResult {
+    let absroot = &RootDir::new(root, ".")?;
+    let mut problems = Vec::new();
+    for (name, r) in crate::boundimage::parse_bound_image_entries(root)? {
+        let Ok(images) = r else {
+            continue;
+        };
+        for img in images {
+            if !img.is_fully_qualified() {
+                problems.push(format!(
+                    "{name}: Image is not fully qualified: {}",
+                    img.image
+                ));
+            }
+            if let Some(auth_file) = img.auth_file.as_deref() {
+                let path = Utf8Path::new(auth_file.trim_start_matches('/'));
+                if !path_resolves(absroot, path)? {
+                    problems.push(format!("{name}: AuthFile not found: {auth_file}"));
+                }
+            }
+        }
+    }
+    if problems.is_empty() {
+        return lint_ok();
+    }
+    problems.sort();
+    let header = "Found bound image references which may not resolve:";
+    lint_err(format_samples(header, problems.iter())?)
+}
+
+#[distributed_slice(LINTS)]
+static LINT_BOUND_IMAGES_DIGEST: Lint = Lint::new_warning(
+    "bound-images-digest",
+    indoc! { r#"
+Check that logically bound images are pinned by digest (e.g. `Image=quay.io/example/app@sha256:...`).
+Images referenced by tag may change independently of this container image, so
+the same bootc image may result in different bound images over time.
+"#},
+    check_bound_images_digest,
+);
+fn check_bound_images_digest(root: &Dir) -> LintResult {
+    let unpinned = crate::boundimage::parse_bound_image_entries(root)?
+        .into_iter()
+        .filter_map(|(name, r)| r.ok().map(|images| (name, images)))
+        .flat_map(|(name, images)| images.into_iter().map(move |img| (name.clone(), img)))
+        .filter(|(_, img)| !img.is_digest_pinned())
+        .map(|(name, img)| format!("{name}: {}", img.image))
+        .collect::<BTreeSet<_>>();
+    if unpinned.is_empty() {
+        return lint_ok();
+    }
+    let header = "Found bound images not pinned by digest:";
+    lint_err(format_samples(header, unpinned.iter())?)
+}
+
 #[distributed_slice(LINTS)]
 static LINT_KERNEL: Lint = Lint::new_fatal(
     "kernel",
@@ -318,13 +1055,180 @@ static LINT_KERNEL: Lint = Lint::new_fatal(
              Only one kernel is supported in an image.
      "# },
     check_kernel,
-);
+)
+.set_inputs(&["usr/lib/modules"]);
 fn check_kernel(root: &Dir) -> LintResult {
     let result = ostree_ext::bootabletree::find_kernel_dir_fs(&root)?;
     tracing::debug!("Found kernel: {:?}", result);
     lint_ok()
 }
 
+/// Return true if this filename looks like a (possibly compressed) kernel module.
+fn is_kernel_module(name: &str) -> bool {
+    KMOD_SUFFIXES.iter().any(|suffix| name.ends_with(suffix))
+}
+
+/// Recursively gather the modification time of all regular files in the target
+/// directory matching the provided filter. Symbolic links are not followed.
+fn collect_regfile_mtimes(
+    root: &Dir,
+    path: &Utf8Path,
+    filter: fn(&str) -> bool,
+    out: &mut BTreeMap<Utf8PathBuf, i64>,
+) -> Result<()> {
+    for entry in root.entries_utf8()? {
+        let entry = entry?;
+        let ty = entry.file_type()?;
+        let name = entry.file_name()?;
+        let path = path.join(&name);
+        if ty.is_file() && filter(&name) {
+            out.insert(path, entry.metadata()?.mtime());
+        } else if ty.is_dir() {
+            let d = entry.open_dir()?;
+            collect_regfile_mtimes(d.as_cap_std(), &path, filter, out)?;
+        }
+    }
+    Ok(())
+}
+
+/// Format a list of items (e.g. paths) as an error message, showing only the first few.
+fn format_samples<T: std::fmt::Display>(
+    header: &str,
+    items: impl Iterator<Item = T> + Clone,
+) -> Result<String> {
+    let mut msg = String::new();
+    if let Some((samples, rest)) = bootc_utils::iterator_split_nonempty_rest_count(items, 5) {
+        writeln!(msg, "{header}")?;
+        for elt in samples {
+            writeln!(msg, "  {elt}")?;
+        }
+        if rest > 0 {
+            writeln!(msg, "  ...and {} more", rest)?;
+        }
+    }
+    Ok(msg)
+}
+
+#[distributed_slice(LINTS)]
+static LINT_KERNEL_INITRAMFS: Lint = Lint::new_warning(
+    "kernel-initramfs",
+    indoc! { r#"
+Check that the kernel in /usr/lib/modules/$kver has a corresponding initramfs.img.
+While an initramfs is not strictly required, almost all systems need one to boot.
+"#},
+    check_kernel_initramfs,
+)
+.set_inputs(&["usr/lib/modules"]);
+fn check_kernel_initramfs(root: &Dir) -> LintResult {
+    let Some(kdir) = ostree_ext::bootabletree::find_kernel_dir_fs(&root)? else {
+        return lint_ok();
+    };
+    let path = kdir.join(INITRAMFS);
+    match root.symlink_metadata_optional(&path)? {
+        Some(meta) if meta.is_file() && meta.size() > 0 => lint_ok(),
+        Some(_) => lint_err(format!("/{path} is not a non-empty regular file")),
+        None => lint_err(format!("Missing /{path}")),
+    }
+}
+
+#[distributed_slice(LINTS)]
+static LINT_KERNEL_MODULES_DEP: Lint = Lint::new_warning(
+    "kernel-modules-dep",
+    indoc! { r#"
+Check that every kernel module in /usr/lib/modules/$kver is listed in modules.dep.
+A common cause of this is installing additional kernel modules in a derived
+image without then running `depmod`; the new modules will not be automatically loaded.
+"#},
+    check_kernel_modules_dep,
+)
+.set_inputs(&["usr/lib/modules"]);
+fn check_kernel_modules_dep(root: &Dir) -> LintResult {
+    let Some(kdir) = ostree_ext::bootabletree::find_kernel_dir_fs(&root)? else {
+        return lint_ok();
+    };
+    let d = root.open_dir(&kdir)?;
+    let mut modules = BTreeMap::new();
+    collect_regfile_mtimes(&d, "".into(), is_kernel_module, &mut modules)?;
+    if modules.is_empty() {
+        return lint_ok();
+    }
+    let Some(f) = d.open_optional(MODULES_DEP)? else {
+        return lint_err(format!(
+            "Found kernel modules, but missing /{kdir}/{MODULES_DEP}"
+        ));
+    };
+    let buf = std::io::read_to_string(f)?;
+    // Each line is of the form `<module>: <dependency> <dependency>...`, where
+    // the paths are relative to the kernel directory.
+    let listed = buf
+        .lines()
+        .filter_map(|line| line.split_once(':'))
+        .map(|(module, _)| Utf8Path::new(module.trim()))
+        .collect::<BTreeSet<_>>();
+    let unlisted = modules
+        .keys()
+        .filter(|module| !listed.contains(module.as_path()))
+        .map(|module| Utf8Path::new("/").join(&kdir).join(module))
+        .collect::<Vec<_>>();
+    if unlisted.is_empty() {
+        return lint_ok();
+    }
+    let header = format!("Found kernel modules missing from {MODULES_DEP} (run depmod):");
+    lint_err(format_samples(&header, unlisted.iter())?)
+}
+
+#[distributed_slice(LINTS)]
+static LINT_KERNEL_INITRAMFS_STALE: Lint = Lint::new_warning(
+    "kernel-initramfs-stale",
+    indoc! { r#"
+Check that the initramfs is newer than the kernel, kernel modules, modules.dep and
+the dracut configuration. If any of these were changed (e.g. in a derived layer)
+after the initramfs was generated, it likely needs to be regenerated via `dracut`.
+"#},
+    check_kernel_initramfs_stale,
+)
+.set_inputs(&[
+    "usr/lib/modules",
+    "usr/lib/dracut/dracut.conf.d",
+    "etc/dracut.conf.d",
+]);
+fn check_kernel_initramfs_stale(root: &Dir) -> LintResult {
+    let Some(kdir) = ostree_ext::bootabletree::find_kernel_dir_fs(&root)? else {
+        return lint_ok();
+    };
+    // A missing initramfs is handled by a distinct lint.
+    let Some(initramfs) = root.symlink_metadata_optional(kdir.join(INITRAMFS))? else {
+        return lint_ok();
+    };
+    let initramfs_mtime = initramfs.mtime();
+
+    let mut inputs = BTreeMap::new();
+    let d = root.open_dir(&kdir)?;
+    let kdir_abs = Utf8Path::new("/").join(&kdir);
+    collect_regfile_mtimes(&d, &kdir_abs, is_kernel_module, &mut inputs)?;
+    for name in [VMLINUZ, MODULES_DEP] {
+        if let Some(meta) = d.symlink_metadata_optional(name)? {
+            inputs.insert(kdir_abs.join(name), meta.mtime());
+        }
+    }
+    for confdir in DRACUT_CONFDIRS {
+        if let Some(d) = root.open_dir_optional(confdir)? {
+            let path = Utf8Path::new("/").join(confdir);
+            collect_regfile_mtimes(&d, &path, |_| true, &mut inputs)?;
+        }
+    }
+
+    let newer = inputs
+        .into_iter()
+        .filter_map(|(path, mtime)| (mtime > initramfs_mtime).then_some(path))
+        .collect::<Vec<_>>();
+    if newer.is_empty() {
+        return lint_ok();
+    }
+    let header = format!("Found content newer than /{kdir}/{INITRAMFS}:");
+    lint_err(format_samples(&header, newer.iter())?)
+}
+
 // This one can be lifted in the future, see https://github.com/containers/bootc/issues/975
 #[distributed_slice(LINTS)]
 static LINT_UTF8: Lint = Lint::new_fatal(
@@ -482,7 +1386,8 @@ timestamps, causing unreproducible container images, and may contain
 sensitive build system information.
 "#},
     check_varlog,
-);
+)
+.set_inputs(&["var/log"]);
 fn check_varlog(root: &Dir) -> LintResult {
     let Some(d) = root.open_dir_optional("var/log")? else {
         return lint_ok();
@@ -596,6 +1501,202 @@ fn check_sysusers(rootfs: &Dir) -> LintResult {
     lint_err(msg)
 }
 
+#[distributed_slice(LINTS)]
+static LINT_TMPFILES_SYNTAX: Lint = Lint::new_warning(
+    "tmpfiles-syntax",
+    indoc! { r#"
+Check that systemd tmpfiles.d fragments in /usr/lib/tmpfiles.d and /etc/tmpfiles.d
+are well formed: each entry must have a known type, an absolute path and a valid mode.
+systemd-tmpfiles ignores invalid lines at boot, which can leave content silently missing.
+"#},
+    check_tmpfiles_syntax,
+)
+.set_inputs(&["usr/lib/tmpfiles.d", "etc/tmpfiles.d"]);
+fn check_tmpfiles_syntax(root: &Dir) -> LintResult {
+    let invalid = bootc_tmpfiles::find_invalid_tmpfiles(root)?;
+    if invalid.is_empty() {
+        return lint_ok();
+    }
+    let header = "Found invalid systemd tmpfiles.d entries:";
+    lint_err(format_samples(header, invalid.iter())?)
+}
+
+#[distributed_slice(LINTS)]
+static LINT_SYSUSERS_SYNTAX: Lint = Lint::new_warning(
+    "sysusers-syntax",
+    indoc! { r#"
+Check that systemd sysusers.d fragments in /usr/lib/sysusers.d and /etc/sysusers.d
+are well formed. systemd-sysusers ignores invalid lines at boot, which can result
+in users or groups silently not being created.
+"#},
+    check_sysusers_syntax,
+)
+.set_inputs(&["usr/lib/sysusers.d", "etc/sysusers.d"]);
+fn check_sysusers_syntax(root: &Dir) -> LintResult {
+    let invalid = bootc_sysusers::find_invalid_sysusers(root)?;
+    if invalid.is_empty() {
+        return lint_ok();
+    }
+    let header = "Found invalid systemd sysusers.d entries:";
+    lint_err(format_samples(header, invalid.iter())?)
+}
+
+/// Extract the executable from a systemd `Exec*=` command line, skipping over
+/// the special prefixes. Returns `None` for values we can't statically check,
+/// such as those using specifiers or environment variable expansion.
+fn systemd_exec_path(value: &str) -> Option<&str> {
+    let value = value.trim_start_matches(['@', '-', ':', '+', '!', '|']);
+    let exe = value.split_ascii_whitespace().next()?;
+    if exe.contains(['%', '$', '"', '\'']) {
+        return None;
+    }
+    Some(exe)
+}
+
+/// Return true if the executable for a systemd `Exec*=` line exists in the root;
+/// relative names are looked up in the fixed search path systemd uses.
+fn systemd_exec_exists(absroot: &RootDir, exe: &str) -> Result<bool> {
+    if let Some(exe) = exe.strip_prefix('/') {
+        return path_resolves(absroot, Utf8Path::new(exe));
+    }
+    for dir in SYSTEMD_EXEC_SEARCH_PATH {
+        if path_resolves(absroot, &Utf8Path::new(dir).join(exe))? {
+            return Ok(true);
+        }
+    }
+    Ok(false)
+}
+
+/// Check a single systemd unit file (or drop-in), appending any problems found.
+fn lint_systemd_unit(
+    absroot: &RootDir,
+    path: &Utf8Path,
+    contents: &str,
+    problems: &mut Vec<String>,
+) -> Result<()> {
+    let mut section: Option<String> = None;
+    let mut lines = contents.lines().enumerate();
+    while let Some((i, line)) = lines.next() {
+        let lineno = i + 1;
+        let mut line = line.trim().to_owned();
+        if line.is_empty() || line.starts_with(['#', ';']) {
+            continue;
+        }
+        // Join continuation lines
+        while let Some(prefix) = line.strip_suffix('\\') {
+            let prefix = prefix.to_owned();
+            let next = lines.next().map(|(_, l)| l.trim()).unwrap_or_default();
+            line = format!("{prefix} {next}");
+        }
+        if let Some(name) = line.strip_prefix('[') {
+            let Some(name) = name.strip_suffix(']') else {
+                problems.push(format!("/{path}:{lineno}: Invalid section header: {line}"));
+                section = None;
+                continue;
+            };
+            if !(SYSTEMD_UNIT_SECTIONS.contains(&name) || name.starts_with("X-")) {
+                problems.push(format!("/{path}:{lineno}: Unknown section [{name}]"));
+            }
+            section = Some(name.to_owned());
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=') else {
+            problems.push(format!("/{path}:{lineno}: Expected Key=Value: {line}"));
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim());
+        let Some(section) = section.as_deref() else {
+            problems.push(format!(
+                "/{path}:{lineno}: Assignment outside of a section: {key}"
+            ));
+            continue;
+        };
+        if key.starts_with("X-") {
+            continue;
+        }
+        let known = match section {
+            "Unit" => {
+                SYSTEMD_UNIT_DIRECTIVES.contains(&key)
+                    || key.starts_with("Condition")
+                    || key.starts_with("Assert")
+            }
+            "Install" => SYSTEMD_INSTALL_DIRECTIVES.contains(&key),
+            // The other sections accept a large (and growing) set of directives
+            _ => true,
+        };
+        if !known {
+            problems.push(format!(
+                "/{path}:{lineno}: Unknown directive {key} in section [{section}]"
+            ));
+        }
+        if SYSTEMD_EXEC_DIRECTIVES.contains(&key) {
+            if let Some(exe) = systemd_exec_path(value) {
+                if !systemd_exec_exists(absroot, exe)? {
+                    problems.push(format!("/{path}:{lineno}: {key}: {exe} not found"));
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+#[distributed_slice(LINTS)]
+static LINT_SYSTEMD_UNITS: Lint = Lint::new_warning(
+    "systemd-units",
+    indoc! { r#"
+Check systemd unit files and drop-ins in /usr/lib/systemd/system and /etc/systemd/system
+for syntax errors, unknown sections, unknown [Unit] or [Install] directives, and
+Exec*= commands whose executable does not exist in the image.
+"#},
+    check_systemd_units,
+);
+fn check_systemd_units(root: &Dir) -> LintResult {
+    let absroot = &RootDir::new(root, ".")?;
+    let mut problems = Vec::new();
+    let mut check_file = |d: &Dir, name: &str, path: &Utf8Path| -> Result<()> {
+        if !d.symlink_metadata(name)?.is_file() {
+            return Ok(());
+        }
+        let contents = d.read_to_string(name)?;
+        lint_systemd_unit(absroot, path, &contents, &mut problems)
+    };
+    for unitdir in SYSTEMD_SYSTEM_UNITDIRS {
+        let Some(d) = root.open_dir_optional(unitdir)? else {
+            continue;
+        };
+        for entry in d.entries_utf8()? {
+            let entry = entry?;
+            let name = entry.file_name()?;
+            let path = Utf8Path::new(unitdir).join(&name);
+            let ty = entry.file_type()?;
+            if ty.is_dir() && name.ends_with(".d") {
+                let dropins = entry.open_dir()?;
+                let dropins = dropins.as_cap_std();
+                for dropin in dropins.entries_utf8()? {
+                    let dropin = dropin?;
+                    let dropin_name = dropin.file_name()?;
+                    if !dropin_name.ends_with(".conf") {
+                        continue;
+                    }
+                    check_file(dropins, &dropin_name, &path.join(&dropin_name))?;
+                }
+            } else if ty.is_file()
+                && Utf8Path::new(&name)
+                    .extension()
+                    .is_some_and(|ext| SYSTEMD_UNIT_SUFFIXES.contains(&ext))
+            {
+                check_file(&d, &name, &path)?;
+            }
+        }
+    }
+    if problems.is_empty() {
+        return lint_ok();
+    }
+    problems.sort();
+    let header = "Found problems in systemd units:";
+    lint_err(format_samples(header, problems.iter())?)
+}
+
 #[distributed_slice(LINTS)]
 static LINT_NONEMPTY_BOOT: Lint = Lint::new_warning(
     "nonempty-boot",
@@ -647,6 +1748,7 @@ mod tests {
         let root = cap_std_ext::cap_tempfile::tempdir(cap_std::ambient_authority())?;
         root.create_dir_all("usr/lib/modules/5.7.2")?;
         root.write("usr/lib/modules/5.7.2/vmlinuz", "vmlinuz")?;
+        root.write("usr/lib/modules/5.7.2/initramfs.img", "initramfs")?;
 
         root.create_dir("boot")?;
         root.create_dir("sysroot")?;
@@ -661,6 +1763,69 @@ mod tests {
         Ok(root)
     }
 
+    fn tar_layer(entries: &[(&str, Option<&str>)]) -> Result<Vec<u8>> {
+        let mut b = tar::Builder::new(Vec::new());
+        for &(path, contents) in entries {
+            let mut h = tar::Header::new_gnu();
+            if let Some(contents) = contents {
+                h.set_entry_type(tar::EntryType::Regular);
+                h.set_mode(0o644);
+                h.set_size(contents.len() as u64);
+                b.append_data(&mut h, path, contents.as_bytes())?;
+            } else {
+                h.set_entry_type(tar::EntryType::Directory);
+                h.set_mode(0o755);
+                h.set_size(0);
+                b.append_data(&mut h, path, std::io::empty())?;
+            }
+        }
+        Ok(b.into_inner()?)
+    }
+
+    #[test]
+    fn test_unpack_layer() -> Result<()> {
+        let td = tempfile::tempdir()?;
+        let destpath = Utf8Path::from_path(td.path()).unwrap();
+        let dest = &Dir::open_ambient_dir(destpath, cap_std::ambient_authority())?;
+        let layer1 = tar_layer(&[
+            ("usr/", None),
+            ("usr/a", Some("a")),
+            ("usr/b", Some("b")),
+            ("./opq/", None),
+            ("./opq/x", Some("x")),
+            ("replaced/", None),
+            ("replaced/foo", Some("foo")),
+        ])?;
+        unpack_layer(dest, destpath, &mut layer1.as_slice())?;
+        assert_eq!(dest.read_to_string("usr/a")?, "a");
+        assert_eq!(dest.read_to_string("opq/x")?, "x");
+
+        let layer2 = tar_layer(&[
+            ("usr/.wh.a", Some("")),
+            ("usr/b", Some("newb")),
+            ("opq/y", Some("y")),
+            ("opq/.wh..wh..opq", Some("")),
+            ("replaced", Some("now a file")),
+        ])?;
+        let changes = unpack_layer(dest, destpath, &mut layer2.as_slice())?;
+        assert!(!dest.try_exists("usr/a")?);
+        assert!(!dest.try_exists("usr/.wh.a")?);
+        assert_eq!(dest.read_to_string("usr/b")?, "newb");
+        assert!(!dest.try_exists("opq/x")?);
+        assert!(!dest.try_exists("opq/.wh..wh..opq")?);
+        assert_eq!(dest.read_to_string("opq/y")?, "y");
+        assert_eq!(dest.read_to_string("replaced")?, "now a file");
+
+        let paths = |s: &BTreeSet<Utf8PathBuf>| s.iter().map(|p| p.as_str()).collect::<Vec<_>>();
+        assert_eq!(paths(&changes.modified), ["opq/y", "replaced", "usr/b"]);
+        assert_eq!(paths(&changes.removed), ["opq", "replaced", "usr/a"]);
+        assert!(changes.affects(&["usr"]));
+        assert!(changes.affects(&["usr/a/b"]));
+        assert!(changes.affects(&["opq/x"]));
+        assert!(!changes.affects(&["usr/c", "etc"]));
+        Ok(())
+    }
+
     #[test]
     fn test_var_run() -> Result<()> {
         let root = &fixture()?;
@@ -736,6 +1901,114 @@ mod tests {
         Ok(())
     }
 
+    fn set_mtime(root: &Dir, path: &str, secs: i64) -> Result<()> {
+        let t = rustix::fs::Timespec {
+            tv_sec: secs,
+            tv_nsec: 0,
+        };
+        let times = rustix::fs::Timestamps {
+            last_access: t,
+            last_modification: t,
+        };
+        rustix::fs::utimensat(root, path, &times, rustix::fs::AtFlags::SYMLINK_NOFOLLOW)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_kernel_initramfs() -> Result<()> {
+        let root = &fixture()?;
+        // No kernel at all is fine
+        check_kernel_initramfs(root).unwrap().unwrap();
+        root.create_dir_all("usr/lib/modules/5.7.2")?;
+        root.write("usr/lib/modules/5.7.2/vmlinuz", "vmlinuz")?;
+        let Err(e) = check_kernel_initramfs(root).unwrap() else {
+            unreachable!()
+        };
+        assert_eq!(
+            e.to_string(),
+            "Missing /usr/lib/modules/5.7.2/initramfs.img"
+        );
+        root.write("usr/lib/modules/5.7.2/initramfs.img", "")?;
+        assert!(check_kernel_initramfs(root).unwrap().is_err());
+        root.write("usr/lib/modules/5.7.2/initramfs.img", "initramfs")?;
+        check_kernel_initramfs(root).unwrap().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn test_kernel_modules_dep() -> Result<()> {
+        let root = &passing_fixture()?;
+        // No modules
+        check_kernel_modules_dep(root).unwrap().unwrap();
+        root.create_dir_all("usr/lib/modules/5.7.2/kernel/drivers/net")?;
+        root.write(
+            "usr/lib/modules/5.7.2/kernel/drivers/net/foo.ko.xz",
+            "foo module",
+        )?;
+        let Err(e) = check_kernel_modules_dep(root).unwrap() else {
+            unreachable!()
+        };
+        assert!(e
+            .to_string()
+            .contains("missing /usr/lib/modules/5.7.2/modules.dep"));
+        root.write(
+            "usr/lib/modules/5.7.2/modules.dep",
+            "kernel/drivers/net/foo.ko.xz:\n",
+        )?;
+        check_kernel_modules_dep(root).unwrap().unwrap();
+
+        // Simulate a derived layer adding a module without running depmod
+        root.create_dir_all("usr/lib/modules/5.7.2/extra")?;
+        root.write("usr/lib/modules/5.7.2/extra/bar.ko", "bar module")?;
+        let Err(e) = check_kernel_modules_dep(root).unwrap() else {
+            unreachable!()
+        };
+        similar_asserts::assert_eq!(
+            e.to_string(),
+            "Found kernel modules missing from modules.dep (run depmod):\n  /usr/lib/modules/5.7.2/extra/bar.ko\n"
+        );
+        root.write(
+            "usr/lib/modules/5.7.2/modules.dep",
+            "kernel/drivers/net/foo.ko.xz:\nextra/bar.ko: kernel/drivers/net/foo.ko.xz\n",
+        )?;
+        check_kernel_modules_dep(root).unwrap().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn test_kernel_initramfs_stale() -> Result<()> {
+        let root = &passing_fixture()?;
+        let kdir = "usr/lib/modules/5.7.2";
+        set_mtime(root, &format!("{kdir}/vmlinuz"), 100)?;
+        set_mtime(root, &format!("{kdir}/initramfs.img"), 100)?;
+        check_kernel_initramfs_stale(root).unwrap().unwrap();
+
+        // A module added in a later layer
+        root.create_dir_all(format!("{kdir}/extra"))?;
+        root.write(format!("{kdir}/extra/bar.ko"), "bar module")?;
+        set_mtime(root, &format!("{kdir}/extra/bar.ko"), 200)?;
+        let Err(e) = check_kernel_initramfs_stale(root).unwrap() else {
+            unreachable!()
+        };
+        similar_asserts::assert_eq!(
+            e.to_string(),
+            "Found content newer than /usr/lib/modules/5.7.2/initramfs.img:\n  /usr/lib/modules/5.7.2/extra/bar.ko\n"
+        );
+        // Regenerating the initramfs fixes things
+        set_mtime(root, &format!("{kdir}/initramfs.img"), 300)?;
+        check_kernel_initramfs_stale(root).unwrap().unwrap();
+
+        // As does changing the dracut configuration
+        root.create_dir_all("etc/dracut.conf.d")?;
+        root.write("etc/dracut.conf.d/foo.conf", "add_drivers+=\" bar \"")?;
+        set_mtime(root, "etc/dracut.conf.d/foo.conf", 400)?;
+        let Err(e) = check_kernel_initramfs_stale(root).unwrap() else {
+            unreachable!()
+        };
+        assert!(e.to_string().contains("/etc/dracut.conf.d/foo.conf"));
+        Ok(())
+    }
+
     #[test]
     fn test_kargs() -> Result<()> {
         let root = &fixture()?;
@@ -760,6 +2033,301 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_etc_shadows_usr() -> Result<()> {
+        let root = &fixture()?;
+        check_etc_shadows_usr(root).unwrap().unwrap();
+        root.create_dir_all("usr/lib/systemd/system")?;
+        root.write("usr/lib/systemd/system/foo.service", "[Unit]\n")?;
+        root.create_dir_all("etc/systemd/system/foo.service.d")?;
+        root.write(
+            "etc/systemd/system/foo.service.d/50-override.conf",
+            "[Unit]\n",
+        )?;
+        root.symlink_contents("/dev/null", "etc/systemd/system/bar.service")?;
+        // Drop-ins and masks are fine
+        check_etc_shadows_usr(root).unwrap().unwrap();
+        root.write("etc/systemd/system/foo.service", "[Unit]\n")?;
+        let Err(e) = check_etc_shadows_usr(root).unwrap() else {
+            unreachable!()
+        };
+        similar_asserts::assert_eq!(
+            e.to_string(),
+            "Found files in /etc shadowing a vendor default in /usr/lib:\n  /etc/systemd/system/foo.service\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_etc_broken_symlinks() -> Result<()> {
+        let root = &fixture()?;
+        check_etc_broken_symlinks(root).unwrap().unwrap();
+        root.create_dir_all("etc/pki")?;
+        root.create_dir_all("usr/share/pki")?;
+        root.write("usr/share/pki/ca.pem", "some cert")?;
+        // Absolute and relative links which resolve
+        root.symlink_contents("/usr/share/pki/ca.pem", "etc/pki/ca.pem")?;
+        root.symlink_contents("../../usr/share/pki/ca.pem", "etc/pki/ca-relative.pem")?;
+        root.symlink_contents("/dev/null", "etc/pki/masked")?;
+        // Links into API filesystems only resolve at runtime
+        root.symlink_contents("../run/systemd/resolve/stub-resolv.conf", "etc/resolv.conf")?;
+        root.symlink_contents("../proc/self/mounts", "etc/mtab")?;
+        root.symlink_contents("/sys/class/foo", "etc/pki/sysfs")?;
+        check_etc_broken_symlinks(root).unwrap().unwrap();
+        root.symlink_contents("/usr/share/pki/nonexistent.pem", "etc/pki/broken.pem")?;
+        root.symlink_contents("loop", "etc/loop")?;
+        let Err(e) = check_etc_broken_symlinks(root).unwrap() else {
+            unreachable!()
+        };
+        similar_asserts::assert_eq!(
+            e.to_string(),
+            "Found broken symbolic links in /etc:\n  /etc/loop\n  /etc/pki/broken.pem\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_etc_systemd_enablement() -> Result<()> {
+        let root = &fixture()?;
+        check_etc_systemd_enablement(root).unwrap().unwrap();
+        root.create_dir_all("usr/lib/systemd/system")?;
+        root.write("usr/lib/systemd/system/foo.service", "[Unit]\n")?;
+        let wants = "etc/systemd/system/multi-user.target.wants";
+        root.create_dir_all(wants)?;
+        root.symlink_contents(
+            "/usr/lib/systemd/system/foo.service",
+            format!("{wants}/foo.service"),
+        )?;
+        check_etc_systemd_enablement(root).unwrap().unwrap();
+        root.symlink_contents(
+            "/usr/lib/systemd/system/bar.service",
+            format!("{wants}/bar.service"),
+        )?;
+        let Err(e) = check_etc_systemd_enablement(root).unwrap() else {
+            unreachable!()
+        };
+        similar_asserts::assert_eq!(
+            e.to_string(),
+            "Found enabled systemd units which do not exist:\n  /etc/systemd/system/multi-user.target.wants/bar.service\n"
+        );
+        // This is not also reported as a generic broken link
+        check_etc_broken_symlinks(root).unwrap().unwrap();
+        // But a broken alias directly in the unit directory is
+        root.symlink_contents(
+            "/usr/lib/systemd/system/bar.service",
+            "etc/systemd/system/alias.service",
+        )?;
+        let Err(e) = check_etc_broken_symlinks(root).unwrap() else {
+            unreachable!()
+        };
+        similar_asserts::assert_eq!(
+            e.to_string(),
+            "Found broken symbolic links in /etc:\n  /etc/systemd/system/alias.service\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_etc_fstab_usr() -> Result<()> {
+        let root = &fixture()?;
+        check_etc_fstab_usr(root).unwrap().unwrap();
+        root.create_dir_all("etc")?;
+        root.write(
+            "etc/fstab",
+            indoc! { "
+            # /usr/share/foo is a comment
+            UUID=6907-17CA /boot/efi vfat umask=0077 0 2
+            /dev/vdb /usrdata xfs defaults 0 0
+            " },
+        )?;
+        check_etc_fstab_usr(root).unwrap().unwrap();
+        root.write("etc/fstab", "/dev/vdb /usr/share/data xfs defaults 0 0\n")?;
+        let Err(e) = check_etc_fstab_usr(root).unwrap() else {
+            unreachable!()
+        };
+        similar_asserts::assert_eq!(
+            e.to_string(),
+            "Found /etc/fstab entries with mount points under read-only /usr:\n  /usr/share/data\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_systemd_units() -> Result<()> {
+        let root = &fixture()?;
+        check_systemd_units(root).unwrap().unwrap();
+        root.create_dir_all("usr/bin")?;
+        root.write("usr/bin/foo", "#!/bin/sh\n")?;
+        root.create_dir_all("usr/lib/systemd/system/foo.service.d")?;
+        root.write(
+            "usr/lib/systemd/system/foo.service",
+            indoc! { r#"
+            # A comment
+            [Unit]
+            Description=Foo
+            ConditionPathExists=/etc/foo
+            X-Custom=bar
+
+            [Service]
+            ExecStartPre=-foo --prepare
+            ExecStart=/usr/bin/foo \
+              --verbose
+            ExecStop=${STOP_CMD} --now
+            ExecReload=%h/reload
+
+            [Install]
+            WantedBy=multi-user.target
+            "# },
+        )?;
+        root.write(
+            "usr/lib/systemd/system/foo.service.d/10-env.conf",
+            "[Service]\nEnvironment=FOO=1\n",
+        )?;
+        check_systemd_units(root).unwrap().unwrap();
+
+        root.create_dir_all("etc/systemd/system")?;
+        root.write(
+            "etc/systemd/system/bar.service",
+            indoc! { r#"
+            Description=Bar
+            [Unit]
+            Desciption=Bar
+            [Servce]
+            ExecStart=/usr/bin/bar
+            [Service
+            ExecStart
+            "# },
+        )?;
+        let Err(e) = check_systemd_units(root).unwrap() else {
+            unreachable!()
+        };
+        similar_asserts::assert_eq!(
+            e.to_string(),
+            indoc! { r#"
+            Found problems in systemd units:
+              /etc/systemd/system/bar.service:1: Assignment outside of a section: Description
+              /etc/systemd/system/bar.service:3: Unknown directive Desciption in section [Unit]
+              /etc/systemd/system/bar.service:4: Unknown section [Servce]
+              /etc/systemd/system/bar.service:5: ExecStart: /usr/bin/bar not found
+              /etc/systemd/system/bar.service:6: Invalid section header: [Service
+              ...and 1 more
+            "# }
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_tmpfiles_sysusers_syntax() -> Result<()> {
+        let root = &fixture()?;
+        check_tmpfiles_syntax(root).unwrap().unwrap();
+        check_sysusers_syntax(root).unwrap().unwrap();
+        root.create_dir_all("usr/lib/tmpfiles.d")?;
+        root.write("usr/lib/tmpfiles.d/foo.conf", "d /var/lib/foo 0755 - - -\n")?;
+        root.create_dir_all("usr/lib/sysusers.d")?;
+        root.write("usr/lib/sysusers.d/foo.conf", "g foo -\n")?;
+        check_tmpfiles_syntax(root).unwrap().unwrap();
+        check_sysusers_syntax(root).unwrap().unwrap();
+
+        root.write("usr/lib/tmpfiles.d/foo.conf", "d var/lib/foo 0755 - - -\n")?;
+        root.write("usr/lib/sysusers.d/foo.conf", "q foo -\n")?;
+        let Err(e) = check_tmpfiles_syntax(root).unwrap() else {
+            unreachable!()
+        };
+        similar_asserts::assert_eq!(
+            e.to_string(),
+            "Found invalid systemd tmpfiles.d entries:\n  /usr/lib/tmpfiles.d/foo.conf:1: Malformed tmpfiles.d line d var/lib/foo 0755 - - -\n"
+        );
+        let Err(e) = check_sysusers_syntax(root).unwrap() else {
+            unreachable!()
+        };
+        similar_asserts::assert_eq!(
+            e.to_string(),
+            "Found invalid systemd sysusers.d entries:\n  /usr/lib/sysusers.d/foo.conf:1: Failed to parse sysusers entry: q foo -\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_bound_images() -> Result<()> {
+        const BOUND_IMAGE_DIR: &str = "usr/lib/bootc/bound-images.d";
+        const CONTAINER_IMAGE_DIR: &str = "usr/share/containers/systemd";
+        let root = &fixture()?;
+        check_bound_images(root).unwrap().unwrap();
+        check_bound_images_references(root).unwrap().unwrap();
+        check_bound_images_digest(root).unwrap().unwrap();
+
+        root.create_dir_all(BOUND_IMAGE_DIR)?;
+        root.create_dir_all(CONTAINER_IMAGE_DIR)?;
+        root.write(
+            format!("{CONTAINER_IMAGE_DIR}/foo.image"),
+            "[Image]\nImage=quay.io/foo/foo@sha256:0000000000000000000000000000000000000000000000000000000000000000\n",
+        )?;
+        root.symlink_contents(
+            format!("/{CONTAINER_IMAGE_DIR}/foo.image"),
+            format!("{BOUND_IMAGE_DIR}/foo.image"),
+        )?;
+        check_bound_images(root).unwrap().unwrap();
+        check_bound_images_references(root).unwrap().unwrap();
+        check_bound_images_digest(root).unwrap().unwrap();
+
+        root.write(
+            format!("{CONTAINER_IMAGE_DIR}/bar.container"),
+            "[Container]\nImage=bar:latest\n",
+        )?;
+        root.symlink_contents(
+            format!("/{CONTAINER_IMAGE_DIR}/bar.container"),
+            format!("{BOUND_IMAGE_DIR}/bar.container"),
+        )?;
+        root.symlink_contents(
+            format!("/{CONTAINER_IMAGE_DIR}/missing.image"),
+            format!("{BOUND_IMAGE_DIR}/missing.image"),
+        )?;
+        let Err(e) = check_bound_images(root).unwrap() else {
+            unreachable!()
+        };
+        let e = e.to_string();
+        assert!(e.starts_with("Found invalid bound images:\n"), "{e}");
+        assert!(
+            e.contains("  missing.image: Reading usr/lib/bootc/bound-images.d/missing.image"),
+            "{e}"
+        );
+        assert!(!e.contains("foo.image"), "{e}");
+        assert!(!e.contains("bar.container"), "{e}");
+
+        root.write(
+            format!("{CONTAINER_IMAGE_DIR}/baz.image"),
+            "[Image]\nImage=quay.io/baz/baz:latest\nAuthFile=/etc/baz-auth.json\n",
+        )?;
+        root.symlink_contents(
+            format!("/{CONTAINER_IMAGE_DIR}/baz.image"),
+            format!("{BOUND_IMAGE_DIR}/baz.image"),
+        )?;
+        let Err(e) = check_bound_images_references(root).unwrap() else {
+            unreachable!()
+        };
+        similar_asserts::assert_eq!(
+            e.to_string(),
+            indoc! { r#"
+            Found bound image references which may not resolve:
+              bar.container: Image is not fully qualified: bar:latest
+              baz.image: AuthFile not found: /etc/baz-auth.json
+            "# }
+        );
+
+        let Err(e) = check_bound_images_digest(root).unwrap() else {
+            unreachable!()
+        };
+        similar_asserts::assert_eq!(
+            e.to_string(),
+            indoc! { r#"
+            Found bound images not pinned by digest:
+              bar.container: bar:latest
+              baz.image: quay.io/baz/baz:latest
+            "# }
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_varlog() -> Result<()> {
         let root = &fixture()?;
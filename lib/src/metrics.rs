@@ -0,0 +1,309 @@
+//! Prometheus metrics describing the host state
+//!
+//! The metrics are output in the Prometheus text exposition format, suitable
+//! for e.g. the node_exporter textfile collector.
+
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use camino::Utf8Path;
+use cap_std_ext::cap_std::fs::Dir;
+use cap_std_ext::dirext::CapStdExtDirExt;
+use chrono::{DateTime, Utc};
+use fn_error_context::context;
+use serde::{Deserialize, Serialize};
+
+use crate::spec::{Host, ImageStatus};
+use crate::store::Storage;
+
+/// Information about the last fetch from the registry, relative to the physical root.
+/// This is in a subdirectory so that updating it does not change the mtime of the
+/// bootc root directory, which would trigger `bootc-status-updated.path`.
+const FETCH_STATE_PATH: &str = "ostree/bootc/state/fetch.json";
+
+/// Information about fetches from the registry, which is not part of the host status.
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct FetchState {
+    /// The time of the last successful check for updates
+    last_check: Option<DateTime<Utc>>,
+    /// The number of bytes downloaded by the last pull
+    last_pull_bytes: Option<u64>,
+}
+
+fn load_fetch_state(sysroot_dir: &Dir) -> Result<FetchState> {
+    let Some(f) = sysroot_dir.open_optional(FETCH_STATE_PATH)? else {
+        return Ok(Default::default());
+    };
+    serde_json::from_reader(std::io::BufReader::new(f)).context("Parsing fetch state")
+}
+
+#[context("Updating fetch state")]
+fn update_fetch_state_impl(sysroot: &Storage, f: impl FnOnce(&mut FetchState)) -> Result<()> {
+    let sysroot_dir = crate::utils::sysroot_dir(sysroot)?;
+    let mut state = load_fetch_state(&sysroot_dir)?;
+    f(&mut state);
+    if let Some(parent) = Utf8Path::new(FETCH_STATE_PATH).parent() {
+        sysroot_dir.create_dir_all(parent)?;
+    }
+    sysroot_dir.atomic_replace_with(FETCH_STATE_PATH, |w| {
+        serde_json::to_writer(w, &state)?;
+        anyhow::Ok(())
+    })?;
+    Ok(())
+}
+
+/// Record a successful check for updates; if an image was pulled,
+/// `pulled_bytes` is the number of bytes downloaded.
+/// This is only used for metrics, so failures are logged, not propagated.
+pub(crate) fn record_fetch(sysroot: &Storage, check: bool, pulled_bytes: Option<u64>) {
+    let r = update_fetch_state_impl(sysroot, |state| {
+        if check {
+            state.last_check = Some(Utc::now());
+        }
+        if let Some(bytes) = pulled_bytes {
+            state.last_pull_bytes = Some(bytes);
+        }
+    });
+    if let Err(e) = r {
+        tracing::warn!("{e:#}");
+    }
+}
+
+/// Escape a label value.
+fn escape_label(s: &str) -> String {
+    s.replace('\\', r"\\")
+        .replace('"', r#"\""#)
+        .replace('\n', r"\n")
+}
+
+/// Write the `HELP` and `TYPE` lines for a gauge.
+fn write_gauge_header(mut out: impl Write, name: &str, help: &str) -> Result<()> {
+    writeln!(out, "# HELP {name} {help}")?;
+    writeln!(out, "# TYPE {name} gauge")?;
+    Ok(())
+}
+
+/// Write all metrics.
+fn write_metrics(
+    mut out: impl Write,
+    host: &Host,
+    fetch: &FetchState,
+    now: DateTime<Utc>,
+) -> Result<()> {
+    let status = &host.status;
+    let slots: [(&str, Option<&ImageStatus>); 4] = [
+        (
+            "staged",
+            status.staged.as_ref().and_then(|e| e.image.as_ref()),
+        ),
+        (
+            "booted",
+            status.booted.as_ref().and_then(|e| e.image.as_ref()),
+        ),
+        (
+            "rollback",
+            status.rollback.as_ref().and_then(|e| e.image.as_ref()),
+        ),
+        (
+            "available",
+            status
+                .booted
+                .as_ref()
+                .and_then(|e| e.cached_update.as_ref()),
+        ),
+    ];
+    let slots = slots
+        .into_iter()
+        .filter_map(|(slot, image)| image.map(|i| (slot, i)))
+        .collect::<Vec<_>>();
+
+    write_gauge_header(
+        &mut out,
+        "bootc_image_info",
+        "The container images of the host; the value is always 1.",
+    )?;
+    for (slot, image) in slots.iter() {
+        writeln!(
+            out,
+            "bootc_image_info{{slot=\"{slot}\",image=\"{}\",digest=\"{}\",version=\"{}\"}} 1",
+            escape_label(&image.image.image),
+            escape_label(&image.image_digest),
+            escape_label(image.version.as_deref().unwrap_or_default())
+        )?;
+    }
+
+    write_gauge_header(
+        &mut out,
+        "bootc_image_build_timestamp_seconds",
+        "The build timestamp of the container images, in seconds since the epoch.",
+    )?;
+    for (slot, image) in slots.iter() {
+        if let Some(ts) = image.timestamp {
+            writeln!(
+                out,
+                "bootc_image_build_timestamp_seconds{{slot=\"{slot}\"}} {}",
+                ts.timestamp()
+            )?;
+        }
+    }
+    write_gauge_header(
+        &mut out,
+        "bootc_image_age_seconds",
+        "The age of the container images when the metrics were generated.",
+    )?;
+    for (slot, image) in slots.iter() {
+        if let Some(ts) = image.timestamp {
+            writeln!(
+                out,
+                "bootc_image_age_seconds{{slot=\"{slot}\"}} {}",
+                (now - ts).num_seconds()
+            )?;
+        }
+    }
+
+    write_gauge_header(
+        &mut out,
+        "bootc_staged_update_pending",
+        "Whether an update is staged for the next boot.",
+    )?;
+    writeln!(
+        out,
+        "bootc_staged_update_pending {}",
+        u8::from(status.staged.is_some())
+    )?;
+    write_gauge_header(
+        &mut out,
+        "bootc_update_available",
+        "Whether a cached update is available to download.",
+    )?;
+    let available = status
+        .booted
+        .as_ref()
+        .is_some_and(|b| b.cached_update.is_some());
+    writeln!(out, "bootc_update_available {}", u8::from(available))?;
+
+    if let Some(last_check) = fetch.last_check {
+        write_gauge_header(
+            &mut out,
+            "bootc_last_check_timestamp_seconds",
+            "The time of the last successful check for updates, in seconds since the epoch.",
+        )?;
+        writeln!(
+            out,
+            "bootc_last_check_timestamp_seconds {}",
+            last_check.timestamp()
+        )?;
+        write_gauge_header(
+            &mut out,
+            "bootc_last_check_age_seconds",
+            "The time since the last successful check for updates when the metrics were generated.",
+        )?;
+        writeln!(
+            out,
+            "bootc_last_check_age_seconds {}",
+            (now - last_check).num_seconds()
+        )?;
+    }
+    if let Some(bytes) = fetch.last_pull_bytes {
+        write_gauge_header(
+            &mut out,
+            "bootc_last_pull_bytes",
+            "The number of bytes downloaded by the last image pull.",
+        )?;
+        writeln!(out, "bootc_last_pull_bytes {bytes}")?;
+    }
+    Ok(())
+}
+
+/// Implementation of `bootc internals metrics`: output the metrics to stdout, or
+/// atomically replace `textfile`.
+#[context("Generating metrics")]
+pub(crate) async fn metrics(textfile: Option<&Utf8Path>) -> Result<()> {
+    let sysroot = super::cli::get_storage().await?;
+    let booted_deployment = sysroot.booted_deployment();
+    let (_deployments, host) = crate::status::get_status(&sysroot, booted_deployment.as_ref())?;
+    let fetch = load_fetch_state(&crate::utils::sysroot_dir(&sysroot)?)?;
+    let now = Utc::now();
+
+    match textfile {
+        Some(path) => {
+            let (dir, name) = path
+                .parent()
+                .zip(path.file_name())
+                .ok_or_else(|| anyhow::anyhow!("Invalid path: {path}"))?;
+            let dir = Dir::open_ambient_dir(dir, cap_std_ext::cap_std::ambient_authority())
+                .with_context(|| format!("Opening {dir}"))?;
+            dir.atomic_replace_with(name, |w| write_metrics(w, &host, &fetch, now))
+                .with_context(|| format!("Writing {path}"))?;
+        }
+        None => {
+            let mut out = std::io::stdout().lock();
+            write_metrics(&mut out, &host, &fetch, now)?;
+            out.flush()?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape_label() {
+        assert_eq!(escape_label("quay.io/foo:bar"), "quay.io/foo:bar");
+        assert_eq!(escape_label("a\"b\\c\nd"), r#"a\"b\\c\nd"#);
+    }
+
+    #[test]
+    fn test_write_metrics() -> Result<()> {
+        let host: Host = serde_yaml::from_str(include_str!("fixtures/spec-staged-booted.yaml"))?;
+        let now = "2023-10-15T19:22:15Z".parse::<DateTime<Utc>>()?;
+        let fetch = FetchState {
+            last_check: Some("2023-10-15T18:22:15Z".parse()?),
+            last_pull_bytes: Some(42),
+        };
+        let mut w = Vec::new();
+        write_metrics(&mut w, &host, &fetch, now)?;
+        let w = String::from_utf8(w)?;
+        let expected = indoc::indoc! { r#"
+            # HELP bootc_image_info The container images of the host; the value is always 1.
+            # TYPE bootc_image_info gauge
+            bootc_image_info{slot="staged",image="quay.io/example/someimage:latest",digest="sha256:16dc2b6256b4ff0d2ec18d2dbfb06d117904010c8cf9732cdb022818cf7a7566",version="nightly"} 1
+            bootc_image_info{slot="booted",image="quay.io/example/someimage:latest",digest="sha256:736b359467c9437c1ac915acaae952aad854e07eb4a16a94999a48af08c83c34",version="nightly"} 1
+            # HELP bootc_image_build_timestamp_seconds The build timestamp of the container images, in seconds since the epoch.
+            # TYPE bootc_image_build_timestamp_seconds gauge
+            bootc_image_build_timestamp_seconds{slot="staged"} 1697311335
+            bootc_image_build_timestamp_seconds{slot="booted"} 1696101736
+            # HELP bootc_image_age_seconds The age of the container images when the metrics were generated.
+            # TYPE bootc_image_age_seconds gauge
+            bootc_image_age_seconds{slot="staged"} 86400
+            bootc_image_age_seconds{slot="booted"} 1295999
+            # HELP bootc_staged_update_pending Whether an update is staged for the next boot.
+            # TYPE bootc_staged_update_pending gauge
+            bootc_staged_update_pending 1
+            # HELP bootc_update_available Whether a cached update is available to download.
+            # TYPE bootc_update_available gauge
+            bootc_update_available 0
+            # HELP bootc_last_check_timestamp_seconds The time of the last successful check for updates, in seconds since the epoch.
+            # TYPE bootc_last_check_timestamp_seconds gauge
+            bootc_last_check_timestamp_seconds 1697394135
+            # HELP bootc_last_check_age_seconds The time since the last successful check for updates when the metrics were generated.
+            # TYPE bootc_last_check_age_seconds gauge
+            bootc_last_check_age_seconds 3600
+            # HELP bootc_last_pull_bytes The number of bytes downloaded by the last image pull.
+            # TYPE bootc_last_pull_bytes gauge
+            bootc_last_pull_bytes 42
+        "#};
+        similar_asserts::assert_eq!(w, expected);
+
+        // Without any fetch state, those metrics are omitted
+        let mut w = Vec::new();
+        write_metrics(&mut w, &host, &FetchState::default(), now)?;
+        let w = String::from_utf8(w)?;
+        assert!(!w.contains("bootc_last_check"));
+        assert!(!w.contains("bootc_last_pull_bytes"));
+        Ok(())
+    }
+}
@@ -9,6 +9,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::k8sapitypes;
 
+pub mod v2;
+
 const API_VERSION: &str = "org.containers.bootc/v1";
 const KIND: &str = "BootcHost";
 /// The default object name we use; there's only one.
@@ -62,6 +64,32 @@ pub struct HostSpec {
     /// If set, and there is a rollback deployment, it will be set for the next boot.
     #[serde(default)]
     pub boot_order: BootOrder,
+    /// Kernel arguments managed locally on this host, in addition to those
+    /// provided by the image.
+    #[serde(default, skip_serializing_if = "LocalKargs::is_empty")]
+    pub kargs: LocalKargs,
+}
+
+/// Kernel arguments managed locally on the host (e.g. via `bootc kargs`); these
+/// are applied on top of the kernel arguments from the image, and are carried
+/// across upgrades.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LocalKargs {
+    /// Kernel arguments to add.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub append: Vec<String>,
+    /// Kernel arguments to remove. An argument without a value (e.g. `console`)
+    /// removes all instances of that key.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub delete: Vec<String>,
+}
+
+impl LocalKargs {
+    /// Returns true if there are no locally managed kernel arguments.
+    pub fn is_empty(&self) -> bool {
+        self.append.is_empty() && self.delete.is_empty()
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
@@ -132,6 +160,92 @@ pub struct BootEntry {
     pub ostree: Option<BootEntryOstree>,
 }
 
+/// The changes between two boot entries.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BootEntryDiff {
+    /// The container image layers
+    pub layers: Option<LayersDiff>,
+    /// The components (e.g. packages) contained in changed layers, as named by the
+    /// `ostree.components` layer annotation of chunked images.
+    pub components: Option<NamesDiff>,
+    /// The kernel arguments
+    pub kargs: NamesDiff,
+    /// The files and directories in `/usr`; directories have a trailing `/`,
+    /// and added or removed directories include their contents.
+    pub usr: NamesDiff,
+}
+
+/// The changes in container image layers.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct LayersDiff {
+    /// The number of layers in the new image
+    pub total: u64,
+    /// The total size of the layers in the new image
+    pub total_size: u64,
+    /// The number of layers which were added
+    pub added: u64,
+    /// The size of the layers which were added
+    pub added_size: u64,
+    /// The number of layers which were removed
+    pub removed: u64,
+    /// The size of the layers which were removed
+    pub removed_size: u64,
+}
+
+/// The changes in a set of names (e.g. packages or paths).
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct NamesDiff {
+    /// Names which were added
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub added: Vec<String>,
+    /// Names which were removed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub removed: Vec<String>,
+    /// Names which are present in both, but were changed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub changed: Vec<String>,
+}
+
+/// A logically bound container image.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BoundImageStatus {
+    /// The image reference
+    pub image: String,
+    /// The manifest digest of the image, as resolved when the boot entry was staged;
+    /// unset if it was not recorded.
+    pub digest: Option<String>,
+}
+
+/// The kernel arguments of a boot entry, split by where they came from.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BootEntryKargs {
+    /// Kernel arguments provided by the image in `/usr/lib/bootc/kargs.d`
+    pub image: Vec<String>,
+    /// The files in `/usr/lib/bootc/kargs.d`, and whether they apply to this host
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<KargsFileStatus>,
+    /// Kernel arguments managed locally on the host
+    #[serde(default)]
+    pub local: LocalKargs,
+}
+
+/// A kargs.d file, and whether its `match-*` conditions apply to this host.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct KargsFileStatus {
+    /// The file name
+    pub name: String,
+    /// Whether the kernel arguments in the file apply
+    pub matched: bool,
+    /// A description of why the file applies or not
+    pub reason: String,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
 #[serde(rename_all = "camelCase")]
 #[non_exhaustive]
@@ -192,9 +306,13 @@ impl HostSpec {
     pub(crate) fn verify_transition(&self, new: &Self) -> anyhow::Result<()> {
         let rollback = self.boot_order != new.boot_order;
         let image_change = self.image != new.image;
+        let kargs_change = self.kargs != new.kargs;
         if rollback && image_change {
             anyhow::bail!("Invalid state transition: rollback and image change");
         }
+        if rollback && kargs_change {
+            anyhow::bail!("Invalid state transition: rollback and kernel argument change");
+        }
         Ok(())
     }
 }
@@ -0,0 +1,181 @@
+//! Version 2 of the host status, which adds more details about each boot entry.
+//!
+//! The spec is unchanged from version 1.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    BootEntryDiff, BootEntryKargs, BoundImageStatus, HostSpec, HostType, NamesDiff, KIND,
+    OBJECT_NAME,
+};
+use crate::k8sapitypes;
+
+const API_VERSION: &str = "org.containers.bootc/v2";
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+/// The core host definition
+pub struct Host {
+    /// Metadata
+    #[serde(flatten)]
+    pub resource: k8sapitypes::Resource,
+    /// The spec
+    #[serde(default)]
+    pub spec: HostSpec,
+    /// The status
+    #[serde(default)]
+    pub status: HostStatus,
+}
+
+/// A bootable entry
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct BootEntry {
+    /// The fields of a version 1 boot entry
+    #[serde(flatten)]
+    pub entry: super::BootEntry,
+    /// The stateroot (operating system name) of this boot entry
+    pub stateroot: String,
+    /// The index of this boot entry in the ostree deployment list
+    pub deploy_index: u32,
+    /// The time this boot entry was deployed, if known
+    pub timestamp: Option<chrono::DateTime<chrono::Utc>>,
+    /// The version of the kernel, as found in `/usr/lib/modules`
+    pub kernel_version: Option<String>,
+    /// The full kernel command line of this boot entry
+    pub cmdline: Vec<String>,
+    /// Whether the host can switch to this boot entry via a soft reboot,
+    /// i.e. it is not booted and has the same kernel, initramfs and command line
+    /// as the booted entry.
+    pub soft_reboot_capable: bool,
+    /// The local changes of `/etc`; this is not computed for the staged entry,
+    /// as its configuration is only merged on shutdown.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub etc_drift: Option<EtcDriftSummary>,
+    /// The kernel arguments of this boot entry
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kargs: Option<BootEntryKargs>,
+    /// The logically bound images of this boot entry
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub bound_images: Vec<BoundImageStatus>,
+    /// For the staged entry, the changes relative to the booted entry; only
+    /// computed on request (`bootc status --diff`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff: Option<BootEntryDiff>,
+}
+
+/// A summary of the changes of `/etc` relative to the image defaults in `/usr/etc`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct EtcDriftSummary {
+    /// The number of files and directories which were added
+    pub added: u64,
+    /// The number of files and directories which were removed
+    pub removed: u64,
+    /// The number of files and directories which were changed
+    pub changed: u64,
+}
+
+/// The status of the host system
+#[derive(Debug, Clone, Serialize, Default, Deserialize, PartialEq, Eq, JsonSchema)]
+#[serde(rename_all = "camelCase")]
+pub struct HostStatus {
+    /// The staged image for the next boot
+    pub staged: Option<BootEntry>,
+    /// The booted image; this will be unset if the host is not bootc compatible.
+    pub booted: Option<BootEntry>,
+    /// The previously booted image
+    pub rollback: Option<BootEntry>,
+    /// Set to true if the rollback entry is queued for the next boot.
+    #[serde(default)]
+    pub rollback_queued: bool,
+
+    /// The detected type of system
+    #[serde(rename = "type")]
+    pub ty: Option<HostType>,
+}
+
+impl From<&NamesDiff> for EtcDriftSummary {
+    fn from(diff: &NamesDiff) -> Self {
+        // SAFETY: usize always fits in u64
+        let len = |v: &Vec<String>| u64::try_from(v.len()).unwrap();
+        Self {
+            added: len(&diff.added),
+            removed: len(&diff.removed),
+            changed: len(&diff.changed),
+        }
+    }
+}
+
+impl Host {
+    /// Create a new host
+    pub fn new(spec: HostSpec) -> Self {
+        let metadata = k8sapitypes::ObjectMeta {
+            name: Some(OBJECT_NAME.to_owned()),
+            ..Default::default()
+        };
+        Self {
+            resource: k8sapitypes::Resource {
+                api_version: API_VERSION.to_owned(),
+                kind: KIND.to_owned(),
+                metadata,
+            },
+            spec,
+            status: Default::default(),
+        }
+    }
+}
+
+impl Default for Host {
+    fn default() -> Self {
+        Self::new(Default::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_spec_v2() {
+        const SPEC_FIXTURE: &str = include_str!("../fixtures/spec-v2-booted.yaml");
+        let host: Host = serde_yaml::from_str(SPEC_FIXTURE).unwrap();
+        assert_eq!(host.resource.api_version, API_VERSION);
+        let booted = host.status.booted.as_ref().unwrap();
+        assert_eq!(booted.stateroot, "default");
+        assert_eq!(
+            booted.kernel_version.as_deref(),
+            Some("6.11.3-200.fc41.x86_64")
+        );
+        assert_eq!(booted.etc_drift.as_ref().unwrap().changed, 2);
+        assert_eq!(booted.kargs.as_ref().unwrap().image, ["console=ttyS0"]);
+        // The v1 fields are flattened in
+        assert_eq!(booted.entry.ostree.as_ref().unwrap().deploy_serial, 0);
+        let rollback = host.status.rollback.as_ref().unwrap();
+        assert!(rollback.soft_reboot_capable);
+        assert!(rollback.etc_drift.is_none());
+
+        // And roundtrip
+        let s = serde_yaml::to_string(&host).unwrap();
+        let host2: Host = serde_yaml::from_str(&s).unwrap();
+        assert_eq!(host, host2);
+    }
+
+    #[test]
+    fn test_etc_drift_summary() {
+        let diff = NamesDiff {
+            added: vec!["/etc/a".into(), "/etc/b/".into()],
+            removed: Vec::new(),
+            changed: vec!["/etc/c".into()],
+        };
+        assert_eq!(
+            EtcDriftSummary::from(&diff),
+            EtcDriftSummary {
+                added: 2,
+                removed: 0,
+                changed: 1
+            }
+        );
+    }
+}
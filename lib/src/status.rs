@@ -1,10 +1,13 @@
 use std::borrow::Cow;
-use std::collections::VecDeque;
+use std::collections::{BTreeMap, VecDeque};
 use std::io::IsTerminal;
 use std::io::Read;
 use std::io::Write;
+use std::time::Duration;
 
 use anyhow::{Context, Result};
+use cap_std_ext::cap_std::fs::Dir;
+use cap_std_ext::dirext::CapStdExtDirExt;
 use fn_error_context::context;
 use ostree::glib;
 use ostree_container::OstreeImageReference;
@@ -13,12 +16,23 @@ use ostree_ext::container_utils::ostree_booted;
 use ostree_ext::keyfileext::KeyFileExt;
 use ostree_ext::oci_spec;
 use ostree_ext::ostree;
+use serde::Serialize;
 
 use crate::cli::OutputFormat;
-use crate::spec::{BootEntry, BootOrder, Host, HostSpec, HostStatus, HostType};
-use crate::spec::{ImageReference, ImageSignature};
+use crate::kargs::HostFacts;
+use crate::spec::{v2, ImageReference, ImageSignature};
+use crate::spec::{
+    BootEntry, BootEntryDiff, BootEntryKargs, BootOrder, Host, HostSpec, HostStatus, HostType,
+    KargsFileStatus, LayersDiff, NamesDiff,
+};
 use crate::store::{CachedImageStatus, ContainerImageStore, Storage};
 
+/// How often `bootc status --watch` checks for changes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+/// The prefix of the kernel argument which selects the deployment; this differs
+/// between all deployments.
+pub(crate) const OSTREE_KARG_PREFIX: &str = "ostree=";
+
 impl From<ostree_container::SignatureSource> for ImageSignature {
     fn from(sig: ostree_container::SignatureSource) -> Self {
         use ostree_container::SignatureSource;
@@ -168,6 +182,253 @@ fn boot_entry_from_deployment(
     Ok(r)
 }
 
+/// Map each component named by the layer annotations of a chunked image to the
+/// digest of its layer.
+fn components_of(manifest: &oci_spec::image::ImageManifest) -> BTreeMap<&str, &str> {
+    manifest
+        .layers()
+        .iter()
+        .flat_map(|layer| {
+            let digest = layer.digest().as_ref();
+            layer
+                .annotations()
+                .as_ref()
+                .and_then(|a| a.get(ostree_container::CONTENT_ANNOTATION))
+                .into_iter()
+                .flat_map(|v| v.split(ostree_container::COMPONENT_SEPARATOR))
+                .map(move |c| (c, digest))
+        })
+        .collect()
+}
+
+/// Compute which components were added, removed, or are in a changed layer.
+/// Returns `None` if neither image has component annotations.
+fn components_diff(
+    from: &oci_spec::image::ImageManifest,
+    to: &oci_spec::image::ImageManifest,
+) -> Option<NamesDiff> {
+    let (from, to) = (components_of(from), components_of(to));
+    if from.is_empty() && to.is_empty() {
+        return None;
+    }
+    let mut r = NamesDiff::default();
+    for (&name, &digest) in to.iter() {
+        match from.get(name) {
+            None => r.added.push(name.to_owned()),
+            Some(&prev) if prev != digest => r.changed.push(name.to_owned()),
+            Some(_) => {}
+        }
+    }
+    r.removed = from
+        .keys()
+        .filter(|&name| !to.contains_key(name))
+        .map(|&name| name.to_owned())
+        .collect();
+    Some(r)
+}
+
+/// Compute the kernel arguments added and removed; the `ostree=` argument is
+/// ignored as it always differs.
+fn kargs_diff(from: &[String], to: &[String]) -> NamesDiff {
+    let only_in = |a: &[String], b: &[String]| {
+        a.iter()
+            .filter(|k| !k.starts_with(OSTREE_KARG_PREFIX) && !b.contains(k))
+            .cloned()
+            .collect()
+    };
+    NamesDiff {
+        added: only_in(to, from),
+        removed: only_in(from, to),
+        changed: Vec::new(),
+    }
+}
+
+/// Return the kernel arguments of a deployment.
+pub(crate) fn deployment_kargs(deployment: &ostree::Deployment) -> Vec<String> {
+    deployment
+        .bootconfig()
+        .and_then(|bootconfig| bootconfig.get("options"))
+        .map(|options| crate::kernel::Cmdline::parse(&options).into_args())
+        .unwrap_or_default()
+}
+
+/// Compute the changes between the booted and staged deployments.
+#[context("Computing staged changes")]
+fn deployment_diff(
+    repo: &ostree::Repo,
+    booted: (&ostree::Deployment, &BootEntry),
+    staged: (&ostree::Deployment, &BootEntry),
+) -> Result<BootEntryDiff> {
+    let (booted_deployment, booted_entry) = booted;
+    let (staged_deployment, staged_entry) = staged;
+    let images = booted_entry
+        .query_image(repo)?
+        .zip(staged_entry.query_image(repo)?);
+    let (layers, components) = if let Some((from, to)) = images.as_ref() {
+        let d = ostree_container::ManifestDiff::new(&from.manifest, &to.manifest);
+        let layers = LayersDiff {
+            total: d.total,
+            total_size: d.total_size,
+            added: d.n_added,
+            added_size: d.added_size,
+            removed: d.n_removed,
+            removed_size: d.removed_size,
+        };
+        (Some(layers), components_diff(&from.manifest, &to.manifest))
+    } else {
+        (None, None)
+    };
+    let kargs = kargs_diff(
+        &deployment_kargs(booted_deployment),
+        &deployment_kargs(staged_deployment),
+    );
+    let usrdiff = ostree_ext::diff::diff(
+        repo,
+        &booted_deployment.csum(),
+        &staged_deployment.csum(),
+        Some("/usr"),
+    )?;
+    let files = |f: &ostree_ext::diff::FileSet, d: &ostree_ext::diff::FileSet| {
+        let mut r = f
+            .iter()
+            .map(|p| format!("/usr{p}"))
+            .chain(d.iter().map(|p| format!("/usr{p}/")))
+            .collect::<Vec<_>>();
+        r.sort();
+        r
+    };
+    let usr = NamesDiff {
+        added: files(&usrdiff.added_files, &usrdiff.added_dirs),
+        removed: files(&usrdiff.removed_files, &usrdiff.removed_dirs),
+        changed: files(&usrdiff.changed_files, &usrdiff.changed_dirs),
+    };
+    Ok(BootEntryDiff {
+        layers,
+        components,
+        kargs,
+        usr,
+    })
+}
+
+/// Returns true if the host can switch from the booted deployment to `deployment`
+/// via a soft reboot, i.e. it has the same kernel, initramfs and command line.
+fn soft_reboot_capable(booted: &ostree::Deployment, deployment: &ostree::Deployment) -> bool {
+    let kargs = |d: &ostree::Deployment| {
+        deployment_kargs(d)
+            .into_iter()
+            .filter(|k| !k.starts_with(OSTREE_KARG_PREFIX))
+            .collect::<Vec<_>>()
+    };
+    !deployment.equal(booted)
+        && deployment.bootcsum() == booted.bootcsum()
+        && kargs(deployment) == kargs(booted)
+}
+
+/// Add the details of the version 2 status to a boot entry.
+#[context("Reading deployment details")]
+fn boot_entry_v2(
+    sysroot: &Storage,
+    deployment: &ostree::Deployment,
+    booted_deployment: Option<&ostree::Deployment>,
+    facts: &HostFacts,
+    entry: BootEntry,
+) -> Result<v2::BootEntry> {
+    let root = crate::utils::deployment_fd(sysroot, deployment)?;
+    // The deployment directory is created when deploying; prefer its birth time
+    // if supported by the filesystem.
+    let meta = root.dir_metadata()?;
+    let timestamp = meta.created().or_else(|_| meta.modified())?;
+    let kernel_version = ostree_ext::bootabletree::find_kernel_dir_fs(&root)?
+        .and_then(|d| d.file_name().map(ToOwned::to_owned));
+    let etc_drift = if deployment.is_staged() {
+        None
+    } else {
+        crate::etcdiff::diff(&root)?.as_ref().map(Into::into)
+    };
+    let kargs = if entry.image.is_some() {
+        let local = deployment
+            .origin()
+            .map(|origin| crate::kargs::local_kargs_from_origin(&origin))
+            .transpose()?
+            .unwrap_or_default();
+        let files = crate::kargs::get_kargs_files_in_root(&root, facts)?;
+        // Prefer the kargs.d arguments which were actually applied
+        let recorded = deployment
+            .origin()
+            .map(|origin| crate::kargs::image_kargs_from_origin(&origin))
+            .transpose()?
+            .flatten();
+        let image = recorded.unwrap_or_else(|| {
+            files
+                .iter()
+                .filter(|f| f.matched)
+                .flat_map(|f| f.kargs.iter().cloned())
+                .collect()
+        });
+        let files = files
+            .into_iter()
+            .map(|f| KargsFileStatus {
+                name: f.name,
+                matched: f.matched,
+                reason: f.reason,
+            })
+            .collect();
+        Some(BootEntryKargs {
+            image,
+            files,
+            local,
+        })
+    } else {
+        None
+    };
+    let bound_images = if entry.image.is_some() {
+        crate::boundimage::bound_images_status(sysroot, deployment)?
+    } else {
+        Vec::new()
+    };
+    Ok(v2::BootEntry {
+        entry,
+        stateroot: deployment.osname().into(),
+        deploy_index: deployment
+            .index()
+            .try_into()
+            .context("Invalid deployment index")?,
+        timestamp: Some(timestamp.into_std().into()),
+        kernel_version,
+        cmdline: deployment_kargs(deployment),
+        soft_reboot_capable: booted_deployment.is_some_and(|b| soft_reboot_capable(b, deployment)),
+        etc_drift,
+        kargs,
+        bound_images,
+        diff: None,
+    })
+}
+
+/// Convert the host status to version 2, adding more details about each boot entry.
+fn host_v2(
+    sysroot: &Storage,
+    booted_deployment: Option<&ostree::Deployment>,
+    deployments: &Deployments,
+    host: Host,
+) -> Result<v2::Host> {
+    let status = host.status;
+    let facts = &HostFacts::running();
+    let entry = |d: Option<&ostree::Deployment>, entry: Option<BootEntry>| {
+        d.zip(entry)
+            .map(|(d, entry)| boot_entry_v2(sysroot, d, booted_deployment, facts, entry))
+            .transpose()
+    };
+    let mut r = v2::Host::new(host.spec);
+    r.status = v2::HostStatus {
+        staged: entry(deployments.staged.as_ref(), status.staged)?,
+        booted: entry(booted_deployment, status.booted)?,
+        rollback: entry(deployments.rollback.as_ref(), status.rollback)?,
+        rollback_queued: status.rollback_queued,
+        ty: status.ty,
+    };
+    Ok(r)
+}
+
 impl BootEntry {
     /// Given a boot entry, find its underlying ostree container image
     pub(crate) fn query_image(
@@ -205,8 +466,10 @@ pub(crate) fn get_status(
     let (mut related_deployments, other_deployments) = sysroot
         .deployments()
         .into_iter()
-        .partition::<VecDeque<_>, _>(|d| Some(d.osname()) == stateroot);
-    let staged = related_deployments
+        // A staged deployment is always queued for the next boot, even in another
+        // stateroot (e.g. after a factory reset).
+        .partition::<VecDeque<_>, _>(|d| Some(d.osname()) == stateroot || d.is_staged());
+    let mut staged = related_deployments
         .iter()
         .position(|d| d.is_staged())
         .map(|i| related_deployments.remove(i).unwrap());
@@ -214,6 +477,15 @@ pub(crate) fn get_status(
     // Filter out the booted, the caller already found that
     if let Some(booted) = booted_deployment.as_ref() {
         related_deployments.retain(|f| !f.equal(booted));
+        // A deployment created by `bootc config reset` is queued for the next boot
+        // like a staged deployment; it is not a rollback.
+        let pending_reset = match related_deployments.front() {
+            Some(d) if staged.is_none() => crate::deploy::is_pending_etc_reset(d, booted)?,
+            _ => false,
+        };
+        if pending_reset {
+            staged = related_deployments.pop_front();
+        }
     }
     let rollback = related_deployments.pop_front();
     let rollback_queued = match (booted_deployment.as_ref(), rollback.as_ref()) {
@@ -253,14 +525,25 @@ pub(crate) fn get_status(
         .map(|d| boot_entry_from_deployment(sysroot, d))
         .transpose()
         .context("Rollback deployment")?;
-    let spec = staged
+    let spec = deployments
+        .staged
         .as_ref()
-        .or(booted.as_ref())
-        .and_then(|entry| entry.image.as_ref())
-        .map(|img| HostSpec {
-            image: Some(img.image.clone()),
-            boot_order,
+        .zip(staged.as_ref())
+        .or(booted_deployment.zip(booted.as_ref()))
+        .and_then(|(d, entry)| Some((d, entry.image.as_ref()?)))
+        .map(|(d, img)| -> Result<_> {
+            let kargs = d
+                .origin()
+                .map(|origin| crate::kargs::local_kargs_from_origin(&origin))
+                .transpose()?
+                .unwrap_or_default();
+            Ok(HostSpec {
+                image: Some(img.image.clone()),
+                boot_order,
+                kargs,
+            })
         })
+        .transpose()?
         .unwrap_or_default();
 
     let ty = if booted
@@ -285,28 +568,165 @@ pub(crate) fn get_status(
     Ok((deployments, host))
 }
 
+/// The host status in one of the supported format versions.
+#[derive(Debug, PartialEq, Serialize)]
+#[serde(untagged)]
+enum VersionedHost {
+    V1(Host),
+    V2(Box<v2::Host>),
+}
+
+impl VersionedHost {
+    /// The status of a host which is not booted via ostree.
+    fn new_default(v2: bool) -> Self {
+        if v2 {
+            Self::V2(Default::default())
+        } else {
+            Self::V1(Default::default())
+        }
+    }
+
+    /// Return the host status in version 2; this is used for the human readable
+    /// output, which is the same for all versions. Boot entries of version 1 have
+    /// none of the version 2 details.
+    fn v2(&self) -> Cow<'_, v2::Host> {
+        match self {
+            Self::V1(host) => {
+                let entry = |e: &Option<BootEntry>| {
+                    e.clone().map(|entry| v2::BootEntry {
+                        entry,
+                        stateroot: String::new(),
+                        deploy_index: 0,
+                        timestamp: None,
+                        kernel_version: None,
+                        cmdline: Vec::new(),
+                        soft_reboot_capable: false,
+                        etc_drift: None,
+                        kargs: None,
+                        bound_images: Vec::new(),
+                        diff: None,
+                    })
+                };
+                let status = &host.status;
+                let mut r = v2::Host::new(host.spec.clone());
+                r.status = v2::HostStatus {
+                    staged: entry(&status.staged),
+                    booted: entry(&status.booted),
+                    rollback: entry(&status.rollback),
+                    rollback_queued: status.rollback_queued,
+                    ty: status.ty.clone(),
+                };
+                Cow::Owned(r)
+            }
+            Self::V2(host) => Cow::Borrowed(host),
+        }
+    }
+}
+
+/// Load the current host status; if `v2` is set, the status is in version 2.
+/// If `diff` is also set, the changes of the staged deployment are computed.
+async fn get_host(diff: bool, v2: bool) -> Result<VersionedHost> {
+    let sysroot = super::cli::get_storage().await?;
+    let booted_deployment = sysroot.booted_deployment();
+    let (deployments, host) = get_status(&sysroot, booted_deployment.as_ref())?;
+    if !v2 {
+        return Ok(VersionedHost::V1(host));
+    }
+    let mut host = host_v2(&sysroot, booted_deployment.as_ref(), &deployments, host)?;
+    let status = &host.status;
+    if let (true, Some(booted), Some(booted_entry), Some(staged), Some(staged_entry)) = (
+        diff,
+        booted_deployment.as_ref(),
+        status.booted.as_ref(),
+        deployments.staged.as_ref(),
+        status.staged.as_ref(),
+    ) {
+        let d = deployment_diff(
+            &sysroot.repo(),
+            (booted, &booted_entry.entry),
+            (staged, &staged_entry.entry),
+        )?;
+        // SAFETY: We checked this above
+        host.status.staged.as_mut().unwrap().diff = Some(d);
+    }
+    Ok(VersionedHost::V2(Box::new(host)))
+}
+
+/// Return the modification time of the bootc storage root, which is
+/// bumped on every change via [`Storage::update_mtime`].
+fn storage_mtime() -> Result<Option<cap_std_ext::cap_std::time::SystemTime>> {
+    let sysroot = Dir::open_ambient_dir("/sysroot", cap_std_ext::cap_std::ambient_authority())?;
+    let meta = sysroot.metadata_optional(crate::store::BOOTC_ROOT)?;
+    meta.map(|m| m.modified()).transpose().map_err(Into::into)
+}
+
+/// Write the host status in the provided format. If `stream` is set, the output
+/// is delimited such that multiple documents can be written.
+fn write_host(
+    mut out: impl Write,
+    format: &OutputFormat,
+    host: &VersionedHost,
+    stream: bool,
+) -> Result<()> {
+    match format {
+        OutputFormat::Json => {
+            serde_json::to_writer(&mut out, host)?;
+            if stream {
+                writeln!(out)?;
+            }
+        }
+        OutputFormat::Yaml => {
+            if stream {
+                writeln!(out, "---")?;
+            }
+            serde_yaml::to_writer(&mut out, host)?;
+        }
+        OutputFormat::HumanReadable => {
+            human_readable_output(&mut out, &host.v2())?;
+            if stream {
+                writeln!(out)?;
+            }
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// Implementation of `bootc status --watch`: output the host status, and again
+/// each time it changes.
+async fn watch(format: OutputFormat, diff: bool, v2: bool) -> Result<()> {
+    anyhow::ensure!(ostree_booted()?, "--watch requires a booted host system");
+    let mut last_mtime = None;
+    let mut last_host = None;
+    loop {
+        // Query the mtime before the status, so we can't miss a change
+        let mtime = storage_mtime()?;
+        if last_mtime.as_ref() != Some(&mtime) {
+            last_mtime = Some(mtime);
+            // Note that the sysroot lock is dropped again before waiting
+            let host = get_host(diff, v2).await?;
+            if last_host.as_ref() != Some(&host) {
+                write_host(std::io::stdout().lock(), &format, &host, true)
+                    .context("Writing to stdout")?;
+                last_host = Some(host);
+            }
+        }
+        tokio::time::sleep(WATCH_INTERVAL).await;
+    }
+}
+
 /// Implementation of the `bootc status` CLI command.
 #[context("Status")]
 pub(crate) async fn status(opts: super::cli::StatusOpts) -> Result<()> {
-    match opts.format_version.unwrap_or_default() {
+    let v2 = match opts.format_version.unwrap_or_default() {
         // For historical reasons, both 0 and 1 mean "v1".
-        0 | 1 => {}
+        0 | 1 => false,
+        2 => true,
         o => anyhow::bail!("Unsupported format version: {o}"),
     };
-    let host = if !ostree_booted()? {
-        Default::default()
-    } else {
-        let sysroot = super::cli::get_storage().await?;
-        let booted_deployment = sysroot.booted_deployment();
-        let (_deployments, host) = get_status(&sysroot, booted_deployment.as_ref())?;
-        host
-    };
-
     // If we're in JSON mode, then convert the ostree data into Rust-native
     // structures that can be serialized.
     // Filter to just the serializable status structures.
-    let out = std::io::stdout();
-    let mut out = out.lock();
     let legacy_opt = if opts.json {
         OutputFormat::Json
     } else if std::io::stdout().is_terminal() {
@@ -315,12 +735,28 @@ pub(crate) async fn status(opts: super::cli::StatusOpts) -> Result<()> {
         OutputFormat::Yaml
     };
     let format = opts.format.unwrap_or(legacy_opt);
-    match format {
-        OutputFormat::Json => serde_json::to_writer(&mut out, &host).map_err(anyhow::Error::new),
-        OutputFormat::Yaml => serde_yaml::to_writer(&mut out, &host).map_err(anyhow::Error::new),
-        OutputFormat::HumanReadable => human_readable_output(&mut out, &host),
+    // The human readable output includes details only available in version 2
+    let v2 = v2 || format == OutputFormat::HumanReadable;
+    anyhow::ensure!(
+        v2 || !opts.diff,
+        "--diff requires --format-version=2 for this output format"
+    );
+    if opts.watch {
+        return watch(format, opts.diff, v2).await;
+    }
+    if opts.history {
+        return crate::history::print(&format).await;
     }
-    .context("Writing to stdout")?;
+
+    let host = if !ostree_booted()? {
+        VersionedHost::new_default(v2)
+    } else {
+        get_host(opts.diff, v2).await?
+    };
+
+    let out = std::io::stdout();
+    let out = out.lock();
+    write_host(out, &format, &host, false).context("Writing to stdout")?;
 
     Ok(())
 }
@@ -357,6 +793,7 @@ fn human_render_imagestatus(
     mut out: impl Write,
     slot: Slot,
     image: &crate::spec::ImageStatus,
+    entry: &v2::BootEntry,
 ) -> Result<()> {
     let transport = &image.image.transport;
     let imagename = &image.image.image;
@@ -394,6 +831,82 @@ fn human_render_imagestatus(
         writeln!(out, "{timestamp}")?;
     }
 
+    if let Some(kargs) = entry.kargs.as_ref().filter(|k| !k.files.is_empty()) {
+        write_row_name(&mut out, "Kargs.d", prefix_len)?;
+        for (i, f) in kargs.files.iter().enumerate() {
+            if i > 0 {
+                // Align with the value column
+                write!(out, "{:width$}", "", width = prefix_len + 2)?;
+            }
+            let state = if f.matched { "applied" } else { "skipped" };
+            writeln!(out, "{}: {state} ({})", f.name, f.reason)?;
+        }
+    }
+
+    if !entry.bound_images.is_empty() {
+        write_row_name(&mut out, "Bound images", prefix_len)?;
+        for (i, img) in entry.bound_images.iter().enumerate() {
+            if i > 0 {
+                // Align with the value column
+                write!(out, "{:width$}", "", width = prefix_len + 2)?;
+            }
+            let digest = img.digest.as_deref().unwrap_or("not present");
+            writeln!(out, "{} ({digest})", img.image)?;
+        }
+    }
+
+    if let Some(diff) = entry.diff.as_ref() {
+        human_render_diff(&mut out, diff, prefix_len)?;
+    }
+
+    Ok(())
+}
+
+/// Render the names in a diff, prefixed by `+`, `-` or `~` for changed.
+fn write_names_diff(mut out: impl Write, diff: &NamesDiff) -> Result<()> {
+    let names = diff
+        .added
+        .iter()
+        .map(|n| format!("+{n}"))
+        .chain(diff.removed.iter().map(|n| format!("-{n}")))
+        .chain(diff.changed.iter().map(|n| format!("~{n}")))
+        .collect::<Vec<_>>();
+    if names.is_empty() {
+        writeln!(out, "unchanged")?;
+    } else {
+        writeln!(out, "{}", names.join(" "))?;
+    }
+    Ok(())
+}
+
+fn human_render_diff(mut out: impl Write, diff: &BootEntryDiff, prefix_len: usize) -> Result<()> {
+    if let Some(layers) = diff.layers.as_ref() {
+        write_row_name(&mut out, "Layers", prefix_len)?;
+        writeln!(
+            out,
+            "{} added ({}), {} removed ({}), {} total ({})",
+            layers.added,
+            glib::format_size(layers.added_size),
+            layers.removed,
+            glib::format_size(layers.removed_size),
+            layers.total,
+            glib::format_size(layers.total_size)
+        )?;
+    }
+    if let Some(components) = diff.components.as_ref() {
+        write_row_name(&mut out, "Components", prefix_len)?;
+        write_names_diff(&mut out, components)?;
+    }
+    write_row_name(&mut out, "Kargs", prefix_len)?;
+    write_names_diff(&mut out, &diff.kargs)?;
+    write_row_name(&mut out, "/usr", prefix_len)?;
+    writeln!(
+        out,
+        "{} added, {} removed, {} changed",
+        diff.usr.added.len(),
+        diff.usr.removed.len(),
+        diff.usr.changed.len()
+    )?;
     Ok(())
 }
 
@@ -411,7 +924,7 @@ fn human_render_ostree(mut out: impl Write, slot: Slot, ostree_commit: &str) ->
     Ok(())
 }
 
-fn human_readable_output_booted(mut out: impl Write, host: &Host) -> Result<()> {
+fn human_readable_output_booted(mut out: impl Write, host: &v2::Host) -> Result<()> {
     let mut first = true;
     for (slot_name, status) in [
         (Slot::Staged, &host.status.staged),
@@ -424,9 +937,9 @@ fn human_readable_output_booted(mut out: impl Write, host: &Host) -> Result<()>
             } else {
                 writeln!(out)?;
             }
-            if let Some(image) = &host_status.image {
-                human_render_imagestatus(&mut out, slot_name, image)?;
-            } else if let Some(ostree) = host_status.ostree.as_ref() {
+            if let Some(image) = &host_status.entry.image {
+                human_render_imagestatus(&mut out, slot_name, image, host_status)?;
+            } else if let Some(ostree) = host_status.entry.ostree.as_ref() {
                 human_render_ostree(&mut out, slot_name, &ostree.checksum)?;
             } else {
                 writeln!(out, "Current {slot_name} state is unknown")?;
@@ -437,7 +950,7 @@ fn human_readable_output_booted(mut out: impl Write, host: &Host) -> Result<()>
 }
 
 /// Implementation of rendering our host structure in a "human readable" way.
-fn human_readable_output(mut out: impl Write, host: &Host) -> Result<()> {
+fn human_readable_output(mut out: impl Write, host: &v2::Host) -> Result<()> {
     if host.status.booted.is_some() {
         human_readable_output_booted(out, host)?;
     } else {
@@ -453,11 +966,205 @@ mod tests {
     fn human_status_from_spec_fixture(spec_fixture: &str) -> Result<String> {
         let host: Host = serde_yaml::from_str(spec_fixture).unwrap();
         let mut w = Vec::new();
+        human_readable_output(&mut w, &VersionedHost::V1(host).v2()).unwrap();
+        let w = String::from_utf8(w).unwrap();
+        Ok(w)
+    }
+
+    fn human_status_from_v2_fixture(spec_fixture: &str) -> Result<String> {
+        let host: v2::Host = serde_yaml::from_str(spec_fixture).unwrap();
+        let mut w = Vec::new();
         human_readable_output(&mut w, &host).unwrap();
         let w = String::from_utf8(w).unwrap();
         Ok(w)
     }
 
+    fn manifest_with_components(layers: &[(char, &str)]) -> oci_spec::image::ImageManifest {
+        let layers = layers
+            .iter()
+            .map(|(c, components)| {
+                serde_json::json!({
+                    "mediaType": "application/vnd.oci.image.layer.v1.tar+gzip",
+                    "digest": format!("sha256:{}", c.to_string().repeat(64)),
+                    "size": 10,
+                    "annotations": { "ostree.components": components },
+                })
+            })
+            .collect::<Vec<_>>();
+        serde_json::from_value(serde_json::json!({
+            "schemaVersion": 2,
+            "mediaType": "application/vnd.oci.image.manifest.v1+json",
+            "config": {
+                "mediaType": "application/vnd.oci.image.config.v1+json",
+                "digest": format!("sha256:{}", "0".repeat(64)),
+                "size": 1,
+            },
+            "layers": layers,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_components_diff() {
+        let from = manifest_with_components(&[('a', "glibc,bash"), ('b', "kernel")]);
+        let to = manifest_with_components(&[('a', "glibc,bash"), ('c', "kernel,podman")]);
+        assert_eq!(
+            components_diff(&from, &to),
+            Some(NamesDiff {
+                added: vec!["podman".into()],
+                removed: Vec::new(),
+                changed: vec!["kernel".into()],
+            })
+        );
+        let to = manifest_with_components(&[('a', "glibc")]);
+        assert_eq!(
+            components_diff(&from, &to),
+            Some(NamesDiff {
+                added: Vec::new(),
+                removed: vec!["bash".into(), "kernel".into()],
+                changed: Vec::new(),
+            })
+        );
+        let unannotated: oci_spec::image::ImageManifest =
+            serde_json::from_value(serde_json::json!({
+                "schemaVersion": 2,
+                "config": {
+                    "mediaType": "application/vnd.oci.image.config.v1+json",
+                    "digest": format!("sha256:{}", "0".repeat(64)),
+                    "size": 1,
+                },
+                "layers": [],
+            }))
+            .unwrap();
+        assert_eq!(components_diff(&unannotated, &unannotated), None);
+    }
+
+    #[test]
+    fn test_kargs_diff() {
+        let from = ["quiet".to_owned(), "console=tty0".to_owned()];
+        let to = ["console=ttyS0".to_owned(), "quiet".to_owned()];
+        assert_eq!(
+            kargs_diff(&from, &to),
+            NamesDiff {
+                added: vec!["console=ttyS0".into()],
+                removed: vec!["console=tty0".into()],
+                changed: Vec::new(),
+            }
+        );
+        let from = [
+            "ostree=/ostree/boot.1/default/abc/0".to_owned(),
+            "quiet".to_owned(),
+        ];
+        let to = [
+            "ostree=/ostree/boot.0/default/def/0".to_owned(),
+            "quiet".to_owned(),
+        ];
+        assert_eq!(kargs_diff(&from, &to), NamesDiff::default());
+    }
+
+    #[test]
+    fn test_human_readable_diff() -> Result<()> {
+        let host: Host = serde_yaml::from_str(include_str!("fixtures/spec-staged-booted.yaml"))?;
+        let mut host = VersionedHost::V1(host).v2().into_owned();
+        host.status.staged.as_mut().unwrap().diff = Some(BootEntryDiff {
+            layers: Some(LayersDiff {
+                total: 10,
+                total_size: 1000,
+                added: 2,
+                added_size: 200,
+                removed: 1,
+                removed_size: 100,
+            }),
+            components: Some(NamesDiff {
+                added: vec!["podman".into()],
+                removed: Vec::new(),
+                changed: vec!["kernel".into()],
+            }),
+            kargs: Default::default(),
+            usr: NamesDiff {
+                added: vec!["/usr/bin/podman".into()],
+                removed: Vec::new(),
+                changed: vec!["/usr/lib/modules/".into(), "/usr/bin/bash".into()],
+            },
+        });
+        let mut w = Vec::new();
+        human_readable_output(&mut w, &host)?;
+        let w = String::from_utf8(w)?;
+        let expected = indoc::indoc! { r"
+            Staged image: quay.io/example/someimage:latest
+                  Digest: sha256:16dc2b6256b4ff0d2ec18d2dbfb06d117904010c8cf9732cdb022818cf7a7566
+                 Version: nightly (2023-10-14 19:22:15 UTC)
+                  Layers: 2 added (200 bytes), 1 removed (100 bytes), 10 total (1.0 kB)
+              Components: +podman ~kernel
+                   Kargs: unchanged
+                    /usr: 1 added, 0 removed, 2 changed
+        
+          ● Booted image: quay.io/example/someimage:latest
+                  Digest: sha256:736b359467c9437c1ac915acaae952aad854e07eb4a16a94999a48af08c83c34
+                 Version: nightly (2023-09-30 19:22:16 UTC)
+        "};
+        similar_asserts::assert_eq!(w, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_host_stream() -> Result<()> {
+        let host: Host = serde_yaml::from_str(include_str!("fixtures/spec-staged-booted.yaml"))?;
+        let versioned = VersionedHost::V1(host.clone());
+        let mut w = Vec::new();
+        for _ in 0..2 {
+            write_host(&mut w, &OutputFormat::Json, &versioned, true)?;
+        }
+        let w = String::from_utf8(w)?;
+        let docs = w
+            .lines()
+            .map(serde_json::from_str)
+            .collect::<serde_json::Result<Vec<Host>>>()?;
+        assert_eq!(docs, [host.clone(), host.clone()]);
+
+        let mut w = Vec::new();
+        write_host(&mut w, &OutputFormat::Yaml, &versioned, true)?;
+        let w = String::from_utf8(w)?;
+        assert!(w.starts_with("---\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_versioned_host() -> Result<()> {
+        // The v1 output matches the baseline, even if the boot entries have
+        // details which are only part of version 2
+        let host: Host = serde_yaml::from_str(include_str!("fixtures/spec-staged-booted.yaml"))?;
+        let mut v2 = VersionedHost::V1(host.clone()).v2().into_owned();
+        for entry in [&mut v2.status.staged, &mut v2.status.booted] {
+            let entry = entry.as_mut().unwrap();
+            entry.kargs = Some(BootEntryKargs {
+                image: vec!["console=ttyS0".into()],
+                ..Default::default()
+            });
+            entry.bound_images = vec![crate::spec::BoundImageStatus {
+                image: "quay.io/example/app:latest".into(),
+                digest: None,
+            }];
+        }
+        v2.status.staged.as_mut().unwrap().diff = Some(BootEntryDiff::default());
+        let v2_json = serde_json::to_value(VersionedHost::V2(Box::new(v2.clone())))?;
+        let staged = &v2_json["status"]["staged"];
+        for k in ["kargs", "boundImages", "diff"] {
+            assert!(staged.get(k).is_some(), "{k}");
+        }
+
+        let entry = |e: &Option<v2::BootEntry>| e.as_ref().map(|e| e.entry.clone());
+        let mut v1 = host;
+        v1.status.staged = entry(&v2.status.staged);
+        v1.status.booted = entry(&v2.status.booted);
+        let mut w = Vec::new();
+        write_host(&mut w, &OutputFormat::Json, &VersionedHost::V1(v1), false)?;
+        let baseline: serde_json::Value =
+            serde_json::from_str(include_str!("fixtures/spec-staged-booted-v1.json"))?;
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(&w)?, baseline);
+        Ok(())
+    }
+
     #[test]
     fn test_human_readable_base_spec() {
         // Tests Staged and Booted, null Rollback
@@ -521,6 +1228,35 @@ mod tests {
         similar_asserts::assert_eq!(w, expected);
     }
 
+    #[test]
+    fn test_human_readable_kargsd() {
+        let w = human_status_from_v2_fixture(include_str!("fixtures/spec-v2-booted-kargsd.yaml"))
+            .expect("No spec found");
+        let expected = indoc::indoc! { r"
+          ● Booted image: quay.io/centos-bootc/centos-bootc:stream9
+                  Digest: sha256:47e5ed613a970b6574bfa954ab25bb6e85656552899aa518b5961d9645102b38
+                 Version: stream9.20240807.0
+                 Kargs.d: 10-console.toml: applied (unconditional)
+                          20-nvidia.toml: skipped (no PCI device matching: 10de:*)
+        "};
+        similar_asserts::assert_eq!(w, expected);
+    }
+
+    #[test]
+    fn test_human_readable_bound_images() {
+        let w =
+            human_status_from_v2_fixture(include_str!("fixtures/spec-v2-booted-bound-images.yaml"))
+                .expect("No spec found");
+        let expected = indoc::indoc! { r"
+          ● Booted image: quay.io/centos-bootc/centos-bootc:stream9
+                  Digest: sha256:47e5ed613a970b6574bfa954ab25bb6e85656552899aa518b5961d9645102b38
+                 Version: stream9.20240807.0
+            Bound images: quay.io/example/app:latest (sha256:0e5c1b2a7d3f4e6a8b9c0d1e2f3a4b5c6d7e8f9a0b1c2d3e4f5a6b7c8d9e0f1a)
+                          quay.io/example/db:16 (not present)
+        "};
+        similar_asserts::assert_eq!(w, expected);
+    }
+
     #[test]
     fn test_human_readable_staged_rollback_spec() {
         // staged/rollback image, no booted
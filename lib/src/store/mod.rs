@@ -89,6 +89,19 @@ impl Storage {
         Ok(self.imgstore.get_or_init(|| imgstore))
     }
 
+    /// Access the image storage if it exists, without initializing it.
+    pub(crate) fn get_imgstore_optional(&self) -> Result<Option<&crate::imgstorage::Storage>> {
+        if let Some(imgstore) = self.imgstore.get() {
+            return Ok(Some(imgstore));
+        }
+        let sysroot_dir = crate::utils::sysroot_dir(&self.sysroot)?;
+        if !crate::imgstorage::Storage::is_initialized(&sysroot_dir)? {
+            return Ok(None);
+        }
+        let imgstore = crate::imgstorage::Storage::open(&sysroot_dir, &self.run)?;
+        Ok(Some(self.imgstore.get_or_init(|| imgstore)))
+    }
+
     /// Update the mtime on the storage root directory
     #[context("Updating storage root mtime")]
     pub(crate) fn update_mtime(&self) -> Result<()> {
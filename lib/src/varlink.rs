@@ -0,0 +1,656 @@
+//! # A local varlink API for bootc
+//!
+//! This implements a small [varlink](https://varlink.org) service exposing the
+//! host status and operations such as upgrading or switching images. It is
+//! intended to be socket activated via `bootc-varlink.socket`, and allows
+//! e.g. unprivileged dashboards to query the status, and management agents to
+//! use a typed API instead of parsing command output.
+//!
+//! Operations are implemented by invoking the `bootc` CLI itself; this avoids
+//! holding the sysroot lock (and a private mount namespace) in the long-running
+//! service. Progress is read via `--progress-fd` and forwarded as streamed replies.
+
+use std::collections::HashMap;
+use std::os::fd::{FromRawFd, OwnedFd};
+use std::os::unix::process::CommandExt;
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use camino::Utf8Path;
+use cap_std_ext::cmdext::CapStdExtCommandExt;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tokio::io::{
+    AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader,
+};
+use tokio::net::{UnixListener, UnixStream};
+
+/// The default path of the varlink socket.
+pub(crate) const DEFAULT_ADDRESS: &str = "/run/bootc/varlink.sock";
+/// The name of the bootc varlink interface.
+const INTERFACE: &str = "org.containers.bootc";
+/// The name of the standard varlink service interface.
+const SERVICE_INTERFACE: &str = "org.varlink.service";
+/// The maximum number of concurrent connections from unprivileged users; further
+/// connections are closed immediately. As each connection handles one call at a time,
+/// this also bounds the number of `bootc` processes spawned. Connections from root
+/// are always accepted, so that they can't be locked out.
+const MAX_CONNECTIONS: usize = 8;
+/// The maximum number of concurrent connections from a single unprivileged user.
+const MAX_CONNECTIONS_PER_UID: usize = 2;
+/// The maximum size of a message, including the terminating NUL byte.
+const MAX_MESSAGE_SIZE: usize = 1024 * 1024;
+/// Close a connection if no call is received for this long.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+/// When socket activated, exit after this long without any connections.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+/// The file descriptor passed to child processes for progress.
+const PROGRESS_FD: i32 = 3;
+/// The first file descriptor passed by systemd socket activation.
+const LISTEN_FDS_START: i32 = 3;
+
+/// The description of the bootc interface, in the varlink interface definition language.
+const INTERFACE_DESCRIPTION: &str = r#"# Manage a bootc host system.
+#
+# The host status is the same as output by `bootc status --format=json`,
+# and progress events are as output by `--progress-fd`.
+interface org.containers.bootc
+
+# Return the status of the host.
+method GetStatus() -> (host: object)
+
+# Check if an update is available, without downloading it.
+method Check() -> (host: object)
+
+# Download and stage an update. When called with `more`, progress
+# events are streamed; the final reply contains the new host status.
+method Upgrade(apply: ?bool) -> (event: ?object, host: ?object)
+
+# Target a new container image reference to boot.
+method Switch(image: string, transport: ?string, apply: ?bool) -> (event: ?object, host: ?object)
+
+# Change the bootloader entry ordering, making the rollback deployment the default.
+method Rollback() -> (host: object)
+
+# Apply a new host specification, as used by `bootc edit`.
+method Edit(host: object) -> (host: object)
+
+# The caller is not authorized to invoke the method.
+error PermissionDenied (method: string)
+
+# The operation failed.
+error OperationFailed (message: string)
+"#;
+
+/// A method call.
+#[derive(Debug, Deserialize)]
+struct Call {
+    method: String,
+    #[serde(default)]
+    parameters: Map<String, Value>,
+    #[serde(default)]
+    more: bool,
+    #[serde(default)]
+    oneway: bool,
+}
+
+/// A reply to a method call.
+#[derive(Debug, Serialize, PartialEq)]
+struct Reply {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    parameters: Value,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    continues: bool,
+}
+
+/// An error returned to the caller.
+#[derive(Debug, PartialEq)]
+struct MethodError {
+    error: String,
+    parameters: Value,
+}
+
+impl MethodError {
+    fn new(error: &str, parameters: Value) -> Self {
+        Self {
+            error: error.to_owned(),
+            parameters,
+        }
+    }
+
+    fn failed(e: anyhow::Error) -> Self {
+        Self::new(
+            &format!("{INTERFACE}.OperationFailed"),
+            json!({ "message": format!("{e:#}") }),
+        )
+    }
+
+    fn invalid_parameter(parameter: &str) -> Self {
+        Self::new(
+            &format!("{SERVICE_INTERFACE}.InvalidParameter"),
+            json!({ "parameter": parameter }),
+        )
+    }
+}
+
+type MethodResult = std::result::Result<Value, MethodError>;
+
+/// Authorization hook: return true if a peer with the provided user ID may invoke
+/// the method. Querying is allowed for everyone, while operations which change
+/// the system require root. This is the place to consult e.g. polkit in the future.
+fn authorize(method: &str, uid: u32) -> bool {
+    if uid == 0 {
+        return true;
+    }
+    matches!(
+        method,
+        "org.containers.bootc.GetStatus"
+            | "org.varlink.service.GetInfo"
+            | "org.varlink.service.GetInterfaceDescription"
+    )
+}
+
+/// Serialize and send a single message, which is terminated by a NUL byte.
+async fn send_message(w: &mut (impl AsyncWrite + Unpin), reply: &Reply) -> Result<()> {
+    let mut buf = serde_json::to_vec(reply)?;
+    buf.push(0);
+    w.write_all(&buf).await?;
+    w.flush().await?;
+    Ok(())
+}
+
+fn param_str<'a>(
+    params: &'a Map<String, Value>,
+    name: &str,
+) -> Result<Option<&'a str>, MethodError> {
+    match params.get(name) {
+        None | Some(Value::Null) => Ok(None),
+        Some(Value::String(s)) => Ok(Some(s)),
+        Some(_) => Err(MethodError::invalid_parameter(name)),
+    }
+}
+
+fn param_bool(params: &Map<String, Value>, name: &str) -> Result<bool, MethodError> {
+    match params.get(name) {
+        None | Some(Value::Null) => Ok(false),
+        Some(Value::Bool(b)) => Ok(*b),
+        Some(_) => Err(MethodError::invalid_parameter(name)),
+    }
+}
+
+/// Create a command invoking ourself.
+fn new_bootc_cmd() -> std::process::Command {
+    let mut cmd = std::process::Command::new("/proc/self/exe");
+    cmd.arg0("bootc");
+    cmd.stdin(Stdio::null());
+    cmd.stdout(Stdio::null());
+    cmd
+}
+
+/// Run `bootc` with the provided arguments; if `events` is provided, progress
+/// events are forwarded to it as streamed replies.
+async fn run_bootc(
+    args: &[&str],
+    mut events: Option<&mut (impl AsyncWrite + Unpin)>,
+) -> Result<()> {
+    let mut cmd = new_bootc_cmd();
+    cmd.args(args);
+    let (send, recv) = tokio::net::unix::pipe::pipe()?;
+    let send: OwnedFd = send.into_blocking_fd()?;
+    cmd.take_fd_n(Arc::new(send), PROGRESS_FD);
+    cmd.arg(format!("--progress-fd={PROGRESS_FD}"));
+    let mut stderr = tempfile::tempfile()?;
+    cmd.stderr(stderr.try_clone()?);
+    tracing::debug!("exec: {cmd:?}");
+    let mut cmd = tokio::process::Command::from(cmd);
+    let mut child = cmd.spawn()?;
+    // Ensure we don't hold the write side of the pipe open
+    drop(cmd);
+
+    let mut lines = BufReader::new(recv).lines();
+    while let Some(line) = lines.next_line().await? {
+        match serde_json::from_str::<Value>(&line) {
+            Ok(event) => {
+                if let Some(w) = events.as_mut() {
+                    let reply = Reply {
+                        error: None,
+                        parameters: json!({ "event": event }),
+                        continues: true,
+                    };
+                    send_message(w, &reply).await?;
+                }
+            }
+            Err(e) => tracing::warn!("Invalid progress event: {e}"),
+        }
+    }
+    let status = child.wait().await?;
+    if !status.success() {
+        use std::io::{Read, Seek};
+        stderr.seek(std::io::SeekFrom::Start(0))?;
+        let mut buf = String::new();
+        stderr.read_to_string(&mut buf)?;
+        anyhow::bail!("bootc {}: {status}: {}", args.join(" "), buf.trim());
+    }
+    Ok(())
+}
+
+/// Query the host status.
+async fn get_status() -> Result<Value> {
+    let mut cmd = new_bootc_cmd();
+    cmd.args(["status", "--format=json"]);
+    cmd.stdout(Stdio::piped());
+    cmd.stderr(Stdio::piped());
+    let o = tokio::process::Command::from(cmd).output().await?;
+    if !o.status.success() {
+        anyhow::bail!(
+            "Querying status: {}",
+            String::from_utf8_lossy(&o.stderr).trim()
+        );
+    }
+    serde_json::from_slice(&o.stdout).context("Parsing status")
+}
+
+/// Handle a call to the bootc interface; progress events are streamed to the caller
+/// if requested.
+async fn call_bootc(method: &str, call: &Call, w: &mut (impl AsyncWrite + Unpin)) -> MethodResult {
+    let params = &call.parameters;
+    let events = call.more.then_some(w);
+    let r = match method {
+        "GetStatus" => Ok(()),
+        "Check" => run_bootc(&["upgrade", "--check"], events).await,
+        "Upgrade" => {
+            let mut args = vec!["upgrade"];
+            if param_bool(params, "apply")? {
+                args.push("--apply");
+            }
+            run_bootc(&args, events).await
+        }
+        "Switch" => {
+            let image = param_str(params, "image")?
+                .ok_or_else(|| MethodError::invalid_parameter("image"))?;
+            let transport = param_str(params, "transport")?.unwrap_or("registry");
+            let transport = format!("--transport={transport}");
+            let mut args = vec!["switch", transport.as_str()];
+            if param_bool(params, "apply")? {
+                args.push("--apply");
+            }
+            args.push(image);
+            run_bootc(&args, events).await
+        }
+        "Rollback" => {
+            let mut cmd = new_bootc_cmd();
+            cmd.arg("rollback");
+            run_simple(cmd).await
+        }
+        "Edit" => {
+            let host = params
+                .get("host")
+                .filter(|v| v.is_object())
+                .ok_or_else(|| MethodError::invalid_parameter("host"))?;
+            edit(host).await
+        }
+        _ => {
+            return Err(MethodError::new(
+                &format!("{SERVICE_INTERFACE}.MethodNotFound"),
+                json!({ "method": call.method }),
+            ))
+        }
+    };
+    r.map_err(MethodError::failed)?;
+    let host = get_status().await.map_err(MethodError::failed)?;
+    Ok(json!({ "host": host }))
+}
+
+/// Run a `bootc` command without progress.
+async fn run_simple(mut cmd: std::process::Command) -> Result<()> {
+    cmd.stderr(Stdio::piped());
+    let o = tokio::process::Command::from(cmd).output().await?;
+    if !o.status.success() {
+        anyhow::bail!("{}", String::from_utf8_lossy(&o.stderr).trim());
+    }
+    Ok(())
+}
+
+/// Apply a new host specification via `bootc edit`.
+async fn edit(host: &Value) -> Result<()> {
+    let tmpf = tempfile::NamedTempFile::new()?;
+    // JSON is valid YAML, as read by `bootc edit`.
+    serde_json::to_writer(tmpf.as_file(), host)?;
+    let path = tmpf
+        .path()
+        .to_str()
+        .ok_or_else(|| anyhow::anyhow!("Invalid tempfile path"))?;
+    let mut cmd = new_bootc_cmd();
+    cmd.args(["edit", "--quiet", "--filename", path]);
+    run_simple(cmd).await
+}
+
+/// Handle a call to the standard varlink service interface.
+fn call_service(method: &str, call: &Call) -> MethodResult {
+    match method {
+        "GetInfo" => Ok(json!({
+            "vendor": "bootc",
+            "product": "bootc",
+            "version": env!("CARGO_PKG_VERSION"),
+            "url": "https://github.com/containers/bootc",
+            "interfaces": [SERVICE_INTERFACE, INTERFACE],
+        })),
+        "GetInterfaceDescription" => match param_str(&call.parameters, "interface")? {
+            Some(INTERFACE) => Ok(json!({ "description": INTERFACE_DESCRIPTION })),
+            Some(i) => Err(MethodError::new(
+                &format!("{SERVICE_INTERFACE}.InterfaceNotFound"),
+                json!({ "interface": i }),
+            )),
+            None => Err(MethodError::invalid_parameter("interface")),
+        },
+        _ => Err(MethodError::new(
+            &format!("{SERVICE_INTERFACE}.MethodNotFound"),
+            json!({ "method": call.method }),
+        )),
+    }
+}
+
+/// Dispatch a single method call.
+async fn dispatch(call: &Call, uid: u32, w: &mut (impl AsyncWrite + Unpin)) -> MethodResult {
+    let (interface, method) = call
+        .method
+        .rsplit_once('.')
+        .ok_or_else(|| MethodError::invalid_parameter("method"))?;
+    if !authorize(&call.method, uid) {
+        return Err(MethodError::new(
+            &format!("{INTERFACE}.PermissionDenied"),
+            json!({ "method": call.method }),
+        ));
+    }
+    match interface {
+        SERVICE_INTERFACE => call_service(method, call),
+        INTERFACE => call_bootc(method, call, w).await,
+        _ => Err(MethodError::new(
+            &format!("{SERVICE_INTERFACE}.InterfaceNotFound"),
+            json!({ "interface": interface }),
+        )),
+    }
+}
+
+/// Read a NUL terminated message into `buf`, without the terminator; returns false
+/// if the connection was closed.
+async fn read_message(r: &mut (impl AsyncBufRead + Unpin), buf: &mut Vec<u8>) -> Result<bool> {
+    buf.clear();
+    let n = (&mut *r)
+        .take(MAX_MESSAGE_SIZE as u64)
+        .read_until(0, buf)
+        .await?;
+    if n == 0 {
+        return Ok(false);
+    }
+    if buf.pop() != Some(0) {
+        anyhow::ensure!(
+            n < MAX_MESSAGE_SIZE,
+            "Message exceeds {MAX_MESSAGE_SIZE} bytes"
+        );
+        anyhow::bail!("Unterminated message");
+    }
+    Ok(true)
+}
+
+/// Serve method calls from `uid` on a single connection until it is closed.
+async fn handle_connection(stream: UnixStream, uid: u32) -> Result<()> {
+    let (r, mut w) = stream.into_split();
+    let mut r = BufReader::new(r);
+    let mut buf = Vec::new();
+    loop {
+        let Ok(read) = tokio::time::timeout(READ_TIMEOUT, read_message(&mut r, &mut buf)).await
+        else {
+            tracing::debug!("Closing idle connection from uid {uid}");
+            return Ok(());
+        };
+        if !read? {
+            return Ok(());
+        }
+        let call: Call = serde_json::from_slice(&buf).context("Parsing call")?;
+        tracing::debug!("Call from uid {uid}: {}", call.method);
+        let reply = match dispatch(&call, uid, &mut w).await {
+            Ok(parameters) => Reply {
+                error: None,
+                parameters,
+                continues: false,
+            },
+            Err(e) => Reply {
+                error: Some(e.error),
+                parameters: e.parameters,
+                continues: false,
+            },
+        };
+        if !call.oneway {
+            send_message(&mut w, &reply).await?;
+        }
+    }
+}
+
+/// The active connections, per peer uid.
+#[derive(Debug, Default)]
+struct Connections(Mutex<HashMap<u32, usize>>);
+
+impl Connections {
+    /// Register a new connection from `uid`, unless this exceeds [`MAX_CONNECTIONS`]
+    /// or [`MAX_CONNECTIONS_PER_UID`]; connections from root are always accepted.
+    fn try_add(&self, uid: u32) -> bool {
+        let mut active = self.0.lock().unwrap();
+        if uid != 0 {
+            let unprivileged = active
+                .iter()
+                .filter(|(&u, _)| u != 0)
+                .map(|(_, n)| n)
+                .sum::<usize>();
+            let n = active.get(&uid).copied().unwrap_or_default();
+            if unprivileged >= MAX_CONNECTIONS || n >= MAX_CONNECTIONS_PER_UID {
+                return false;
+            }
+        }
+        *active.entry(uid).or_default() += 1;
+        true
+    }
+
+    /// Unregister a connection from `uid`.
+    fn remove(&self, uid: u32) {
+        let mut active = self.0.lock().unwrap();
+        if let Some(n) = active.get_mut(&uid) {
+            *n -= 1;
+            if *n == 0 {
+                active.remove(&uid);
+            }
+        }
+    }
+
+    /// Returns true if there are no active connections.
+    fn is_empty(&self) -> bool {
+        self.0.lock().unwrap().is_empty()
+    }
+}
+
+/// Return the listening socket passed via systemd socket activation, if any.
+fn activated_listener() -> Result<Option<std::os::unix::net::UnixListener>> {
+    let Some(pid) = std::env::var_os("LISTEN_PID") else {
+        return Ok(None);
+    };
+    if pid.to_str().and_then(|p| p.parse::<u32>().ok()) != Some(std::process::id()) {
+        return Ok(None);
+    }
+    let nfds = std::env::var("LISTEN_FDS")
+        .ok()
+        .and_then(|n| n.parse::<u32>().ok())
+        .unwrap_or_default();
+    anyhow::ensure!(nfds == 1, "Expected a single socket, found {nfds}");
+    // SAFETY: systemd passed us ownership of this file descriptor
+    let fd = unsafe { OwnedFd::from_raw_fd(LISTEN_FDS_START) };
+    Ok(Some(fd.into()))
+}
+
+/// Implementation of `bootc internals varlink`: serve the varlink API, either
+/// on the socket passed via systemd socket activation, or on `address`.
+pub(crate) async fn serve(address: Option<&Utf8Path>) -> Result<()> {
+    let (listener, activated) = match (activated_listener()?, address) {
+        (Some(l), None) => {
+            l.set_nonblocking(true)?;
+            (UnixListener::from_std(l)?, true)
+        }
+        (_, address) => {
+            let address = address.unwrap_or(Utf8Path::new(DEFAULT_ADDRESS));
+            if let Some(parent) = address.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let _ = std::fs::remove_file(address);
+            let l = UnixListener::bind(address).with_context(|| format!("Binding {address}"))?;
+            (l, false)
+        }
+    };
+    let connections = Arc::new(Connections::default());
+    loop {
+        let accepted = if activated {
+            match tokio::time::timeout(IDLE_TIMEOUT, listener.accept()).await {
+                Ok(r) => r,
+                Err(_) if connections.is_empty() => {
+                    tracing::debug!("Exiting after being idle");
+                    return Ok(());
+                }
+                Err(_) => continue,
+            }
+        } else {
+            listener.accept().await
+        };
+        let (stream, _) = accepted?;
+        let uid = match stream.peer_cred() {
+            Ok(cred) => cred.uid(),
+            Err(e) => {
+                tracing::warn!("Rejecting varlink connection: {e}");
+                continue;
+            }
+        };
+        if !connections.try_add(uid) {
+            tracing::warn!("Rejecting varlink connection from uid {uid}: too many connections");
+            drop(stream);
+            continue;
+        }
+        let connections = connections.clone();
+        tokio::spawn(async move {
+            if let Err(e) = handle_connection(stream, uid).await {
+                tracing::warn!("varlink connection: {e:#}");
+            }
+            connections.remove(uid);
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_authorize() {
+        assert!(authorize("org.containers.bootc.Upgrade", 0));
+        assert!(authorize("org.containers.bootc.GetStatus", 1000));
+        assert!(authorize("org.varlink.service.GetInfo", 1000));
+        assert!(!authorize("org.containers.bootc.Upgrade", 1000));
+        assert!(!authorize("org.containers.bootc.Check", 1000));
+        assert!(!authorize("org.containers.bootc.Edit", 1000));
+    }
+
+    #[test]
+    fn test_call_service() {
+        let call: Call = serde_json::from_str(
+            r#"{"method": "org.varlink.service.GetInterfaceDescription", "parameters": {"interface": "org.containers.bootc"}}"#,
+        )
+        .unwrap();
+        let r = call_service("GetInterfaceDescription", &call).unwrap();
+        assert_eq!(r["description"], INTERFACE_DESCRIPTION);
+
+        let call: Call =
+            serde_json::from_str(r#"{"method": "org.varlink.service.GetInfo"}"#).unwrap();
+        let r = call_service("GetInfo", &call).unwrap();
+        assert_eq!(r["interfaces"], json!([SERVICE_INTERFACE, INTERFACE]));
+
+        let e = call_service("Nope", &call).unwrap_err();
+        assert_eq!(e.error, "org.varlink.service.MethodNotFound");
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_errors() {
+        let mut out = Vec::new();
+        let call: Call =
+            serde_json::from_str(r#"{"method": "org.containers.bootc.Upgrade"}"#).unwrap();
+        let e = dispatch(&call, 1000, &mut out).await.unwrap_err();
+        assert_eq!(e.error, "org.containers.bootc.PermissionDenied");
+
+        let call: Call = serde_json::from_str(r#"{"method": "org.example.Foo"}"#).unwrap();
+        let e = dispatch(&call, 0, &mut out).await.unwrap_err();
+        assert_eq!(e.error, "org.varlink.service.InterfaceNotFound");
+
+        let call: Call = serde_json::from_str(
+            r#"{"method": "org.containers.bootc.Switch", "parameters": {"image": 42}}"#,
+        )
+        .unwrap();
+        let e = dispatch(&call, 0, &mut out).await.unwrap_err();
+        assert_eq!(e, MethodError::invalid_parameter("image"));
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn test_connections() {
+        let c = Connections::default();
+        assert!(c.try_add(1000));
+        assert!(c.try_add(1000));
+        assert!(!c.try_add(1000));
+        for uid in 1001..1004 {
+            assert!(c.try_add(uid));
+            assert!(c.try_add(uid));
+        }
+        assert!(!c.try_add(1004));
+        // Root is never locked out
+        assert!(c.try_add(0));
+        c.remove(1000);
+        assert!(c.try_add(1004));
+        c.remove(0);
+        for uid in [1000, 1001, 1001, 1002, 1002, 1003, 1003, 1004] {
+            c.remove(uid);
+        }
+        assert!(c.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_read_message() -> Result<()> {
+        let mut buf = Vec::new();
+        let mut r: &[u8] = b"{}\0{\"a\": 1}\0";
+        assert!(read_message(&mut r, &mut buf).await?);
+        assert_eq!(buf, b"{}");
+        assert!(read_message(&mut r, &mut buf).await?);
+        assert_eq!(buf, b"{\"a\": 1}");
+        assert!(!read_message(&mut r, &mut buf).await?);
+
+        let mut r: &[u8] = b"{}";
+        assert!(read_message(&mut r, &mut buf).await.is_err());
+        let big = vec![b' '; MAX_MESSAGE_SIZE + 1];
+        let mut r = big.as_slice();
+        let e = read_message(&mut r, &mut buf).await.unwrap_err();
+        assert!(e.to_string().contains("exceeds"), "{e}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_message() -> Result<()> {
+        let mut out = Vec::new();
+        let reply = Reply {
+            error: None,
+            parameters: json!({ "event": { "type": "Start" } }),
+            continues: true,
+        };
+        send_message(&mut out, &reply).await?;
+        assert_eq!(
+            out,
+            b"{\"parameters\":{\"event\":{\"type\":\"Start\"}},\"continues\":true}\0"
+        );
+        Ok(())
+    }
+}
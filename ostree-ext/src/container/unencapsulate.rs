@@ -183,6 +183,33 @@ pub(crate) async fn join_fetch<T: std::fmt::Debug>(
     }
 }
 
+/// Fetch a single layer of an opened image, and pass the decompressed tar stream
+/// to the provided function, which is run on a blocking worker thread.
+pub async fn fetch_layer_with<T, F>(
+    proxy: &ImageProxy,
+    img: &OpenedImage,
+    manifest: &oci_image::ImageManifest,
+    layer: &oci_image::Descriptor,
+    layer_info: Option<&Vec<containers_image_proxy::ConvertedLayerInfo>>,
+    transport_src: Transport,
+    f: F,
+) -> Result<T>
+where
+    F: FnOnce(&mut dyn Read) -> Result<T> + Send + 'static,
+    T: std::fmt::Debug + Send + 'static,
+{
+    let (blob, driver, media_type) =
+        fetch_layer(proxy, img, manifest, layer, None, layer_info, transport_src).await?;
+    let worker = crate::tokio_util::spawn_blocking_flatten(move || {
+        let blob = tokio_util::io::SyncIoBridge::new(blob);
+        let mut blob = decompressor(&media_type, blob)?;
+        f(&mut blob)
+    });
+    join_fetch(worker, driver)
+        .await
+        .map_err(|e| e.context(format!("Layer {}", layer.digest())))
+}
+
 /// Fetch a container image and import its embedded OSTree commit.
 #[context("Importing {}", imgref)]
 #[instrument(level = "debug", skip(repo))]
@@ -181,6 +181,120 @@ impl SysusersEntry {
     }
 }
 
+/// All sysusers.d directories which we validate
+const SYSUSERS_DIRS: &[&str] = &[SYSUSERSD, "etc/sysusers.d"];
+
+impl SysusersEntry {
+    /// Check a line against the syntax accepted by systemd-sysusers, see `sysusers.d(5)`.
+    /// Unlike [`Self::parse`], this accepts all valid forms (e.g. `u!` lines, or taking
+    /// the ID from the ownership of a file) and rejects unknown entry types.
+    fn validate(s: &str) -> Result<()> {
+        let err = || Error::ParseFailure(s.to_owned());
+        let mut fields = Vec::new();
+        let mut rest = s;
+        while !rest.trim().is_empty() {
+            // An empty quoted string is a valid (empty) field
+            if let Some(next) = rest.trim_start().strip_prefix("\"\"") {
+                fields.push("");
+                rest = next;
+                continue;
+            }
+            // Otherwise this fails for an unterminated quote
+            let (field, next) = Self::next_token(rest).ok_or_else(err)?;
+            fields.push(field);
+            rest = next;
+        }
+        let field = |i: usize| fields.get(i).copied().filter(|&f| f != "-");
+        let is_id = |v: &str| v.parse::<u32>().is_ok();
+        // A numeric ID, or the path of a file whose ownership determines it
+        let is_id_or_path = |v: &str| is_id(v) || v.starts_with('/');
+        // At most type, name, ID, GECOS, home directory and shell
+        let valid = fields.len() <= 6
+            && match fields.first().copied() {
+                Some("u" | "u!") => {
+                    let id_valid = match field(2) {
+                        None => true,
+                        Some(id) if id.starts_with('/') => true,
+                        Some(id) => match id.split_once(':') {
+                            Some((uid, gid)) => is_id(uid) && !gid.is_empty(),
+                            None => is_id(id),
+                        },
+                    };
+                    field(1).is_some() && id_valid
+                }
+                Some("g") => field(1).is_some() && field(2).is_none_or(is_id_or_path),
+                Some("m") => field(1).is_some() && field(2).is_some(),
+                Some("r") => {
+                    let range_valid = field(2).is_some_and(|range| match range.split_once('-') {
+                        Some((start, end)) => is_id(start) && is_id(end),
+                        None => is_id(range),
+                    });
+                    field(1).is_none() && range_valid
+                }
+                _ => false,
+            };
+        if valid {
+            Ok(())
+        } else {
+            Err(err())
+        }
+    }
+}
+
+/// A sysusers.d entry which could not be parsed.
+#[derive(Debug)]
+pub struct InvalidSysusersEntry {
+    /// Path to the sysusers.d fragment, relative to the root
+    pub path: PathBuf,
+    /// The line number in the file (starting from 1)
+    pub line: usize,
+    /// The parse error
+    pub err: Error,
+}
+
+impl std::fmt::Display for InvalidSysusersEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "/{}:{}: {}", self.path.display(), self.line, self.err)
+    }
+}
+
+/// Parse all sysusers.d fragments in the target root (both the vendor ones
+/// in /usr and the local ones in /etc), returning any entries which are invalid.
+pub fn find_invalid_sysusers(rootfs: &Dir) -> Result<Vec<InvalidSysusersEntry>> {
+    let mut r = Vec::new();
+    for dirpath in SYSUSERS_DIRS {
+        let Some(d) = rootfs.open_dir_optional(dirpath)? else {
+            continue;
+        };
+        let d = DirUtf8::from_cap_std(d);
+        for name in d.filenames_sorted()? {
+            let Some("conf") = Utf8Path::new(&name).extension() else {
+                continue;
+            };
+            if !d.symlink_metadata(&name)?.is_file() {
+                continue;
+            }
+            let path = Utf8Path::new(dirpath).join(&name);
+            let f = d.open(&name).map(BufReader::new)?;
+            for (i, line) in f.lines().enumerate() {
+                let line = line?;
+                let line = line.trim();
+                if line.is_empty() || line.starts_with('#') {
+                    continue;
+                }
+                if let Err(err) = SysusersEntry::validate(line) {
+                    r.push(InvalidSysusersEntry {
+                        path: path.clone().into(),
+                        line: i + 1,
+                        err,
+                    });
+                }
+            }
+        }
+    }
+    Ok(r)
+}
+
 /// Read all tmpfiles.d entries in the target directory, and return a mapping
 /// from (file path) => (single tmpfiles.d entry line)
 pub fn read_sysusers(rootfs: &Dir) -> Result<Vec<SysusersEntry>> {
@@ -568,4 +682,48 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_find_invalid_sysusers() -> Result<()> {
+        let root = &newroot()?;
+        assert!(find_invalid_sysusers(root)?.is_empty());
+
+        root.create_dir_all("etc/sysusers.d")?;
+        root.write(
+            "etc/sysusers.d/local.conf",
+            indoc! { r#"
+            # A comment
+            g foo -
+            m foo wheel
+            x bar -
+            u baz notanumber "Baz" -
+            m foo
+            u! locked - "Locked user"
+            u fromfile /usr/bin/foo "Owner of foo"
+            u nogecos -
+            u withgroup 1000:foo
+            g fromfile /usr/bin/foo
+            r - 500-900
+            r - 1000
+            r foo 500-900
+            u "unterminated
+            g foo - - - - extra
+            u emptygecos - ""
+        "#},
+        )?;
+        let r = find_invalid_sysusers(root)?;
+        let r = r.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            r,
+            [
+                "/etc/sysusers.d/local.conf:4: Failed to parse sysusers entry: x bar -",
+                "/etc/sysusers.d/local.conf:5: Failed to parse sysusers entry: u baz notanumber \"Baz\" -",
+                "/etc/sysusers.d/local.conf:6: Failed to parse sysusers entry: m foo",
+                "/etc/sysusers.d/local.conf:14: Failed to parse sysusers entry: r foo 500-900",
+                "/etc/sysusers.d/local.conf:15: Failed to parse sysusers entry: u \"unterminated",
+                "/etc/sysusers.d/local.conf:16: Failed to parse sysusers entry: g foo - - - - extra",
+            ]
+        );
+        Ok(())
+    }
 }
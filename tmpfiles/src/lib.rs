@@ -14,12 +14,19 @@ use camino::Utf8PathBuf;
 use cap_std::fs::MetadataExt;
 use cap_std::fs::{Dir, Permissions, PermissionsExt};
 use cap_std_ext::cap_std;
-use cap_std_ext::dirext::CapStdExtDirExt;
+use cap_std_ext::cap_std::fs_utf8::Dir as DirUtf8;
+use cap_std_ext::dirext::{CapStdExtDirExt, CapStdExtDirExtUtf8};
 use rustix::fs::Mode;
 use rustix::path::Arg;
 use thiserror::Error;
 
 const TMPFILESD: &str = "usr/lib/tmpfiles.d";
+/// All tmpfiles.d directories which we validate
+const TMPFILES_DIRS: &[&str] = &[TMPFILESD, "etc/tmpfiles.d"];
+/// The valid tmpfiles.d entry types
+const TMPFILES_TYPES: &[u8] = b"fFwdDevqQpLcbCxXrRzZtThHaA";
+/// Modifiers which may follow the entry type
+const TMPFILES_TYPE_MODIFIERS: &[u8] = b"+!-=~^";
 /// The path to the file we use for generation
 const BOOTC_GENERATED_PREFIX: &str = "bootc-autogenerated-var";
 
@@ -512,6 +519,104 @@ fn tmpfiles_entry_get_path(line: &str) -> Result<PathBuf> {
     unescape_path(&mut it)
 }
 
+/// Validate a single (non-comment) tmpfiles.d line; this checks the entry type,
+/// that the path is absolute, and the mode (if provided).
+fn validate_tmpfiles_entry(line: &str) -> Result<()> {
+    let err = || Error::MalformedTmpfilesEntry(line.to_string());
+    let mut it = line.as_bytes().iter().copied().peekable();
+    while let Some(_) = it.next_if(|c| c.is_ascii_whitespace()) {}
+    let mut ftype = Vec::new();
+    while let Some(c) = it.next_if(|c| !c.is_ascii_whitespace()) {
+        ftype.push(c);
+    }
+    let Some((ty, modifiers)) = ftype.split_first() else {
+        return Err(err());
+    };
+    if !TMPFILES_TYPES.contains(ty)
+        || !modifiers
+            .iter()
+            .all(|c| TMPFILES_TYPE_MODIFIERS.contains(c))
+    {
+        return Err(err());
+    }
+    while let Some(_) = it.next_if(|c| c.is_ascii_whitespace()) {}
+    let quoted = it.peek() == Some(&b'"');
+    let path = unescape_path(&mut it)?;
+    if quoted && it.next_if_eq(&b'"').is_none() {
+        return Err(err());
+    }
+    // Paths must be absolute, but may also start with a specifier such as %h
+    if !matches!(path.as_os_str().as_bytes().first(), Some(b'/' | b'%')) {
+        return Err(err());
+    }
+    let rest: Vec<u8> = it.collect();
+    let rest = String::from_utf8_lossy(&rest);
+    match rest.split_ascii_whitespace().next() {
+        None | Some("-") => {}
+        Some(mode) => {
+            let mode = mode.trim_start_matches(['~', ':']);
+            if mode.is_empty() || mode.len() > 4 || u32::from_str_radix(mode, 8).is_err() {
+                return Err(err());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A tmpfiles.d entry which could not be parsed.
+#[derive(Debug)]
+pub struct InvalidTmpfilesEntry {
+    /// Path to the tmpfiles.d fragment, relative to the root
+    pub path: PathBuf,
+    /// The line number in the file (starting from 1)
+    pub line: usize,
+    /// The parse error
+    pub err: Error,
+}
+
+impl std::fmt::Display for InvalidTmpfilesEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "/{}:{}: {}", self.path.display(), self.line, self.err)
+    }
+}
+
+/// Parse all tmpfiles.d fragments in the target root (both the vendor ones
+/// in /usr and the local ones in /etc), returning any entries which are invalid.
+pub fn find_invalid_tmpfiles(rootfs: &Dir) -> Result<Vec<InvalidTmpfilesEntry>> {
+    let mut r = Vec::new();
+    for dirpath in TMPFILES_DIRS {
+        let Some(d) = rootfs.open_dir_optional(dirpath)? else {
+            continue;
+        };
+        let d = DirUtf8::from_cap_std(d);
+        for name in d.filenames_sorted()? {
+            if Path::new(&name).extension() != Some(OsStr::new("conf")) {
+                continue;
+            }
+            if !d.symlink_metadata(&name)?.is_file() {
+                continue;
+            }
+            let path = Path::new(dirpath).join(&name);
+            let f = BufReader::new(d.open(&name)?);
+            for (i, line) in f.lines().enumerate() {
+                let line = line?;
+                let trimmed = line.trim();
+                if trimmed.is_empty() || trimmed.starts_with('#') {
+                    continue;
+                }
+                if let Err(err) = validate_tmpfiles_entry(trimmed) {
+                    r.push(InvalidTmpfilesEntry {
+                        path: path.clone(),
+                        line: i + 1,
+                        err,
+                    });
+                }
+            }
+        }
+    }
+    Ok(r)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -536,6 +641,75 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_validate_tmpfiles_entry() {
+        let valid = [
+            "z /dev/kvm          0666 - kvm -",
+            "d /run/lock/lvm 0700 root root -",
+            "a+      /var/lib/tpm2-tss/system/keystore   -    -    -     -           default:group:tss:rwx",
+            "d \"/run/file with spaces/foo\" 0700 root root -",
+            "L+ /etc/mtab - - - - ../proc/self/mounts",
+            "d %t/foo :0755",
+            "f~ /etc/foo ~0644 - - - Zm9vCg==",
+            "r! /var/tmp/foo",
+        ];
+        for input in valid {
+            validate_tmpfiles_entry(input).unwrap();
+        }
+        let invalid = [
+            "y /dev/kvm 0666 - kvm -",
+            "d+y /run/foo 0755",
+            "d run/foo 0755",
+            "d /run/foo 0999",
+            "d /run/foo 07555",
+            "d \"/run/unterminated 0755",
+            "d",
+        ];
+        for input in invalid {
+            assert!(validate_tmpfiles_entry(input).is_err(), "Input: {input}");
+        }
+    }
+
+    #[test]
+    fn test_find_invalid_tmpfiles() -> anyhow::Result<()> {
+        let rootfs = &newroot()?;
+        assert!(find_invalid_tmpfiles(rootfs)?.is_empty());
+        rootfs.write(
+            Path::new(TMPFILESD).join("ok.conf"),
+            "# A comment\n\nd /var/lib/foo 0755 - - -\n",
+        )?;
+        assert!(find_invalid_tmpfiles(rootfs)?.is_empty());
+        rootfs.create_dir_all("etc/tmpfiles.d")?;
+        rootfs.write(
+            "etc/tmpfiles.d/bad.conf",
+            "d /var/lib/bar 0755 - - -\ndd /var/lib/baz\n",
+        )?;
+        // Not a .conf file, so ignored
+        rootfs.write("etc/tmpfiles.d/README", "hello world")?;
+        let r = find_invalid_tmpfiles(rootfs)?;
+        assert_eq!(r.len(), 1);
+        assert_eq!(r[0].path, Path::new("etc/tmpfiles.d/bad.conf"));
+        assert_eq!(r[0].line, 2);
+        assert_eq!(
+            r[0].to_string(),
+            "/etc/tmpfiles.d/bad.conf:2: Malformed tmpfiles.d line dd /var/lib/baz"
+        );
+        // Files are processed in a stable order
+        rootfs.write("etc/tmpfiles.d/aaa.conf", "dd /var/lib/first\n")?;
+        rootfs.write("etc/tmpfiles.d/zzz.conf", "dd /var/lib/last\n")?;
+        let r = find_invalid_tmpfiles(rootfs)?;
+        let paths = r.iter().map(|e| e.path.as_path()).collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                Path::new("etc/tmpfiles.d/aaa.conf"),
+                Path::new("etc/tmpfiles.d/bad.conf"),
+                Path::new("etc/tmpfiles.d/zzz.conf")
+            ]
+        );
+        Ok(())
+    }
+
     fn newroot() -> Result<cap_std_ext::cap_tempfile::TempDir> {
         let root = cap_std_ext::cap_tempfile::tempdir(cap_std::ambient_authority())?;
         root.create_dir_all(TMPFILESD)?;